@@ -0,0 +1,28 @@
+//! Parse time of the classes of the test dex, against reading only the interface ids
+//! of the classes from the class defs, without parsing or resolving them.
+use criterion::{criterion_group, criterion_main, Criterion};
+use dex::DexReader;
+
+fn parse_classes(c: &mut Criterion) {
+    let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+    c.bench_function("classes", |b| {
+        b.iter(|| {
+            for class in dex.classes() {
+                class.expect("cannot parse class");
+            }
+        })
+    });
+    c.bench_function("class_def_interface_ids", |b| {
+        b.iter(|| {
+            for class_def in dex.class_defs() {
+                class_def
+                    .expect("cannot read class def")
+                    .interface_ids(&dex)
+                    .expect("cannot read interfaces");
+            }
+        })
+    });
+}
+
+criterion_group!(benches, parse_classes);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+#![no_main]
+
+use dex::{profile::ArtProfile, DexReader};
+use libfuzzer_sys::fuzz_target;
+
+// Parses the input as an ART profile and as a dex file, then walks all of its strings,
+// classes, members and instructions and runs the analyses on them. Errors are fine,
+// panics and aborts are not.
+fuzz_target!(|data: &[u8]| {
+    let _ = ArtProfile::from_bytes(data);
+    // fix the checksum, else almost all the inputs are rejected by it.
+    let mut data = data.to_vec();
+    if data.len() > 12 {
+        let checksum = adler32::RollingAdler32::from_buffer(&data[12..]).hash();
+        data[8..12].copy_from_slice(&checksum.to_le_bytes());
+    }
+    let dex = match DexReader::new().read(data) {
+        Ok(dex) => dex,
+        Err(_) => return,
+    };
+    for string in dex.strings() {
+        let _ = string;
+    }
+    let _ = dex.dynamic_loading_sites();
+    let _ = dex.reflective_field_access();
+    let _ = dex.export_methods_csv(Vec::new());
+    for class in dex.classes() {
+        let class = match class {
+            Ok(class) => class,
+            Err(_) => continue,
+        };
+        let _ = class.interface_ids(&dex);
+        for method in class.methods() {
+            let _ = method.opcode_set(&dex);
+            let _ = method.disassemble_with_lines(&dex);
+            let _ = method.to_smali(&dex);
+            if let Some(code) = method.code() {
+                for instruction in code.instructions() {
+                    let _ = instruction;
+                }
+                let _ = code.disassemble().count();
+                let _ = code.cfg();
+                let _ = code.liveness();
+                let _ = code.reachable_instructions();
+            }
+        }
+    }
+});
@@ -1,17 +1,19 @@
 //! Structures for Annotations on a `Class`, `Method`, `MethodParams` and `Field`s.
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::ops::Deref;
 use scroll::{ctx, Pread, Uleb128};
-use std::ops::Deref;
 
 use getset::{CopyGetters, Getters};
 
 use crate::{
+    class::ClassId,
     encoded_value::EncodedValue,
     error::Error,
     field::FieldId,
     jtype::{Type, TypeId},
     method::MethodId,
     string::{DexString, StringId},
-    ubyte, uint,
+    ubyte, uint, HashSet,
 };
 
 use num_derive::FromPrimitive;
@@ -19,7 +21,7 @@ use num_traits::FromPrimitive;
 
 /// Contains the type and parameters of an Annotation.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#encoded-annotation)
-#[derive(Debug, Getters, PartialEq)]
+#[derive(Debug, Clone, Getters, PartialEq)]
 #[get = "pub"]
 pub struct EncodedAnnotation {
     /// Type of the annotation. Should be a class type.
@@ -33,6 +35,31 @@ impl EncodedAnnotation {
     pub fn find_element(&self, name: &str) -> Option<&AnnotationElement> {
         self.elements().iter().find(|e| e.name() == name)
     }
+
+    /// Value of the element with the `name`, e.g. `value` for the element of
+    /// `@Retention(RetentionPolicy.RUNTIME)`.
+    pub fn element(&self, name: &str) -> Option<&EncodedValue> {
+        self.find_element(name).map(|element| element.value())
+    }
+
+    /// Adds the type of this annotation and the types of the annotations
+    /// nested in its element values to `types`.
+    pub(crate) fn collect_types(&self, types: &mut HashSet<Type>) {
+        types.insert(self.jtype.clone());
+        for element in self.elements() {
+            collect_value_types(element.value(), types);
+        }
+    }
+}
+
+fn collect_value_types(value: &EncodedValue, types: &mut HashSet<Type>) {
+    match value {
+        EncodedValue::Annotation(annotation) => annotation.collect_types(types),
+        EncodedValue::Array(values) => values
+            .iter()
+            .for_each(|value| collect_value_types(value, types)),
+        _ => {}
+    }
 }
 
 impl Deref for EncodedAnnotation {
@@ -64,7 +91,7 @@ where
 /// Represents a parameter of an annotation. For example, if `@Author(name = "Benjamin Franklin")`, is
 /// the annotation, this structure represents `name = "Benjamin Franklin"`.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#annotation-element)
-#[derive(Debug, Getters, PartialEq)]
+#[derive(Debug, Clone, Getters, PartialEq)]
 #[get = "pub"]
 pub struct AnnotationElement {
     /// Name of the element. Should conform to the syntax defined
@@ -105,7 +132,7 @@ pub enum Visibility {
 
 /// An Annotation along with its visibility.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#annotation-item)
-#[derive(Debug, Getters, CopyGetters)]
+#[derive(Debug, Clone, Getters, CopyGetters)]
 pub struct AnnotationItem {
     /// Visibility of this annotation.
     #[get_copy = "pub"]
@@ -147,9 +174,22 @@ where
     }
 }
 
+/// Item of a `Dex` to which an annotation is attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnnotationTarget {
+    /// Annotation of the class.
+    Class(ClassId),
+    /// Annotation of the field.
+    Field(FieldId),
+    /// Annotation of the method.
+    Method(MethodId),
+    /// Annotation of the parameter of the method at the given index.
+    Parameter(MethodId, usize),
+}
+
 /// List of Annotation Sets. Used for method parameter annotations.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#set-ref-list)
-#[derive(Debug, Default, Getters)]
+#[derive(Debug, Clone, Default, Getters)]
 #[get = "pub"]
 pub struct AnnotationSetRefList {
     annotation_set_list: Vec<AnnotationSetItem>,
@@ -163,6 +203,12 @@ impl Deref for AnnotationSetRefList {
     }
 }
 
+impl AnnotationSetRefList {
+    pub(crate) fn into_inner(self) -> Vec<AnnotationSetItem> {
+        self.annotation_set_list
+    }
+}
+
 impl<'a, S> ctx::TryFromCtx<'a, &super::Dex<S>> for AnnotationSetRefList
 where
     S: AsRef<[u8]>,
@@ -192,7 +238,7 @@ where
 
 /// A set of annotations on an element.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#annotation-set-item)
-#[derive(Debug, Default, Getters)]
+#[derive(Debug, Clone, Default, Getters)]
 #[get = "pub"]
 pub struct AnnotationSetItem {
     annotations: Vec<AnnotationItem>,
@@ -206,6 +252,24 @@ impl Deref for AnnotationSetItem {
     }
 }
 
+impl AnnotationSetItem {
+    /// Finds the annotation with the given type descriptor, e.g.
+    /// `Ljava/lang/annotation/Retention;`.
+    pub fn find_annotation(&self, type_descriptor: &str) -> Option<&AnnotationItem> {
+        self.annotations
+            .iter()
+            .find(|item| item.jtype() == type_descriptor)
+    }
+
+    /// Consumes the set, returning the annotations visible at runtime.
+    pub(crate) fn into_runtime_visible(self) -> impl Iterator<Item = EncodedAnnotation> {
+        self.annotations
+            .into_iter()
+            .filter(|item| item.visibility == Visibility::Runtime)
+            .map(|item| item.annotation)
+    }
+}
+
 impl<'a, S> ctx::TryFromCtx<'a, &super::Dex<S>> for AnnotationSetItem
 where
     S: AsRef<[u8]>,
@@ -0,0 +1,117 @@
+//! Extraction of the dex files of APKs, which are zip archives.
+use std::io::{Read, Seek};
+
+use zip::ZipArchive;
+
+use crate::{error::Error, ubyte, Result};
+
+/// Returns the number of a `classes.dex`, `classes2.dex`, ... entry name, `1` for
+/// `classes.dex`, or `None` if the entry is not a dex file of the application.
+fn dex_number(name: &str) -> Option<usize> {
+    let number = name.strip_prefix("classes")?.strip_suffix(".dex")?;
+    if number.is_empty() {
+        return Some(1);
+    }
+    match number.parse() {
+        Ok(n) if n > 1 && !number.starts_with('0') => Some(n),
+        _ => None,
+    }
+}
+
+/// Extracts the `classes*.dex` entries of the APK, in class loading order. Returns
+/// `Error::MissingDex` if the APK has no dex file.
+pub(crate) fn dex_entries<R: Read + Seek>(reader: R) -> Result<Vec<Vec<ubyte>>> {
+    let mut archive = ZipArchive::new(reader)?;
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let number = match dex_number(entry.name()) {
+            Some(number) => number,
+            None => continue,
+        };
+        // the size comes from the archive, so it isn't used to preallocate the data.
+        // the CRC-32 of the entry is checked once it is read to the end.
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        entries.push((number, data));
+    }
+    if entries.is_empty() {
+        return Err(Error::MissingDex);
+    }
+    entries.sort_by_key(|(number, _)| *number);
+    Ok(entries.into_iter().map(|(_, data)| data).collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::{dex_entries, dex_number};
+
+    /// Builds a zip archive with the entries, deflating the ones flagged.
+    pub(crate) fn zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data, deflate) in entries {
+            let method = if *deflate {
+                CompressionMethod::Deflated
+            } else {
+                CompressionMethod::Stored
+            };
+            writer
+                .start_file(
+                    *name,
+                    SimpleFileOptions::default().compression_method(method),
+                )
+                .expect("cannot add zip entry");
+            writer.write_all(data).expect("cannot write zip entry");
+        }
+        writer.finish().expect("cannot write zip").into_inner()
+    }
+
+    #[test]
+    fn test_dex_number() {
+        assert_eq!(dex_number("classes.dex"), Some(1));
+        assert_eq!(dex_number("classes2.dex"), Some(2));
+        assert_eq!(dex_number("classes12.dex"), Some(12));
+        assert_eq!(dex_number("classes1.dex"), None);
+        assert_eq!(dex_number("classes02.dex"), None);
+        assert_eq!(dex_number("lib/classes.dex"), None);
+        assert_eq!(dex_number("AndroidManifest.xml"), None);
+    }
+
+    #[test]
+    fn test_dex_entries() {
+        let apk = zip(&[
+            ("AndroidManifest.xml", b"manifest", true),
+            ("classes2.dex", b"second", true),
+            ("res/raw/classes3.dex", b"resource", false),
+            ("classes.dex", b"first", false),
+        ]);
+        assert_eq!(
+            dex_entries(Cursor::new(&apk)).expect("error reading APK"),
+            vec![b"first".to_vec(), b"second".to_vec()]
+        );
+
+        let apk = zip(&[("AndroidManifest.xml", b"manifest", false)]);
+        assert!(matches!(
+            dex_entries(Cursor::new(&apk)),
+            Err(crate::Error::MissingDex)
+        ));
+        assert!(dex_entries(Cursor::new(b"not a zip file")).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_entry() {
+        let data = b"stored dex file";
+        let mut apk = zip(&[("classes.dex", data, false)]);
+        // the CRC-32 of the entry doesn't match its data anymore.
+        let offset = apk
+            .windows(data.len())
+            .position(|window| window == data)
+            .expect("no stored data");
+        apk[offset] ^= 0xff;
+        assert!(dex_entries(Cursor::new(&apk)).is_err());
+    }
+}
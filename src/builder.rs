@@ -0,0 +1,343 @@
+//! Re-serialization of a `Dex` with modifications.
+use alloc::{string::ToString, vec::Vec};
+
+use scroll::{Pread, Pwrite, Uleb128};
+
+use crate::{
+    code::DEBUG_INFO_OFFSET,
+    dex::{Dex, ItemType},
+    error::Error,
+    ubyte, uint, ulong, ushort, utils, Result,
+};
+
+/// Offset of `file_size` in the header.
+const FILE_SIZE_OFFSET: usize = 0x20;
+/// Offset of `data_size` in the header.
+const DATA_SIZE_OFFSET: usize = 0x68;
+/// Offsets of the `link_off`, `map_off`, `*_ids_off`, `class_defs_off` and `data_off`
+/// fields of the header.
+const HEADER_OFFSETS: [usize; 9] = [0x30, 0x34, 0x3c, 0x44, 0x4c, 0x54, 0x5c, 0x64, 0x6c];
+/// Size of an entry of the map list.
+const MAP_ITEM_SIZE: usize = 12;
+
+/// New value of a field of the file, at its offset in the original file.
+enum Patch {
+    /// A `uint`.
+    Uint(uint),
+    /// A `uleb128` of the given length in bytes, which is kept.
+    Uleb(usize, uint),
+}
+
+/// Writes the contents of a `Dex` back to bytes, applying the selected options.
+pub struct DexBuilder<'a, T> {
+    dex: &'a Dex<T>,
+    strip_debug_info: bool,
+}
+
+impl<'a, T: AsRef<[u8]>> DexBuilder<'a, T> {
+    /// Builder for the contents of `dex`, without any modification.
+    pub fn new(dex: &'a Dex<T>) -> Self {
+        Self {
+            dex,
+            strip_debug_info: false,
+        }
+    }
+
+    /// Removes the debug info of every method: the `debug_info_off` of the code items
+    /// is set to `0` and the debug info items are dropped from the file. The items
+    /// following them are moved back, keeping their 4-byte alignment, and the offsets
+    /// into them are updated.
+    pub fn strip_debug_info(mut self) -> Self {
+        self.strip_debug_info = true;
+        self
+    }
+
+    /// Returns the contents of the dex file, with the size fields and the checksums
+    /// of the header updated.
+    pub fn build(&self) -> Result<Vec<ubyte>> {
+        let mut contents = if self.strip_debug_info {
+            self.write_stripped_debug_info()?
+        } else {
+            self.dex.source.as_ref().to_vec()
+        };
+        utils::update_checksums(&mut contents, self.dex.get_endian())?;
+        Ok(contents)
+    }
+
+    fn write_stripped_debug_info(&self) -> Result<Vec<ubyte>> {
+        let endian = self.dex.get_endian();
+        let source = self.dex.source.as_ref();
+        let header = self.dex.header();
+        let items = self.dex.map_list().items();
+        let (start, end) = match items
+            .iter()
+            .position(|item| item.item_type() == ItemType::DebugInfoItem)
+        {
+            Some(index) => {
+                let end = items.get(index + 1).ok_or_else(|| {
+                    Error::MalFormed(
+                        "Debug info items must be followed by another map item".to_string(),
+                    )
+                })?;
+                (items[index].offset() as usize, end.offset() as usize)
+            }
+            // No debug info items: only the offsets of the code items are cleared.
+            None => (source.len(), source.len()),
+        };
+        if start > end || end > source.len() {
+            return Err(Error::OutOfBounds {
+                offset: end as ulong,
+                file_size: source.len(),
+            });
+        }
+        // The items following the debug info are moved by a multiple of 4 bytes.
+        let shift = (end - start) & !3;
+        let relocate = |offset: uint| {
+            if offset as usize >= end {
+                offset - shift as uint
+            } else {
+                offset
+            }
+        };
+        let moved = |offset: usize| {
+            if offset >= end {
+                offset - shift
+            } else {
+                offset
+            }
+        };
+        let read = |offset: usize| -> Result<uint> { Ok(source.pread_with(offset, endian)?) };
+
+        let mut patches = Vec::new();
+        let mut offset_at = |offset: usize| -> Result<()> {
+            patches.push((offset, Patch::Uint(relocate(read(offset)?))));
+            Ok(())
+        };
+        for offset in HEADER_OFFSETS.iter() {
+            offset_at(*offset)?;
+        }
+        let string_ids_off = header.string_ids_off() as usize;
+        for index in 0..header.string_ids_size() as usize {
+            offset_at(string_ids_off + index * 4)?;
+        }
+        let proto_ids_off = header.proto_ids_off() as usize;
+        for index in 0..header.proto_ids_size() as usize {
+            // parameters_off
+            offset_at(proto_ids_off + index * 12 + 8)?;
+        }
+        if let Some(call_sites) = self.dex.map_list().get(ItemType::CallSiteIdItem) {
+            for index in 0..call_sites.size() as usize {
+                offset_at(call_sites.offset() as usize + index * 4)?;
+            }
+        }
+        let mut class_data_offs = Vec::new();
+        let class_defs_off = header.class_defs_off() as usize;
+        for index in 0..header.class_defs_size() as usize {
+            let class_def = class_defs_off + index * 32;
+            // interfaces_off, annotations_off, class_data_off and static_values_off
+            for field in [12, 20, 24, 28].iter() {
+                offset_at(class_def + field)?;
+            }
+            class_data_offs.push(read(class_def + 24)? as usize);
+        }
+        for item in items {
+            let mut offset = item.offset() as usize;
+            for _ in 0..item.size() {
+                match item.item_type() {
+                    ItemType::AnnotationSetRefList | ItemType::AnnotationSetItem => {
+                        offset = (offset + 3) & !3;
+                        let size = read(offset)? as usize;
+                        for entry in 0..size {
+                            offset_at(offset + 4 + entry * 4)?;
+                        }
+                        offset += 4 + size * 4;
+                    }
+                    ItemType::AnnotationsDirectoryItem => {
+                        offset = (offset + 3) & !3;
+                        offset_at(offset)?;
+                        let mut size = 0;
+                        for count in 1..4 {
+                            size += read(offset + count * 4)? as usize;
+                        }
+                        for entry in 0..size {
+                            // annotations_off of the field, method or parameter entry
+                            offset_at(offset + 16 + entry * 8 + 4)?;
+                        }
+                        offset += 16 + size * 8;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        for class_data_off in class_data_offs.into_iter().filter(|off| *off != 0) {
+            let mut offset = class_data_off;
+            let offset = &mut offset;
+            let mut sizes = [0; 4];
+            for size in sizes.iter_mut() {
+                *size = Uleb128::read(source, offset)?;
+            }
+            for _ in 0..(sizes[0] + sizes[1]) * 2 {
+                Uleb128::read(source, offset)?;
+            }
+            for _ in 0..sizes[2] + sizes[3] {
+                Uleb128::read(source, offset)?;
+                Uleb128::read(source, offset)?;
+                let code_off_offset = *offset;
+                let code_off = Uleb128::read(source, offset)? as uint;
+                if code_off != 0 {
+                    let len = *offset - code_off_offset;
+                    patches.push((code_off_offset, Patch::Uleb(len, relocate(code_off))));
+                    patches.push((code_off as usize + DEBUG_INFO_OFFSET, Patch::Uint(0)));
+                }
+            }
+        }
+
+        let mut contents = Vec::with_capacity(source.len() - shift);
+        contents.extend_from_slice(&source[..start]);
+        contents.resize(start + (end - start - shift), 0);
+        contents.extend_from_slice(&source[end..]);
+        for (offset, patch) in patches {
+            let offset = moved(offset);
+            match patch {
+                Patch::Uint(value) => {
+                    contents.pwrite_with(value, offset, endian)?;
+                }
+                Patch::Uleb(len, value) => {
+                    // Padded with continuation bytes to keep the length of the value.
+                    for index in 0..len {
+                        let mut byte = (value >> (7 * index)) as ubyte & 0x7f;
+                        if index + 1 < len {
+                            byte |= 0x80;
+                        }
+                        contents[offset + index] = byte;
+                    }
+                }
+            }
+        }
+
+        if start == end {
+            return Ok(contents);
+        }
+        let map_off = relocate(header.map_off()) as usize;
+        let mut entry = map_off + 4;
+        for item in items
+            .iter()
+            .filter(|item| item.item_type() != ItemType::DebugInfoItem)
+        {
+            contents.pwrite_with(item.item_type() as ushort, entry, endian)?;
+            contents.pwrite_with::<ushort>(0, entry + 2, endian)?;
+            contents.pwrite_with(item.size(), entry + 4, endian)?;
+            contents.pwrite_with(relocate(item.offset()), entry + 8, endian)?;
+            entry += MAP_ITEM_SIZE;
+        }
+        contents.pwrite_with(items.len() as uint - 1, map_off, endian)?;
+        let mut data_size = header.data_size() - shift as uint;
+        if entry + MAP_ITEM_SIZE == contents.len() {
+            // The map list ends the file: drop its last entry.
+            contents.truncate(entry);
+            data_size -= MAP_ITEM_SIZE as uint;
+        } else {
+            contents[entry..entry + MAP_ITEM_SIZE]
+                .iter_mut()
+                .for_each(|byte| *byte = 0);
+        }
+        contents.pwrite_with(contents.len() as uint, FILE_SIZE_OFFSET, endian)?;
+        contents.pwrite_with(data_size, DATA_SIZE_OFFSET, endian)?;
+        Ok(contents)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::DexBuilder;
+    use crate::DexReader;
+
+    #[test]
+    fn test_build_unchanged() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let contents = DexBuilder::new(&dex).build().expect("cannot build dex");
+        assert_eq!(
+            contents,
+            std::fs::read("resources/classes.dex").expect("cannot read dex file")
+        );
+    }
+
+    #[test]
+    fn test_strip_debug_info() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let has_debug_info = dex.classes().any(|class| {
+            class
+                .expect("error parsing class")
+                .methods()
+                .any(|method| method.code().is_some_and(|c| c.debug_info_item().is_some()))
+        });
+        assert!(has_debug_info);
+
+        let contents = DexBuilder::new(&dex)
+            .strip_debug_info()
+            .build()
+            .expect("cannot build dex");
+        let stripped = DexReader::new()
+            .read(contents)
+            .expect("cannot read stripped dex");
+        assert_eq!(
+            stripped.header().file_size() as usize,
+            stripped.source.as_ref().len()
+        );
+        // The debug info items are followed by the annotation items, the static values,
+        // the class data and the map list, which ends the fixture and loses an entry.
+        let debug_info = dex
+            .map_list()
+            .items()
+            .windows(2)
+            .find(|items| items[0].item_type() == crate::dex::ItemType::DebugInfoItem)
+            .map(|items| (items[1].offset() - items[0].offset()) & !3)
+            .expect("no debug info items");
+        assert_eq!(
+            dex.header().file_size() - stripped.header().file_size(),
+            debug_info + 12
+        );
+        assert!(stripped
+            .map_list()
+            .get(crate::dex::ItemType::DebugInfoItem)
+            .is_none());
+        assert!(stripped
+            .strings()
+            .map(|string| string.expect("error reading string"))
+            .eq(dex
+                .strings()
+                .map(|string| string.expect("error reading string"))));
+        let mut methods = 0;
+        for (class, original) in stripped.classes().zip(dex.classes()) {
+            let class = class.expect("error parsing class");
+            let original = original.expect("error parsing class");
+            assert_eq!(class.jtype(), original.jtype());
+            assert_eq!(
+                format!("{:?}", class.annotations()),
+                format!("{:?}", original.annotations())
+            );
+            for (field, original) in class.fields().zip(original.fields()) {
+                assert_eq!(
+                    format!("{:?}", field.initial_value()),
+                    format!("{:?}", original.initial_value())
+                );
+            }
+            for (method, original) in class.methods().zip(original.methods()) {
+                assert_eq!(method.name(), original.name());
+                assert_eq!(
+                    format!("{:?}", method.annotations()),
+                    format!("{:?}", original.annotations())
+                );
+                if let Some(code) = method.code() {
+                    assert!(code.debug_info_item().is_none());
+                    assert_eq!(
+                        code.insns(),
+                        original.code().expect("missing code item").insns()
+                    );
+                    methods += 1;
+                }
+            }
+        }
+        assert!(methods > 0);
+    }
+}
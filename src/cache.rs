@@ -1,31 +1,70 @@
-use std::{cell::RefCell, cmp::Eq, hash::Hash, rc::Rc};
+use alloc::rc::Rc;
+use core::{
+    cell::{Cell, RefCell},
+    cmp::Eq,
+    hash::Hash,
+    num::NonZeroUsize,
+};
 
+use getset::CopyGetters;
 use lru::LruCache;
 
+/// Number of lookups in a cache which found the value and which had to compute it, see
+/// `Dex::string_cache_stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct CacheStats {
+    /// Lookups which found the value in the cache.
+    hits: usize,
+    /// Lookups which didn't find the value in the cache.
+    misses: usize,
+}
+
 /// LRU cache that provides interior mutability
 pub(crate) struct Cache<K, V> {
-    inner: Rc<RefCell<LruCache<K, V>>>,
+    /// `None` if the capacity is 0.
+    inner: Rc<RefCell<Option<LruCache<K, V>>>>,
+    stats: Rc<Cell<CacheStats>>,
 }
 
 impl<K: Hash + Eq, V: Clone> Cache<K, V> {
-    /// Get a new instance of cache with the given capacity
+    /// Get a new instance of cache with the given capacity. A cache with a capacity
+    /// of 0 retains nothing.
     pub(crate) fn new(cap: usize) -> Self {
         Self {
-            inner: Rc::new(RefCell::new(LruCache::new(cap))),
+            inner: Rc::new(RefCell::new(NonZeroUsize::new(cap).map(LruCache::new))),
+            stats: Rc::new(Cell::new(CacheStats::default())),
         }
     }
 
     /// Get a reference to the value at key from the cache, if found
     pub(crate) fn get(&self, key: &K) -> Option<V> {
-        self.inner
+        let value = self
+            .inner
             .borrow_mut()
-            .get(key)
-            .map(std::clone::Clone::clone)
+            .as_mut()
+            .and_then(|inner| inner.get(key))
+            .map(core::clone::Clone::clone);
+        let mut stats = self.stats.get();
+        if value.is_some() {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+        self.stats.set(stats);
+        value
     }
 
     /// Insert a new key value pair into the cache
     pub(crate) fn put(&self, key: K, value: V) {
-        self.inner.borrow_mut().put(key, value);
+        if let Some(inner) = self.inner.borrow_mut().as_mut() {
+            inner.put(key, value);
+        }
+    }
+
+    /// Hit and miss counts of the lookups since the cache was created.
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats.get()
     }
 }
 
@@ -33,6 +72,7 @@ impl<K, V> Clone for Cache<K, V> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            stats: self.stats.clone(),
         }
     }
 }
@@ -0,0 +1,179 @@
+//! Call graph of the methods defined in a `Dex`.
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+};
+use core::fmt::Write;
+
+use getset::Getters;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{index::DexIndex, method::MethodId};
+
+/// Directed graph from each method defined in a `Dex` to the methods it invokes.
+/// Methods without code, i.e. abstract and native methods, are nodes without callees.
+#[derive(Debug, Default, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CallGraph {
+    /// Methods invoked by each method defined in the dex.
+    #[get = "pub"]
+    calls: BTreeMap<MethodId, BTreeSet<MethodId>>,
+    /// Methods invoking each method of the graph, built from `calls`.
+    callers: BTreeMap<MethodId, BTreeSet<MethodId>>,
+    /// Name of each method in the graph, as `Lclass;->name`.
+    #[get = "pub"]
+    names: BTreeMap<MethodId, String>,
+}
+
+impl CallGraph {
+    /// Builds the graph from the calls of `index`, adding the methods without code as
+    /// nodes without callees.
+    pub(crate) fn from_index(index: &DexIndex) -> Self {
+        let mut calls = index.calls().clone();
+        for class in index.classes() {
+            for method in class.methods() {
+                calls.entry(*method).or_default();
+            }
+        }
+        let mut callers: BTreeMap<MethodId, BTreeSet<MethodId>> = BTreeMap::new();
+        for (caller, callees) in &calls {
+            for callee in callees {
+                callers.entry(*callee).or_default().insert(*caller);
+            }
+        }
+        let names = calls
+            .iter()
+            .flat_map(|(caller, callees)| core::iter::once(caller).chain(callees.iter()))
+            .filter_map(|method_id| {
+                // `Lclass;->name(params)return` without the prototype.
+                let signature = index.methods().get(*method_id as usize)?;
+                let name = signature.split('(').next().unwrap_or(signature);
+                Some((*method_id, name.to_string()))
+            })
+            .collect();
+        Self {
+            calls,
+            callers,
+            names,
+        }
+    }
+
+    /// Returns the methods invoked by `method_id`.
+    pub fn callees(&self, method_id: MethodId) -> Option<&BTreeSet<MethodId>> {
+        self.calls.get(&method_id)
+    }
+
+    /// Returns the methods invoking `method_id`, `None` if no method invokes it.
+    pub fn callers(&self, method_id: MethodId) -> Option<&BTreeSet<MethodId>> {
+        self.callers.get(&method_id)
+    }
+
+    /// Returns the graph in the Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        for (method_id, name) in &self.names {
+            let _ = writeln!(dot, "  {} [label={:?}];", method_id, name);
+        }
+        for (caller, callees) in &self.calls {
+            for callee in callees {
+                let _ = writeln!(dot, "  {} -> {};", caller, callee);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    #[test]
+    fn test_call_graph() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let graph = dex.call_graph().expect("cannot build call graph");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "setText")
+            .expect("method not found");
+        let names = graph
+            .callees(method.id())
+            .expect("method not in graph")
+            .iter()
+            .map(|callee| graph.names()[callee].as_str())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"Landroid/widget/TextView;->setText"));
+        for callee in graph.callees(method.id()).expect("method not in graph") {
+            assert!(graph
+                .callers(*callee)
+                .expect("callee without callers")
+                .contains(&method.id()));
+        }
+        for (method_id, callers) in &graph.callers {
+            let expected = graph
+                .calls()
+                .iter()
+                .filter(|(_, callees)| callees.contains(method_id))
+                .map(|(caller, _)| *caller)
+                .collect::<Vec<_>>();
+            assert!(callers.iter().copied().eq(expected));
+        }
+        // e.g. the static initializers are never invoked.
+        assert!(graph
+            .calls()
+            .keys()
+            .any(|method_id| graph.callers(*method_id).is_none()));
+
+        let abstract_method = dex
+            .classes()
+            .find_map(|class| {
+                let class = class.expect("error parsing class");
+                let method = class.methods().find(|method| method.code().is_none());
+                method.map(|method| method.id())
+            })
+            .expect("no method without code");
+        assert_eq!(
+            graph.callees(abstract_method).map(|callees| callees.len()),
+            Some(0)
+        );
+        assert_eq!(
+            graph.calls().len(),
+            dex.classes()
+                .map(|class| class.expect("error parsing class").methods().count())
+                .sum::<usize>()
+        );
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph calls {\n"));
+        assert!(dot.contains(&format!(
+            "  {} [label=\"Lcom/devoteam/quickaction/QuickActionItem;->setText\"];\n",
+            method.id()
+        )));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_malformed_code() {
+        let (_file, dex) = crate::dex::tests::patched_dex(|contents| {
+            let dex =
+                crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let code_offset = dex
+                .classes()
+                .flat_map(|class| {
+                    let class = class.expect("error parsing class");
+                    class.methods().map(|m| m.code_offset()).collect::<Vec<_>>()
+                })
+                .find(|offset| *offset != 0)
+                .expect("no method with code") as usize;
+            // the first instruction becomes the unused opcode 0x3e.
+            contents[code_offset + 16] = 0x3e;
+        });
+        // the classes still parse, the code is only decoded by the call graph.
+        assert!(dex.classes().all(|class| class.is_ok()));
+        assert!(dex.call_graph().is_err());
+    }
+}
@@ -0,0 +1,701 @@
+//! Control flow graph of a `Method`'s code.
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    code::TryCatchHandlers,
+    error::Error,
+    instruction::{self, Instruction},
+    int, ushort, Result,
+};
+
+/// A sequence of instructions which is only entered at the first instruction and
+/// only left after the last one.
+#[derive(Debug, Getters, CopyGetters)]
+pub struct BasicBlock {
+    /// Address of the first instruction of the block, in 16-bit code units.
+    #[get_copy = "pub"]
+    start_addr: usize,
+    /// Address right after the last instruction of the block, in 16-bit code units.
+    #[get_copy = "pub"]
+    end_addr: usize,
+    /// Indices of the blocks control can flow to from this block, including the
+    /// exception handlers covering it.
+    #[get = "pub"]
+    successors: Vec<usize>,
+    /// Indices of the blocks control can flow from to this block.
+    #[get = "pub"]
+    predecessors: Vec<usize>,
+    /// Outgoing edges of the block, as the index of the target block along with the
+    /// kind of the edge, in the order of `successors`. A successor reached in several
+    /// ways, e.g. by a branch to the next block, has an edge of each kind.
+    #[get = "pub"]
+    edges: Vec<(usize, EdgeKind)>,
+}
+
+/// How control flows along an edge of a `ControlFlowGraph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction, including when the condition of
+    /// an `if-*` is false or no case of a switch matches.
+    Fallthrough,
+    /// Taken branch of an `if-*` instruction.
+    Branch,
+    /// Target of a `goto` instruction.
+    Goto,
+    /// Case of a `packed-switch` or `sparse-switch`, resolved from its payload.
+    Switch,
+    /// Catch handler of a try block covering the block.
+    Exception,
+}
+
+/// Control flow graph of a `CodeItem`. The first block is the entry block.
+///
+/// Switch and fill-array-data payloads are not part of any block. A block covered by
+/// a try block has an edge to each of its catch handlers.
+#[derive(Debug, Getters)]
+pub struct ControlFlowGraph {
+    /// Basic blocks of the code, in ascending order of their addresses.
+    #[get = "pub"]
+    blocks: Vec<BasicBlock>,
+}
+
+/// An instruction as seen by the control flow graph.
+struct Node {
+    addr: usize,
+    len: usize,
+    /// Addresses of the branch targets of the instruction.
+    targets: Vec<usize>,
+    /// Kind of the edges to `targets`.
+    target_kind: EdgeKind,
+    /// Whether execution can continue with the next instruction.
+    falls_through: bool,
+    /// Whether the instruction ends the block it is in.
+    ends_block: bool,
+    /// Whether this is a payload pseudo-instruction.
+    is_payload: bool,
+}
+
+fn relative_addr(insns: &[ushort], addr: usize, offset: i64) -> Result<usize> {
+    let target = addr as i64 + offset;
+    if target < 0 || target as usize >= insns.len() {
+        return Err(Error::MalFormed(format!(
+            "Branch target {} of instruction at {} out of bounds",
+            target, addr
+        )));
+    }
+    Ok(target as usize)
+}
+
+/// Returns the targets of the switch instruction at `addr` whose payload is at
+/// `payload_offset` relative to it.
+fn switch_targets(insns: &[ushort], addr: usize, payload_offset: int) -> Result<Vec<usize>> {
+    let payload = relative_addr(insns, addr, i64::from(payload_offset))?;
+    let targets = match instruction::decode(insns, payload)?.0 {
+        Instruction::PackedSwitchPayload { targets, .. }
+        | Instruction::SparseSwitchPayload { targets, .. } => targets,
+        other => {
+            return Err(Error::MalFormed(format!(
+                "Expected a switch payload at {}, found {:?}",
+                payload, other
+            )))
+        }
+    };
+    targets
+        .into_iter()
+        .map(|offset| relative_addr(insns, addr, i64::from(offset)))
+        .collect()
+}
+
+fn nodes(insns: &[ushort]) -> Result<Vec<Node>> {
+    let mut nodes = Vec::new();
+    let mut addr = 0;
+    while addr < insns.len() {
+        let (instruction, len) = instruction::decode(insns, addr)?;
+        let (targets, target_kind) = match instruction {
+            Instruction::Goto { offset } => (
+                vec![relative_addr(insns, addr, i64::from(offset))?],
+                EdgeKind::Goto,
+            ),
+            Instruction::If { offset, .. } | Instruction::IfZero { offset, .. } => (
+                vec![relative_addr(insns, addr, i64::from(offset))?],
+                EdgeKind::Branch,
+            ),
+            Instruction::PackedSwitch { payload_offset, .. }
+            | Instruction::SparseSwitch { payload_offset, .. } => (
+                switch_targets(insns, addr, payload_offset)?,
+                EdgeKind::Switch,
+            ),
+            _ => (Vec::new(), EdgeKind::Fallthrough),
+        };
+        let is_payload = matches!(
+            instruction,
+            Instruction::PackedSwitchPayload { .. }
+                | Instruction::SparseSwitchPayload { .. }
+                | Instruction::FillArrayDataPayload { .. }
+        );
+        let is_terminator = matches!(
+            instruction,
+            Instruction::Goto { .. }
+                | Instruction::ReturnVoid
+                | Instruction::Return { .. }
+                | Instruction::Throw { .. }
+        );
+        let is_branch = matches!(
+            instruction,
+            Instruction::If { .. }
+                | Instruction::IfZero { .. }
+                | Instruction::PackedSwitch { .. }
+                | Instruction::SparseSwitch { .. }
+        );
+        nodes.push(Node {
+            addr,
+            len,
+            targets,
+            target_kind,
+            falls_through: !is_payload && !is_terminator,
+            ends_block: is_payload || is_terminator || is_branch,
+            is_payload,
+        });
+        addr += len;
+    }
+    Ok(nodes)
+}
+
+impl ControlFlowGraph {
+    pub(crate) fn new(insns: &[ushort], tries: &[TryCatchHandlers]) -> Result<Self> {
+        let nodes = nodes(insns)?;
+
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for node in &nodes {
+            leaders.extend(node.targets.iter().cloned());
+            if node.ends_block {
+                leaders.insert(node.addr + node.len);
+            }
+        }
+        for try_block in tries {
+            let start = try_block.start_addr() as usize;
+            leaders.insert(start);
+            leaders.insert(start + try_block.insn_count() as usize);
+            leaders.extend(try_block.catch_handlers().iter().map(|h| h.addr() as usize));
+        }
+
+        // (start, end, index of the last node) of each block
+        let mut ranges: Vec<(usize, usize, usize)> = Vec::new();
+        let mut current: Option<(usize, usize)> = None;
+        for (index, node) in nodes.iter().enumerate() {
+            if node.is_payload {
+                continue;
+            }
+            let start = match current {
+                Some((start, _)) if !leaders.contains(&node.addr) => start,
+                Some((start, last)) => {
+                    ranges.push((start, node.addr, last));
+                    node.addr
+                }
+                None => node.addr,
+            };
+            current = Some((start, index));
+            if node.ends_block {
+                ranges.push((start, node.addr + node.len, index));
+                current = None;
+            }
+        }
+        if let Some((start, last)) = current {
+            ranges.push((start, nodes[last].addr + nodes[last].len, last));
+        }
+
+        let block_at: BTreeMap<usize, usize> = ranges
+            .iter()
+            .enumerate()
+            .map(|(index, (start, _, _))| (*start, index))
+            .collect();
+        let find_block = |addr: usize| {
+            block_at.get(&addr).cloned().ok_or_else(|| {
+                Error::MalFormed(format!(
+                    "Control flow target {} is not an instruction",
+                    addr
+                ))
+            })
+        };
+
+        let mut blocks = Vec::with_capacity(ranges.len());
+        for (start, end, last) in &ranges {
+            let last = &nodes[*last];
+            let mut edges = Vec::new();
+            if last.falls_through {
+                if let Some(next) = block_at.get(end) {
+                    edges.push((*next, EdgeKind::Fallthrough));
+                }
+            }
+            for target in &last.targets {
+                edges.push((find_block(*target)?, last.target_kind));
+            }
+            for try_block in tries {
+                let try_start = try_block.start_addr() as usize;
+                let try_end = try_start + try_block.insn_count() as usize;
+                if try_start <= *start && *start < try_end {
+                    for handler in try_block.catch_handlers() {
+                        edges.push((find_block(handler.addr() as usize)?, EdgeKind::Exception));
+                    }
+                }
+            }
+            let mut seen = BTreeSet::new();
+            edges.retain(|edge| seen.insert(*edge));
+            let mut seen = BTreeSet::new();
+            let successors = edges
+                .iter()
+                .map(|(successor, _)| *successor)
+                .filter(|successor| seen.insert(*successor))
+                .collect();
+            blocks.push(BasicBlock {
+                start_addr: *start,
+                end_addr: *end,
+                successors,
+                predecessors: Vec::new(),
+                edges,
+            });
+        }
+        for index in 0..blocks.len() {
+            for successor in blocks[index].successors.clone() {
+                blocks[successor].predecessors.push(index);
+            }
+        }
+
+        Ok(Self { blocks })
+    }
+
+    /// Indices of the blocks reachable from the entry block, through branches and
+    /// exception edges.
+    pub fn reachable_blocks(&self) -> BTreeSet<usize> {
+        self.reverse_postorder().into_iter().collect()
+    }
+
+    /// Returns the blocks in reverse postorder starting from the entry block.
+    /// Unreachable blocks are not included.
+    fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.blocks.len());
+        if self.blocks.is_empty() {
+            return order;
+        }
+        let mut visited = vec![false; self.blocks.len()];
+        // (block, index of the next successor to visit)
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block].successors.get(next) {
+                Some(&successor) => {
+                    stack.push((block, next + 1));
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Computes the dominator tree of the graph.
+    pub fn dominators(&self) -> DominatorTree {
+        // "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            position[*block] = index;
+        }
+        let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        if let Some(entry) = order.first() {
+            idom[*entry] = Some(*entry);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().skip(1) {
+                let mut new_idom: Option<usize> = None;
+                for predecessor in &self.blocks[*block].predecessors {
+                    if idom[*predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *predecessor,
+                        Some(current) => {
+                            let (mut a, mut b) = (current, *predecessor);
+                            while a != b {
+                                while position[a] > position[b] {
+                                    a = idom[a].expect("processed block without dominator");
+                                }
+                                while position[b] > position[a] {
+                                    b = idom[b].expect("processed block without dominator");
+                                }
+                            }
+                            a
+                        }
+                    });
+                }
+                if new_idom.is_some() && idom[*block] != new_idom {
+                    idom[*block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        if let Some(entry) = order.first() {
+            idom[*entry] = None;
+        }
+        DominatorTree {
+            idom,
+            reachable: position.iter().map(|p| *p != usize::MAX).collect(),
+        }
+    }
+
+    /// Returns `true` if the graphs have the same shape, which approximates graph
+    /// isomorphism in `O(E log E)`: both graphs must have the same number of blocks, the
+    /// same number of loops and the same multiset of edges, an edge being described by
+    /// the in and out degrees of its endpoints. Isomorphic graphs always have the same
+    /// shape, but graphs with the same shape are not necessarily isomorphic.
+    pub fn same_shape(&self, other: &ControlFlowGraph) -> bool {
+        self.blocks.len() == other.blocks.len()
+            && self.edge_profile() == other.edge_profile()
+            && self.loops().len() == other.loops().len()
+    }
+
+    /// Sorted in and out degrees of the endpoints of every edge, starting with the
+    /// degrees of the entry block.
+    fn edge_profile(&self) -> Vec<((usize, usize), (usize, usize))> {
+        let degrees = |block: usize| {
+            let block = &self.blocks[block];
+            (block.predecessors.len(), block.successors.len())
+        };
+        let mut edges = self
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, block)| {
+                block
+                    .successors
+                    .iter()
+                    .map(move |successor| (degrees(index), degrees(*successor)))
+            })
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        if !self.blocks.is_empty() {
+            edges.insert(0, (degrees(0), (0, 0)));
+        }
+        edges
+    }
+
+    /// Returns the natural loops of the graph, in ascending order of their headers.
+    /// Loops sharing a header are merged into a single loop. Only blocks reachable from
+    /// the entry block take part in loops, the body of a loop being made of blocks
+    /// dominated by its header.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dominators = self.dominators();
+        let mut loops: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            // an edge to a block dominating its source is a back edge.
+            for header in block
+                .successors
+                .iter()
+                .filter(|s| dominators.dominates(**s, index))
+            {
+                let body = loops.entry(*header).or_insert_with(|| {
+                    let mut body = BTreeSet::new();
+                    body.insert(*header);
+                    body
+                });
+                // the body is formed by the blocks reaching the back edge without
+                // going through the header. An unreachable block jumping into the
+                // loop isn't dominated by the header and is left out.
+                let mut stack = vec![index];
+                while let Some(block) = stack.pop() {
+                    if dominators.dominates(*header, block) && body.insert(block) {
+                        stack.extend(self.blocks[block].predecessors.iter().cloned());
+                    }
+                }
+            }
+        }
+        loops
+            .into_iter()
+            .map(|(header, blocks)| Loop { header, blocks })
+            .collect()
+    }
+}
+
+/// A natural loop of a `ControlFlowGraph`. Blocks are identified by their index
+/// in `ControlFlowGraph::blocks`.
+#[derive(Debug, Getters, CopyGetters)]
+pub struct Loop {
+    /// The block dominating every block of the loop, which all back edges jump to.
+    #[get_copy = "pub"]
+    header: usize,
+    /// Blocks of the loop, including the header.
+    #[get = "pub"]
+    blocks: BTreeSet<usize>,
+}
+
+/// Dominator tree of a `ControlFlowGraph`. Blocks are identified by their index
+/// in `ControlFlowGraph::blocks`.
+#[derive(Debug)]
+pub struct DominatorTree {
+    idom: Vec<Option<usize>>,
+    reachable: Vec<bool>,
+}
+
+impl DominatorTree {
+    /// Returns the immediate dominator of the block, or `None` for the entry block
+    /// and blocks unreachable from it.
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.idom.get(block).cloned().flatten()
+    }
+
+    /// Returns `true` if every path from the entry block to `block` goes through
+    /// `dominator`. Every reachable block dominates itself.
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        if !self.reachable.get(block).cloned().unwrap_or(false) {
+            return false;
+        }
+        let mut current = Some(block);
+        while let Some(b) = current {
+            if b == dominator {
+                return true;
+            }
+            current = self.idom[b];
+        }
+        false
+    }
+
+    /// Returns the blocks immediately dominated by `block`.
+    pub fn children(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.idom
+            .iter()
+            .enumerate()
+            .filter(move |(_, idom)| **idom == Some(block))
+            .map(|(child, _)| child)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ControlFlowGraph, EdgeKind};
+
+    #[test]
+    fn test_diamond() {
+        let insns = [
+            0x0038, 0x0004, // 0: if-eqz v0, +4
+            0x1012, // 2: const/4 v0, 1
+            0x0228, // 3: goto +2
+            0x2012, // 4: const/4 v0, 2
+            0x000f, // 5: return v0
+        ];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        let ranges = cfg
+            .blocks()
+            .iter()
+            .map(|b| (b.start_addr(), b.end_addr()))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(0, 2), (2, 4), (4, 5), (5, 6)]);
+        assert_eq!(cfg.blocks()[0].successors(), &vec![1, 2]);
+        assert_eq!(cfg.blocks()[3].predecessors(), &vec![1, 2]);
+
+        let dominators = cfg.dominators();
+        assert_eq!(dominators.immediate_dominator(0), None);
+        for block in 1..4 {
+            assert_eq!(dominators.immediate_dominator(block), Some(0));
+            assert!(dominators.dominates(0, block));
+        }
+        assert!(!dominators.dominates(1, 3));
+        assert_eq!(dominators.children(0).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_same_shape() {
+        let diamond = [
+            0x0038, 0x0004, // 0: if-eqz v0, +4
+            0x1012, // 2: const/4 v0, 1
+            0x0228, // 3: goto +2
+            0x2012, // 4: const/4 v0, 2
+            0x000f, // 5: return v0
+        ];
+        // Same structure with other registers, constants and branch condition.
+        let renamed = [
+            0x0339, 0x0005, // 0: if-nez v3, +5
+            0x7312, // 2: const/4 v3, 7
+            0x0000, // 3: nop
+            0x0228, // 4: goto +2
+            0x5312, // 5: const/4 v3, 5
+            0x030f, // 6: return v3
+        ];
+        let chain = [
+            0x0038, 0x0003, // 0: if-eqz v0, +3
+            0x000e, // 2: return-void
+            0x000e, // 3: return-void
+            0x000e, // 4: return-void
+        ];
+        let cfg = |insns: &[u16]| ControlFlowGraph::new(insns, &[]).expect("cannot build cfg");
+        assert!(cfg(&diamond).same_shape(&cfg(&renamed)));
+        assert!(cfg(&renamed).same_shape(&cfg(&diamond)));
+        assert!(!cfg(&diamond).same_shape(&cfg(&chain)));
+    }
+
+    #[test]
+    fn test_loop_and_unreachable_block() {
+        let insns = [
+            0x0012, // 0: const/4 v0, 0
+            0x0038, 0x0003, // 1: if-eqz v0, +3
+            0xfe28, // 3: goto -2
+            0x000e, // 4: return-void
+            0x000e, // 5: return-void
+        ];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        assert_eq!(cfg.blocks().len(), 5);
+        assert_eq!(cfg.blocks()[2].successors(), &vec![1]);
+        let dominators = cfg.dominators();
+        assert_eq!(dominators.immediate_dominator(1), Some(0));
+        assert_eq!(dominators.immediate_dominator(2), Some(1));
+        assert_eq!(dominators.immediate_dominator(3), Some(1));
+        assert_eq!(dominators.immediate_dominator(4), None);
+        assert!(!dominators.dominates(0, 4));
+        assert_eq!(
+            cfg.reachable_blocks().into_iter().collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let insns = [
+            0x0012, // 0: const/4 v0, 0
+            0x0038, 0x0005, // 1: if-eqz v0, +5
+            0x0039, 0x0003, // 3: if-nez v0, +3
+            0xfc28, // 5: goto -4
+            0xfb28, // 6: goto -5
+            0x000e, // 7: return-void
+        ];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        let loops = cfg.loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header(), 1);
+        assert_eq!(
+            loops[0].blocks().iter().cloned().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+
+        let insns = [0x0038, 0x0002, 0x000e, 0x000e];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        assert!(cfg.loops().is_empty());
+
+        // an unreachable block jumping into the body of a loop.
+        let insns = [
+            0x0012, // 0: const/4 v0, 0
+            0x0038, 0x0003, // 1: if-eqz v0, +3
+            0xfe28, // 3: goto -2
+            0x000e, // 4: return-void
+            0xfe28, // 5: goto -2
+        ];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        let ranges = cfg
+            .blocks()
+            .iter()
+            .map(|b| (b.start_addr(), b.end_addr()))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(0, 1), (1, 3), (3, 4), (4, 5), (5, 6)]);
+        assert_eq!(cfg.blocks()[4].successors(), &vec![2]);
+        let loops = cfg.loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header(), 1);
+        assert_eq!(
+            loops[0].blocks().iter().cloned().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_packed_switch() {
+        let insns = [
+            0x002b, 0x0006, 0x0000, // 0: packed-switch v0, +6
+            0x000e, // 3: return-void
+            0x000e, // 4: return-void
+            0x000e, // 5: return-void
+            0x0100, 0x0002, 0x0000, 0x0000, // 6: packed-switch-payload, 2 keys from 0
+            0x0004, 0x0000, 0x0005, 0x0000, // targets +4, +5
+        ];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        assert_eq!(cfg.blocks().len(), 4);
+        assert_eq!(cfg.blocks()[0].successors(), &vec![1, 2, 3]);
+        assert_eq!(
+            cfg.blocks()[0].edges(),
+            &vec![
+                (1, EdgeKind::Fallthrough),
+                (2, EdgeKind::Switch),
+                (3, EdgeKind::Switch)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sparse_switch() {
+        let insns = [
+            0x002c, 0x0008, 0x0000, // 0: sparse-switch v0, +8
+            0x0000, // 3: nop
+            0x000e, // 4: return-void
+            0x0028, // 5: goto +0
+            0x000e, // 6: return-void
+            0x0000, // 7: nop, padding before the payload
+            0x0200, 0x0003, // 8: sparse-switch-payload, 3 keys
+            0xfffe, 0xffff, 0x0005, 0x0000, 0x0064, 0x0000, // keys -2, 5, 100
+            0x0004, 0x0000, 0x0006, 0x0000, 0x0004, 0x0000, // targets +4, +6, +4
+        ];
+        let cfg = ControlFlowGraph::new(&insns, &[]).expect("cannot build cfg");
+        let ranges = cfg
+            .blocks()
+            .iter()
+            .map(|b| (b.start_addr(), b.end_addr()))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(0, 3), (3, 4), (4, 5), (5, 6), (6, 7), (7, 8)]);
+        // two keys share a target, which is not the block right after the switch.
+        assert_eq!(cfg.blocks()[0].successors(), &vec![1, 2, 4]);
+        assert_eq!(
+            cfg.blocks()[0].edges(),
+            &vec![
+                (1, EdgeKind::Fallthrough),
+                (2, EdgeKind::Switch),
+                (4, EdgeKind::Switch)
+            ]
+        );
+        assert_eq!(cfg.blocks()[3].edges(), &vec![(3, EdgeKind::Goto)]);
+        assert_eq!(cfg.blocks()[2].predecessors(), &vec![0, 1]);
+
+        // a target out of the code is an error.
+        let mut insns = insns;
+        insns[16] = 0x0100;
+        assert!(ControlFlowGraph::new(&insns, &[]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_dex_methods() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            for code in class.methods().filter_map(|m| m.code()) {
+                let cfg = code.cfg().expect("cannot build cfg");
+                let dominators = cfg.dominators();
+                for (index, block) in cfg.blocks().iter().enumerate() {
+                    assert!(block.start_addr() < block.end_addr());
+                    if let Some(idom) = dominators.immediate_dominator(index) {
+                        assert!(dominators.dominates(0, idom));
+                        assert!(dominators.dominates(idom, index));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,25 +1,33 @@
 //! Dex `Class` and supporting structures.
-use std::clone::Clone;
+use alloc::{string::String, vec::Vec};
+use core::{cell::OnceCell, clone::Clone, fmt};
 
 use getset::{CopyGetters, Getters};
 use scroll::{ctx, Pread, Uleb128};
 
 use crate::{
-    annotation::{AnnotationSetItem, AnnotationsDirectoryItem},
+    annotation::{AnnotationElement, AnnotationItem, AnnotationSetItem, AnnotationsDirectoryItem},
+    dex::ParseWarning,
     encoded_item::EncodedItemArrayCtx,
-    error::Error,
+    encoded_value::EncodedValue,
+    error::{Error, FlagsOwner},
     field::{EncodedFieldArray, Field},
-    jtype::Type,
+    instruction::Instruction,
+    jtype::{Type, TypeId},
     method::{EncodedMethodArray, Method},
     source::Source,
     string::DexString,
-    uint, utils,
+    uint, ulong, utils, HashSet,
 };
 
 /// `ClassId` is an index into the Types section. The corresponding `Type` denotes the type of
 /// this class. The `Type` must be a class type, not a primitive or an array.
 pub type ClassId = uint;
 
+/// A class and its super class or one of its interfaces, as in
+/// `Dex::classes_topological_with_cuts`.
+pub type HierarchyEdge = (ClassId, ClassId);
+
 bitflags! {
     /// Access flags of a `Class`.
     pub struct AccessFlags: uint {
@@ -36,6 +44,49 @@ bitflags! {
     }
 }
 
+impl AccessFlags {
+    /// Returns the Java modifiers of a class declaration in canonical order, e.g.
+    /// `public final` or `public abstract interface`. The synthetic, annotation and
+    /// enum flags, which have no modifier in the source, are omitted.
+    pub fn to_modifier_string(&self) -> String {
+        self.modifiers(false).join(" ")
+    }
+
+    /// Same as `to_modifier_string` but also renders the synthetic, annotation and
+    /// enum flags: `synthetic` is added before the kind of the class, and
+    /// annotations and enums read `@interface` and `enum` respectively, e.g.
+    /// `public abstract @interface`.
+    pub fn to_modifier_string_verbose(&self) -> String {
+        self.modifiers(true).join(" ")
+    }
+
+    fn modifiers(&self, verbose: bool) -> Vec<&'static str> {
+        let mut modifiers: Vec<_> = [
+            (AccessFlags::PUBLIC, "public"),
+            (AccessFlags::PROTECTED, "protected"),
+            (AccessFlags::PRIVATE, "private"),
+            (AccessFlags::ABSTRACT, "abstract"),
+            (AccessFlags::STATIC, "static"),
+            (AccessFlags::FINAL, "final"),
+        ]
+        .iter()
+        .filter(|(flag, _)| self.contains(*flag))
+        .map(|(_, modifier)| *modifier)
+        .collect();
+        if verbose && self.contains(AccessFlags::SYNTHETIC) {
+            modifiers.push("synthetic");
+        }
+        if verbose && self.contains(AccessFlags::ANNOTATION) {
+            modifiers.push("@interface");
+        } else if self.contains(AccessFlags::INTERFACE) {
+            modifiers.push("interface");
+        } else if verbose && self.contains(AccessFlags::ENUM) {
+            modifiers.push("enum");
+        }
+        modifiers
+    }
+}
+
 /// A `Dex` Class. This is constructed from a `ClassDefItem` and a `ClassDataItem`.
 #[derive(Debug, Getters, CopyGetters)]
 pub struct Class {
@@ -53,6 +104,14 @@ pub struct Class {
     /// Index into the `TypeId`s for the super class, if there is one.
     #[get_copy = "pub"]
     pub(crate) super_class: Option<ClassId>,
+    /// Type of the super class, if there is one.
+    #[get = "pub"]
+    pub(crate) super_type: Option<Type>,
+    /// Offset of the `type_list` of the interfaces implemented by this class, read on
+    /// demand by `interface_ids`.
+    pub(crate) interfaces_off: uint,
+    /// `TypeId`s of the interfaces implemented by this class, once read.
+    pub(crate) interface_ids: OnceCell<Vec<TypeId>>,
     /// List of the interfaces implemented by this class.
     #[get = "pub"]
     pub(crate) interfaces: Vec<Type>,
@@ -87,11 +146,154 @@ impl Class {
     gen_is_flag_set!(is_annotation, ANNOTATION);
     gen_is_flag_set!(is_enum, ENUM);
 
+    /// Returns the super class if it is defined in `dex`. Returns `None` for a class
+    /// without super class or extending a class defined elsewhere, e.g. a framework
+    /// class, whose type is still available through `super_type`.
+    pub fn resolve_super<S: AsRef<[u8]>>(
+        &self,
+        dex: &super::Dex<S>,
+    ) -> super::Result<Option<Class>> {
+        match self.super_class {
+            Some(super_class) => dex.find_class_by_type(super_class),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the annotation of the class with the given type descriptor, e.g.
+    /// `Ljava/lang/Deprecated;`, regardless of its visibility.
+    pub fn annotation(&self, type_descriptor: &str) -> Option<&AnnotationItem> {
+        self.annotations.find_annotation(type_descriptor)
+    }
+
     /// Returns the value of `dalvik.annotation.Signature`.
     pub fn signature(&self) -> super::Result<Option<String>> {
         utils::get_signature(self.annotations())
     }
 
+    /// Returns the class immediately enclosing this class, taken from the
+    /// `dalvik.annotation.EnclosingClass` annotation for member classes and from the
+    /// `dalvik.annotation.EnclosingMethod` annotation for anonymous and local classes.
+    pub fn enclosing_class<S: AsRef<[u8]>>(
+        &self,
+        dex: &super::Dex<S>,
+    ) -> super::Result<Option<Type>> {
+        for item in self.annotations().iter() {
+            if item.jtype() != "Ldalvik/annotation/EnclosingClass;"
+                && item.jtype() != "Ldalvik/annotation/EnclosingMethod;"
+            {
+                continue;
+            }
+            let value = item.annotation().find_element("value").map(|e| e.value());
+            match value {
+                Some(EncodedValue::Type(jtype)) => return Ok(Some(jtype.clone())),
+                Some(EncodedValue::Method(method)) => {
+                    return Ok(Some(dex.get_type(method.class_idx().into())?));
+                }
+                // Classes declared in an initializer have no enclosing method.
+                Some(EncodedValue::Null) => {}
+                other => {
+                    return Err(Error::MalFormed(format!(
+                        "Expected enclosing type or method, found: {:?}",
+                        other
+                    )));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the member classes declared in this class, taken from the
+    /// `dalvik.annotation.MemberClasses` annotation. Anonymous and local classes are
+    /// not listed there. The list is empty if the class has no member classes.
+    pub fn member_classes(&self) -> super::Result<Vec<Type>> {
+        let item = match self
+            .annotations()
+            .find_annotation("Ldalvik/annotation/MemberClasses;")
+        {
+            Some(item) => item,
+            None => return Ok(Vec::new()),
+        };
+        match item.annotation().find_element("value").map(|e| e.value()) {
+            Some(EncodedValue::Array(values)) => values
+                .iter()
+                .map(|value| match value {
+                    EncodedValue::Type(jtype) => Ok(jtype.clone()),
+                    other => Err(Error::MalFormed(format!(
+                        "Expected member class type, found: {:?}",
+                        other
+                    ))),
+                })
+                .collect(),
+            other => Err(Error::MalFormed(format!(
+                "Expected array of member classes, found: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Returns the default values of the elements of this annotation class, taken from
+    /// the `dalvik.annotation.AnnotationDefault` annotation. Elements without a default
+    /// are not listed. `None` if the class has no such annotation, which is the case of
+    /// the classes which are not annotations.
+    pub fn annotation_defaults(&self) -> super::Result<Option<&[AnnotationElement]>> {
+        let item = match self
+            .annotations()
+            .find_annotation("Ldalvik/annotation/AnnotationDefault;")
+        {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        match item.annotation().element("value") {
+            Some(EncodedValue::Annotation(defaults)) => Ok(Some(defaults.elements())),
+            other => Err(Error::MalFormed(format!(
+                "Expected annotation of default values, found: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Returns the outermost class enclosing this class, following the enclosing
+    /// classes up to a top level class. This is the type of this class if it is
+    /// not nested. The search stops at the first class which is not defined in `dex`.
+    pub fn top_level_class<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<Type> {
+        let mut visited = HashSet::new();
+        visited.insert(self.id);
+        let mut top_level = self.jtype.clone();
+        let mut enclosing = self.enclosing_class(dex)?;
+        while let Some(outer) = enclosing {
+            if !visited.insert(outer.id()) {
+                return Err(Error::MalFormed(format!(
+                    "Cycle in the enclosing classes of {}",
+                    self.jtype
+                )));
+            }
+            enclosing = match dex.find_class_by_type(outer.id())? {
+                Some(class) => class.enclosing_class(dex)?,
+                None => None,
+            };
+            top_level = outer;
+        }
+        Ok(top_level)
+    }
+
+    /// `TypeId`s of the interfaces implemented by this class, read from its `type_list`
+    /// on the first call without resolving them, see `interfaces` for the resolved
+    /// types. Returns `Error::InvalidId` if the class is not defined in `dex`, e.g.
+    /// when given another dex of a `MultiDex`.
+    pub fn interface_ids<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<&[TypeId]> {
+        if let Some(ids) = self.interface_ids.get() {
+            return Ok(ids);
+        }
+        if dex.get_type(self.id)? != self.jtype {
+            return Err(Error::InvalidId(format!(
+                "Class {} is not defined in this dex",
+                self.jtype
+            )));
+        }
+        let ids = dex.get_type_id_list(self.interfaces_off)?;
+        Ok(self.interface_ids.get_or_init(|| ids))
+    }
+
     /// The file in which this class is found in the source code.
     pub fn source_file(&self) -> Option<&DexString> {
         self.source_file.as_ref()
@@ -104,16 +306,140 @@ impl Class {
             .chain(self.instance_fields().iter())
     }
 
-    /// List of methods defined in this class.
+    /// List of methods defined in this class, in the order used by `dexdump`: the
+    /// direct methods followed by the virtual methods, each in ascending order of
+    /// their method index. Parsing the class data fails if a list is not ascending.
     pub fn methods(&self) -> impl Iterator<Item = &Method> + '_ {
         self.direct_methods()
             .iter()
             .chain(self.virtual_methods().iter())
     }
 
+    /// The static initializer (`<clinit>`) of this class, if it has one.
+    pub fn static_initializer(&self) -> Option<&Method> {
+        self.direct_methods()
+            .iter()
+            .find(|method| method.name() == "<clinit>")
+    }
+
+    /// Constants of this enum class, in declaration order. They are the `public static
+    /// final` enum fields of the type of the class, so the synthetic `$VALUES` array
+    /// is not included. The dex sorts the fields by name, so the declaration order is
+    /// the order in which `<clinit>` initializes the constants. The list is empty if
+    /// the class is not an enum.
+    pub fn enum_constants(&self) -> Vec<&Field> {
+        if !self.is_enum() {
+            return Vec::new();
+        }
+        let mut constants = self
+            .static_fields
+            .iter()
+            .filter(|field| {
+                field.is_public()
+                    && field.is_static()
+                    && field.is_final()
+                    && field.is_enum()
+                    && field.jtype() == &self.jtype
+            })
+            .collect::<Vec<_>>();
+        let stores = self
+            .static_initializer()
+            .and_then(|method| method.code())
+            .map(|code| {
+                code.instructions()
+                    .filter_map(|instruction| match instruction {
+                        Ok((_, Instruction::StaticPut { field_idx, .. })) => Some(field_idx),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        // constants which are not initialized keep the order of the dex, last.
+        constants.sort_by_key(|field| {
+            stores
+                .iter()
+                .position(|field_idx| *field_idx == field.id())
+                .unwrap_or(usize::MAX)
+        });
+        constants
+    }
+
+    /// Initial value of `field` from the `static_values` of this class. `None` if the
+    /// field is not a static field of this class or has no value in the dex, in which
+    /// case it is `0`, `false` or `null` until `<clinit>` assigns it. The values are
+    /// paired with the static fields in order, so only the last fields can lack one.
+    pub fn static_value_for(&self, field: &Field) -> Option<&EncodedValue> {
+        self.static_fields
+            .iter()
+            .find(|static_field| static_field.id() == field.id())
+            .and_then(|static_field| static_field.initial_value())
+    }
+
+    /// Methods defined in this class in the order used by `dexdump`: the direct
+    /// methods followed by the virtual methods, each in ascending order of their
+    /// method index. The order is checked when the class data is parsed.
+    pub fn methods_in_dex_order(&self) -> impl Iterator<Item = &Method> + '_ {
+        self.methods()
+    }
+
+    /// Members of this class in the order they are encoded in its `ClassDataItem`:
+    /// the static fields, instance fields, direct methods and virtual methods, each
+    /// list in ascending order of the member indices as required by the format.
+    /// The member lists of a `Class` keep this order, so it can be written back as is.
+    pub fn raw_member_order(&self) -> Vec<RawMember> {
+        let mut members = Vec::with_capacity(
+            self.static_fields.len()
+                + self.instance_fields.len()
+                + self.direct_methods.len()
+                + self.virtual_methods.len(),
+        );
+        let lists: [(MemberKind, Vec<ulong>); 4] = [
+            (
+                MemberKind::StaticField,
+                self.static_fields.iter().map(|f| f.id()).collect(),
+            ),
+            (
+                MemberKind::InstanceField,
+                self.instance_fields.iter().map(|f| f.id()).collect(),
+            ),
+            (
+                MemberKind::DirectMethod,
+                self.direct_methods.iter().map(|m| m.id()).collect(),
+            ),
+            (
+                MemberKind::VirtualMethod,
+                self.virtual_methods.iter().map(|m| m.id()).collect(),
+            ),
+        ];
+        for (kind, ids) in lists.iter() {
+            let mut prev = 0;
+            for id in ids {
+                members.push(RawMember {
+                    kind: *kind,
+                    id: *id,
+                    idx_diff: id - prev,
+                });
+                prev = *id;
+            }
+        }
+        members
+    }
+
     pub(crate) fn try_from_dex<T: AsRef<[u8]>>(
         dex: &super::Dex<T>,
         class_def: &ClassDefItem,
+        class_def_index: usize,
+    ) -> super::Result<Self> {
+        Self::parse(dex, class_def, class_def_index, &mut Vec::new())
+    }
+
+    /// Parses the class, adding to `warnings` the recoveries made when the file is
+    /// read with `DexReader::lenient`.
+    pub(crate) fn parse<T: AsRef<[u8]>>(
+        dex: &super::Dex<T>,
+        class_def: &ClassDefItem,
+        class_def_index: usize,
+        warnings: &mut Vec<ParseWarning>,
     ) -> super::Result<Self> {
         debug!(target: "class", "trying to load class: {}", class_def.class_idx);
         let jtype = dex.get_type(class_def.class_idx)?;
@@ -129,9 +455,10 @@ impl Class {
             mut parameter_annotations,
         } = dex.get_annotations_directory_item(class_def.annotations_off)?;
         let static_values = dex.get_static_values(class_def.static_values_off)?;
-        let (static_fields, instance_fields, direct_methods, virtual_methods) = dex
+        let static_values_len = static_values.values().len();
+        let (mut static_fields, mut instance_fields, mut direct_methods, mut virtual_methods) = dex
             .get_class_data(data_off)?
-            .map(move |c| {
+            .map(|c| {
                 let mut static_values = static_values.into_inner();
                 // the order of static values corresponds to the fields list.
                 // reversing the values so that the pop below returns values in
@@ -139,23 +466,27 @@ impl Class {
                 static_values.reverse();
                 Ok((
                     try_from_item!(c.static_fields, |encoded_field| {
-                        dex.get_field(
+                        Field::try_from_dex(
+                            dex,
                             &encoded_field,
                             static_values.pop(),
                             field_annotations
                                 .binary_search_by_key(&encoded_field.field_id(), |f| f.field_idx())
                                 .map(|index| field_annotations.remove(index).annotations)
                                 .unwrap_or_else(|_| Default::default()),
+                            warnings,
                         )
                     }),
                     try_from_item!(c.instance_fields, |encoded_field| {
-                        dex.get_field(
+                        Field::try_from_dex(
+                            dex,
                             &encoded_field,
                             None,
                             field_annotations
                                 .binary_search_by_key(&encoded_field.field_id(), |f| f.field_idx())
                                 .map(|index| field_annotations.remove(index).annotations)
                                 .unwrap_or_else(|_| Default::default()),
+                            warnings,
                         )
                     }),
                     try_from_item!(c.direct_methods, |encoded_method| {
@@ -167,7 +498,13 @@ impl Class {
                             .binary_search_by_key(&encoded_method.method_id(), |m| m.method_idx())
                             .map(|index| parameter_annotations.remove(index).annotations)
                             .unwrap_or_else(|_| Default::default());
-                        dex.get_method(&encoded_method, method_annotations, parameter_annotations)
+                        Method::try_from_dex(
+                            dex,
+                            &encoded_method,
+                            method_annotations,
+                            parameter_annotations,
+                            warnings,
+                        )
                     }),
                     try_from_item!(c.virtual_methods, |encoded_method| {
                         let method_annotations = method_annotations
@@ -178,17 +515,59 @@ impl Class {
                             .binary_search_by_key(&encoded_method.method_id(), |m| m.method_idx())
                             .map(|index| parameter_annotations.remove(index).annotations)
                             .unwrap_or_else(|_| Default::default());
-                        dex.get_method(&encoded_method, method_annotations, parameter_annotations)
+                        Method::try_from_dex(
+                            dex,
+                            &encoded_method,
+                            method_annotations,
+                            parameter_annotations,
+                            warnings,
+                        )
                     }),
                 ))
             })
             .unwrap_or_else(|| Ok::<_, Error>(Default::default()))?;
+        // trailing static fields may have no value, but every value needs a field.
+        if static_values_len > static_fields.len() {
+            return Err(Error::MalFormed(format!(
+                "Class {} has {} static values for {} static fields",
+                jtype,
+                static_values_len,
+                static_fields.len()
+            )));
+        }
+        let count = static_fields.len()
+            + instance_fields.len()
+            + direct_methods.len()
+            + virtual_methods.len();
+        let hidden_api_flags = match dex.get_hidden_api_flags(class_def_index, count) {
+            Ok(flags) => flags,
+            Err(_) if dex.lenient => {
+                warnings.push(ParseWarning::InvalidHiddenApiFlags {
+                    class_id: class_def.class_idx,
+                });
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(flags) = hidden_api_flags {
+            let mut flags = flags.into_iter();
+            for field in static_fields.iter_mut().chain(instance_fields.iter_mut()) {
+                field.hidden_api_flags = flags.next();
+            }
+            for method in direct_methods.iter_mut().chain(virtual_methods.iter_mut()) {
+                method.hidden_api_flags = flags.next();
+            }
+        }
 
         debug!(target: "class", "super class id: {}", class_def.superclass_idx);
         let super_class = if class_def.superclass_idx == super::NO_INDEX {
-            Some(class_def.superclass_idx)
-        } else {
             None
+        } else {
+            Some(class_def.superclass_idx)
+        };
+        let super_type = match super_class {
+            Some(super_class) => Some(dex.get_type(super_class)?),
+            None => None,
         };
         debug!(target: "class", "access flags: {}", class_def.access_flags);
 
@@ -196,13 +575,27 @@ impl Class {
             id: class_def.class_idx,
             jtype,
             super_class,
+            super_type,
+            interfaces_off: class_def.interfaces_off,
+            interface_ids: OnceCell::new(),
             interfaces: dex.get_interfaces(class_def.interfaces_off)?,
-            access_flags: AccessFlags::from_bits(class_def.access_flags).ok_or_else(|| {
-                Error::InvalidId(format!(
-                    "Invalid Access flags in class {}",
-                    class_def.class_idx
-                ))
-            })?,
+            access_flags: match AccessFlags::from_bits(class_def.access_flags) {
+                Some(access_flags) => access_flags,
+                None if dex.lenient => {
+                    warnings.push(ParseWarning::UnknownAccessFlags {
+                        class_id: class_def.class_idx,
+                        access_flags: class_def.access_flags,
+                    });
+                    AccessFlags::from_bits_truncate(class_def.access_flags)
+                }
+                None => {
+                    return Err(Error::InvalidAccessFlags {
+                        class_idx: class_def.class_idx,
+                        owner: FlagsOwner::Class,
+                        raw: ulong::from(class_def.access_flags),
+                    })
+                }
+            },
             source_file: dex.get_source_file(class_def.source_file_idx)?,
             static_fields,
             instance_fields,
@@ -213,6 +606,70 @@ impl Class {
     }
 }
 
+/// Declaration of the class in Java source form, with the modifiers of
+/// `AccessFlags::to_modifier_string`, e.g.
+/// `public final class com.example.Foo extends android.app.Activity implements java.io.Serializable`.
+/// `extends java.lang.Object` is omitted, and interfaces are declared with the
+/// `interface` keyword, without `abstract`, extending their super interfaces.
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_interface = self.access_flags.contains(AccessFlags::INTERFACE);
+        let mut modifiers = self.access_flags.modifiers(false);
+        if is_interface {
+            modifiers.retain(|modifier| *modifier != "abstract");
+        } else {
+            modifiers.push("class");
+        }
+        write!(f, "{} {}", modifiers.join(" "), self.jtype.to_java_type())?;
+        match self.super_type {
+            Some(ref super_type) if !is_interface && super_type != "Ljava/lang/Object;" => {
+                write!(f, " extends {}", super_type.to_java_type())?
+            }
+            _ => {}
+        }
+        if !self.interfaces.is_empty() {
+            let interfaces = self
+                .interfaces
+                .iter()
+                .map(Type::to_java_type)
+                .collect::<Vec<_>>();
+            let keyword = if is_interface {
+                "extends"
+            } else {
+                "implements"
+            };
+            write!(f, " {} {}", keyword, interfaces.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// List of a `ClassDataItem` in which a member is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemberKind {
+    /// Member of the `static_fields` list.
+    StaticField,
+    /// Member of the `instance_fields` list.
+    InstanceField,
+    /// Member of the `direct_methods` list.
+    DirectMethod,
+    /// Member of the `virtual_methods` list.
+    VirtualMethod,
+}
+
+/// A field or method as encoded in a `ClassDataItem`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct RawMember {
+    /// List the member is encoded in.
+    kind: MemberKind,
+    /// Index into the `FieldId`s or `MethodId`s list, depending on the kind.
+    id: ulong,
+    /// Encoded difference from the index of the previous member of the list, or
+    /// the index itself for the first member.
+    idx_diff: ulong,
+}
+
 /// Contains the details about fields and methods of a class.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#class-data-item)
 #[derive(Getters)]
@@ -306,6 +763,14 @@ pub struct ClassDefItem {
     pub(crate) static_values_off: uint,
 }
 
+impl ClassDefItem {
+    /// `TypeId`s of the interfaces implemented by the class, read from the `type_list`
+    /// at `interfaces_off` without resolving them or parsing the class.
+    pub fn interface_ids<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<Vec<TypeId>> {
+        dex.get_type_id_list(self.interfaces_off)
+    }
+}
+
 /// Iterator over the class_def_items in the class_defs section.
 pub(crate) struct ClassDefItemIter<T> {
     /// Source file of the parent `Dex`.
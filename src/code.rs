@@ -1,17 +1,28 @@
 //! Structures defining the contents of a `Method`'s code.
-use scroll::{ctx, Pread, Uleb128};
-use std::{fmt, ops::Deref};
+use alloc::{collections::BTreeSet, string::ToString, vec::Vec};
+use core::{convert::TryFrom, fmt, ops::Deref};
+use scroll::{ctx, Pread, Sleb128, Uleb128};
 
 use getset::{CopyGetters, Getters};
 
 use crate::{
-    disass::InstIterator, encoded_item::EncodedCatchHandlers, error::Error, jtype::Type,
-    string::DexString, uint, ulong, ushort,
+    cfg::ControlFlowGraph,
+    disass::InstIterator,
+    encoded_item::EncodedCatchHandlers,
+    error::Error,
+    instruction::InstructionIterator,
+    jtype::{Type, TypeId},
+    liveness::Liveness,
+    string::DexString,
+    ubyte, uint, ulong, ushort, HashMap,
 };
 
+/// Value of the opcode of a Dalvik instruction.
+pub type Opcode = ubyte;
+
 /// Debug Info of a method.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#debug-info-item)
-#[derive(Debug, Getters, CopyGetters)]
+#[derive(Debug, Clone, Getters, CopyGetters)]
 pub struct DebugInfoItem {
     /// Initial value for the state machines's line register.
     #[get_copy = "pub"]
@@ -19,10 +30,131 @@ pub struct DebugInfoItem {
     /// Names of the incoming parameters.
     #[get = "pub"]
     parameter_names: Vec<Option<DexString>>,
+    /// Entries of the line number table, in ascending order of their addresses.
+    #[get = "pub"]
+    positions: Vec<PositionEntry>,
+    /// Local variables of the method. When parsed as part of a method, `this` and the
+    /// parameters are live from the start of the code, in their incoming registers.
+    #[get = "pub"]
+    local_variables: LocalVariableTable,
+}
+
+impl DebugInfoItem {
+    /// Returns the source line of the instruction at `address`, in 16-bit code units.
+    pub fn line_for_address(&self, address: usize) -> Option<uint> {
+        self.positions
+            .iter()
+            .take_while(|position| position.address <= address)
+            .last()
+            .map(|position| position.line)
+    }
+}
+
+/// Entry of the line number table of a method: the instructions from `address` to
+/// the next entry belong to `line`.
+#[derive(Debug, Clone, Copy, PartialEq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct PositionEntry {
+    /// Address of the first instruction, in 16-bit code units.
+    address: usize,
+    /// Line number in the source file.
+    line: uint,
+}
+
+/// Local variable of a method, live in `register` from `start_address` to
+/// `end_address`, in 16-bit code units.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct LocalVariable {
+    /// Register holding the variable.
+    #[get_copy = "pub"]
+    register: ushort,
+    /// Name of the variable, if known.
+    #[get = "pub"]
+    name: Option<DexString>,
+    /// Type of the variable, if known.
+    #[get = "pub"]
+    jtype: Option<Type>,
+    /// Generic signature of the variable, only set by `DBG_START_LOCAL_EXTENDED`.
+    #[get = "pub"]
+    signature: Option<DexString>,
+    /// Address of the first instruction where the variable is live.
+    #[get_copy = "pub"]
+    start_address: usize,
+    /// Address of the first instruction where the variable is no longer live, `None`
+    /// if the variable is live until the end of the code.
+    #[get_copy = "pub"]
+    end_address: Option<usize>,
+}
+
+impl LocalVariable {
+    /// Returns `true` if the variable is live at `address`.
+    pub fn is_live_at(&self, address: usize) -> bool {
+        self.start_address <= address && self.end_address.is_none_or(|end| address < end)
+    }
+}
+
+/// Local variables of a method, in the order their live ranges start. A variable
+/// ended and restarted is reported once per live range.
+#[derive(Debug, Clone, Default, PartialEq, Getters)]
+pub struct LocalVariableTable {
+    /// Live ranges of the local variables.
+    #[get = "pub"]
+    variables: Vec<LocalVariable>,
+}
+
+impl LocalVariableTable {
+    /// Returns the variable held by `register` at `address`, if there is one.
+    pub fn variable_at(&self, register: ushort, address: usize) -> Option<&LocalVariable> {
+        self.variables
+            .iter()
+            .find(|variable| variable.register == register && variable.is_live_at(address))
+    }
+
+    /// Starts a new live range of a variable in `register`, ending the live range of
+    /// the variable previously held by the register.
+    fn start(&mut self, live: &mut HashMap<ushort, usize>, variable: LocalVariable) {
+        self.end(live, variable.register, variable.start_address);
+        live.insert(variable.register, self.variables.len());
+        self.variables.push(variable);
+    }
+
+    /// Ends the live range of the variable held by `register`, if there is one.
+    fn end(&mut self, live: &mut HashMap<ushort, usize>, register: ushort, address: usize) {
+        if let Some(index) = live.remove(&register) {
+            self.variables[index].end_address = Some(address);
+        }
+    }
+}
+
+/// Incoming arguments of a method, which seed the local variables of its debug info.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Arguments<'a> {
+    /// Type of `this`, for an instance method.
+    pub(crate) this: Option<&'a Type>,
+    /// Types of the parameters of the method.
+    pub(crate) parameters: &'a [Type],
 }
 
+/// Offset of `debug_info_off` in a code item.
+pub(crate) const DEBUG_INFO_OFFSET: usize = 8;
+
+// Opcodes of the debug info state machine.
+const DBG_END_SEQUENCE: ubyte = 0x00;
+const DBG_ADVANCE_PC: ubyte = 0x01;
+const DBG_ADVANCE_LINE: ubyte = 0x02;
+const DBG_START_LOCAL: ubyte = 0x03;
+const DBG_START_LOCAL_EXTENDED: ubyte = 0x04;
+const DBG_END_LOCAL: ubyte = 0x05;
+const DBG_RESTART_LOCAL: ubyte = 0x06;
+const DBG_SET_PROLOGUE_END: ubyte = 0x07;
+const DBG_SET_EPILOGUE_BEGIN: ubyte = 0x08;
+const DBG_SET_FILE: ubyte = 0x09;
+const DBG_FIRST_SPECIAL: ubyte = 0x0a;
+const DBG_LINE_BASE: i64 = -4;
+const DBG_LINE_RANGE: ubyte = 15;
+
 /// Code and Debug Info of a method.
-#[derive(Getters, CopyGetters)]
+#[derive(Clone, Getters, CopyGetters)]
 pub struct CodeItem {
     /// The number of registers the method must use.
     #[get_copy = "pub"]
@@ -49,9 +181,52 @@ impl CodeItem {
         self.debug_info_item.as_ref()
     }
 
+    /// Local variables of the method, if the code has debug info. See `DebugInfoItem`.
+    pub fn local_variables(&self) -> Option<&LocalVariableTable> {
+        self.debug_info_item
+            .as_ref()
+            .map(|debug_info| &debug_info.local_variables)
+    }
+
+    /// Iterator over the instructions of this code, which stops at the first truncated
+    /// instruction. Prefer `instructions`, which reports it as an error.
     pub fn disassemble(&self) -> InstIterator {
-        let (_, data, _) = unsafe { (&self.insns()).align_to::<u8>() };
-        InstIterator::new(data, self.insns().len() * 2)
+        InstIterator::from_insns(self.insns())
+    }
+
+    /// Iterator over the typed instructions of this code, see `Instruction`.
+    pub fn instructions(&self) -> InstructionIterator<'_> {
+        InstructionIterator::new(self.insns())
+    }
+
+    /// Builds the control flow graph of this code.
+    pub fn cfg(&self) -> super::Result<ControlFlowGraph> {
+        ControlFlowGraph::new(self.insns(), self.tries())
+    }
+
+    /// Computes the registers live at each instruction of this code.
+    pub fn liveness(&self) -> super::Result<Liveness> {
+        Liveness::new(self.insns(), self.registers_size, &self.cfg()?)
+    }
+
+    /// Addresses, in 16-bit code units, of the instructions reachable from the entry of
+    /// the code through branches and exception handlers. Payloads are not included.
+    pub fn reachable_instructions(&self) -> super::Result<BTreeSet<usize>> {
+        let cfg = self.cfg()?;
+        let ranges = cfg
+            .reachable_blocks()
+            .into_iter()
+            .map(|block| &cfg.blocks()[block])
+            .map(|block| block.start_addr()..block.end_addr())
+            .collect::<Vec<_>>();
+        let mut reachable = BTreeSet::new();
+        for instruction in self.instructions() {
+            let (address, _) = instruction?;
+            if ranges.iter().any(|range| range.contains(&address)) {
+                reachable.insert(address);
+            }
+        }
+        Ok(reachable)
     }
 }
 
@@ -95,7 +270,7 @@ pub struct CatchHandler {
 }
 
 /// Represents Try and catch blocks.
-#[derive(Debug, Getters, CopyGetters)]
+#[derive(Debug, Clone, Getters, CopyGetters)]
 pub struct TryCatchHandlers {
     /// Start of the try block.
     #[get_copy = "pub"]
@@ -108,8 +283,18 @@ pub struct TryCatchHandlers {
     catch_handlers: Vec<CatchHandler>,
 }
 
+impl TryCatchHandlers {
+    /// Address of the catch-all handler of this try block, if it has one.
+    pub fn catch_all_addr(&self) -> Option<ulong> {
+        self.catch_handlers
+            .iter()
+            .find(|handler| matches!(handler.exception, ExceptionType::BaseException))
+            .map(|handler| handler.addr)
+    }
+}
+
 /// List of try-catch blocks found in this method.
-#[derive(Debug, Default, Getters, CopyGetters)]
+#[derive(Debug, Clone, Default, Getters, CopyGetters)]
 pub struct Tries {
     #[get = "pub"]
     try_catch_blocks: Vec<TryCatchHandlers>,
@@ -161,6 +346,25 @@ where
     }
 }
 
+/// Reads the register operand of a local variable opcode.
+fn read_register(source: &[ubyte], offset: &mut usize) -> super::Result<ushort> {
+    let register = Uleb128::read(source, offset)?;
+    ushort::try_from(register)
+        .map_err(|_| Error::MalFormed(format!("Invalid register in debug info: {}", register)))
+}
+
+/// Reads a uleb128p1 encoded string id, where `0` stands for `NO_INDEX`.
+fn read_string<S: AsRef<[u8]>>(
+    source: &[ubyte],
+    offset: &mut usize,
+    dex: &super::Dex<S>,
+) -> super::Result<Option<DexString>> {
+    match Uleb128::read(source, offset)? {
+        0 => Ok(None),
+        string_id => Ok(Some(dex.get_string((string_id - 1) as uint)?)),
+    }
+}
+
 impl<'a, S> ctx::TryFromCtx<'a, &super::Dex<S>> for DebugInfoItem
 where
     S: AsRef<[u8]>,
@@ -171,30 +375,154 @@ where
     fn try_from_ctx(
         source: &'a [u8],
         dex: &super::Dex<S>,
+    ) -> Result<(Self, Self::Size), Self::Error> {
+        let arguments: Option<(ushort, Arguments<'_>)> = None;
+        ctx::TryFromCtx::try_from_ctx(source, (dex, arguments))
+    }
+}
+
+/// The arguments, if given, start in the first register of the tuple.
+impl<'a, 'b, S> ctx::TryFromCtx<'a, (&'b super::Dex<S>, Option<(ushort, Arguments<'b>)>)>
+    for DebugInfoItem
+where
+    S: AsRef<[u8]>,
+{
+    type Error = Error;
+    type Size = usize;
+
+    fn try_from_ctx(
+        source: &'a [u8],
+        (dex, arguments): (&'b super::Dex<S>, Option<(ushort, Arguments<'b>)>),
     ) -> Result<(Self, Self::Size), Self::Error> {
         let offset = &mut 0;
         let line_start = Uleb128::read(source, offset)? as usize;
         let parameters_size = Uleb128::read(source, offset)?;
-        let mut parameter_names = Vec::with_capacity(parameters_size as usize);
+        let mut parameter_names = Vec::new();
         for _ in 0..parameters_size {
-            let string_id = Uleb128::read(source, offset)? + 1;
-            parameter_names.push(if string_id != u64::from(crate::NO_INDEX) {
-                Some(dex.get_string(string_id as uint)?)
-            } else {
-                None
-            });
+            parameter_names.push(read_string(source, offset, dex)?);
+        }
+        let mut positions = Vec::new();
+        let mut local_variables = LocalVariableTable::default();
+        // Index of the variable live in each register, and of the last variable ended.
+        let (mut live, mut ended) = (HashMap::new(), HashMap::new());
+        if let Some((mut register, arguments)) = arguments {
+            let this = arguments
+                .this
+                .map(|jtype| (Some(DexString::from("this".to_string())), jtype));
+            let parameters = arguments
+                .parameters
+                .iter()
+                .enumerate()
+                .map(|(index, jtype)| (parameter_names.get(index).cloned().flatten(), jtype));
+            for (name, jtype) in this.into_iter().chain(parameters) {
+                local_variables.start(
+                    &mut live,
+                    LocalVariable {
+                        register,
+                        name,
+                        jtype: Some(jtype.clone()),
+                        signature: None,
+                        start_address: 0,
+                        end_address: None,
+                    },
+                );
+                let width = if jtype.is_long() || jtype.is_double() {
+                    2
+                } else {
+                    1
+                };
+                register = register.wrapping_add(width);
+            }
+        }
+        let (mut address, mut line) = (0, line_start as i64);
+        loop {
+            let opcode: ubyte = source.gread(offset)?;
+            match opcode {
+                DBG_END_SEQUENCE => break,
+                DBG_ADVANCE_PC => address += Uleb128::read(source, offset)? as usize,
+                DBG_ADVANCE_LINE => line += Sleb128::read(source, offset)?,
+                DBG_START_LOCAL | DBG_START_LOCAL_EXTENDED => {
+                    let register = read_register(source, offset)?;
+                    let name = read_string(source, offset, dex)?;
+                    let jtype = match Uleb128::read(source, offset)? {
+                        0 => None,
+                        type_id => Some(dex.get_type((type_id - 1) as TypeId)?),
+                    };
+                    let signature = if opcode == DBG_START_LOCAL_EXTENDED {
+                        read_string(source, offset, dex)?
+                    } else {
+                        None
+                    };
+                    ended.remove(&register);
+                    local_variables.start(
+                        &mut live,
+                        LocalVariable {
+                            register,
+                            name,
+                            jtype,
+                            signature,
+                            start_address: address,
+                            end_address: None,
+                        },
+                    );
+                }
+                DBG_END_LOCAL => {
+                    let register = read_register(source, offset)?;
+                    if let Some(&index) = live.get(&register) {
+                        ended.insert(register, index);
+                    }
+                    local_variables.end(&mut live, register, address);
+                }
+                DBG_RESTART_LOCAL => {
+                    let register = read_register(source, offset)?;
+                    // Restarting a live variable or a register which never held one
+                    // is ignored, as the runtime does.
+                    if live.contains_key(&register) {
+                        continue;
+                    }
+                    match ended.get(&register) {
+                        Some(&index) => {
+                            let variable = LocalVariable {
+                                start_address: address,
+                                end_address: None,
+                                ..local_variables.variables[index].clone()
+                            };
+                            local_variables.start(&mut live, variable);
+                        }
+                        None => {
+                            debug!(target: "debug-info", "restart of unknown local in v{}", register)
+                        }
+                    }
+                }
+                DBG_SET_FILE => {
+                    Uleb128::read(source, offset)?;
+                }
+                DBG_SET_PROLOGUE_END | DBG_SET_EPILOGUE_BEGIN => {}
+                special => {
+                    let adjusted = special - DBG_FIRST_SPECIAL;
+                    line += DBG_LINE_BASE + i64::from(adjusted % DBG_LINE_RANGE);
+                    address += usize::from(adjusted / DBG_LINE_RANGE);
+                    let line = uint::try_from(line).map_err(|_| {
+                        Error::MalFormed(format!("Invalid line number in debug info: {}", line))
+                    })?;
+                    positions.push(PositionEntry { address, line });
+                }
+            }
         }
         Ok((
             Self {
                 line_start,
                 parameter_names,
+                positions,
+                local_variables,
             },
             *offset,
         ))
     }
 }
 
-impl<'a, S> ctx::TryFromCtx<'a, &super::Dex<S>> for CodeItem
+/// The arguments, if given, are those of the method of the code.
+impl<'a, 'b, S> ctx::TryFromCtx<'a, (&'b super::Dex<S>, Option<Arguments<'b>>)> for CodeItem
 where
     S: AsRef<[u8]>,
 {
@@ -203,7 +531,7 @@ where
 
     fn try_from_ctx(
         source: &'a [u8],
-        dex: &super::Dex<S>,
+        (dex, arguments): (&'b super::Dex<S>, Option<Arguments<'b>>),
     ) -> Result<(Self, Self::Size), Self::Error> {
         let offset = &mut 0;
         let endian = dex.get_endian();
@@ -213,7 +541,16 @@ where
         let tries_size: ushort = source.gread_with(offset, endian)?;
         let debug_info_off = source.gread_with(offset, endian)?;
         let debug_info_item = if debug_info_off != 0 {
-            Some(dex.get_debug_info_item(debug_info_off)?)
+            let first_argument = registers_size.wrapping_sub(ins_size);
+            match dex.debug_info_item_at(
+                debug_info_off,
+                arguments.map(|arguments| (first_argument, arguments)),
+            ) {
+                Ok(debug_info_item) => Some(debug_info_item),
+                // reported by the parsing of the method, see `ParseWarning::InvalidDebugInfo`.
+                Err(_) if dex.lenient => None,
+                Err(e) => return Err(e),
+            }
         } else {
             None
         };
@@ -240,3 +577,191 @@ where
         ))
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use scroll::Pread;
+
+    use super::{Arguments, DebugInfoItem, LocalVariable, PositionEntry};
+
+    #[test]
+    fn test_line_number_program() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let program = [
+            10,   // line_start
+            0,    // parameters_size
+            0x07, // DBG_SET_PROLOGUE_END
+            0x2d, // special: address += 2, line += 1
+            0x03, 0x01, 0x00, 0x00, // DBG_START_LOCAL v1
+            0x01, 0x03, // DBG_ADVANCE_PC 3
+            0x02, 0x7e, // DBG_ADVANCE_LINE -2
+            0x0e, // special: address += 0, line += 0
+            0x00, // DBG_END_SEQUENCE
+            0xff, // not part of the item
+        ];
+        let debug_info: DebugInfoItem = program
+            .pread_with(0, &dex)
+            .expect("cannot parse debug info");
+        assert_eq!(
+            debug_info.positions(),
+            &vec![
+                PositionEntry {
+                    address: 2,
+                    line: 11
+                },
+                PositionEntry {
+                    address: 5,
+                    line: 9
+                },
+            ]
+        );
+        assert_eq!(debug_info.line_for_address(0), None);
+        assert_eq!(debug_info.line_for_address(2), Some(11));
+        assert_eq!(debug_info.line_for_address(4), Some(11));
+        assert_eq!(debug_info.line_for_address(7), Some(9));
+
+        // names of the parameters are uleb128p1 encoded string ids.
+        let program = [1, 2, 0x01, 0x00, 0x00];
+        let debug_info: DebugInfoItem = program
+            .pread_with(0, &dex)
+            .expect("cannot parse debug info");
+        assert_eq!(
+            debug_info.parameter_names(),
+            &vec![Some(dex.get_string(0).expect("bad string")), None]
+        );
+        assert!(debug_info.positions().is_empty());
+        // the program must be terminated by DBG_END_SEQUENCE.
+        assert!(program[..4].pread_with::<DebugInfoItem>(0, &dex).is_err());
+
+        // line numbers can't be negative.
+        let program = [1, 0, 0x02, 0x7c, 0x0a, 0x00];
+        assert!(program.pread_with::<DebugInfoItem>(0, &dex).is_err());
+    }
+
+    #[test]
+    fn test_local_variables() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let program = [
+            1, 0, // line_start and parameters_size
+            0x03, 0x01, 0x01, 0x01, // DBG_START_LOCAL v1, string 0, type 0
+            0x01, 0x02, // DBG_ADVANCE_PC 2
+            0x04, 0x02, 0x02, 0x02,
+            0x03, // DBG_START_LOCAL_EXTENDED v2, strings 1 and 2, type 1
+            0x05, 0x01, // DBG_END_LOCAL v1
+            0x01, 0x03, // DBG_ADVANCE_PC 3
+            0x06, 0x01, // DBG_RESTART_LOCAL v1
+            0x06, 0x03, // DBG_RESTART_LOCAL v3, which never held a variable
+            0x01, 0x01, // DBG_ADVANCE_PC 1
+            0x03, 0x02, 0x00, 0x00, // DBG_START_LOCAL v2, without name nor type
+            0x00, // DBG_END_SEQUENCE
+        ];
+        let debug_info: DebugInfoItem = program
+            .pread_with(0, &dex)
+            .expect("cannot parse debug info");
+        let string = |id| Some(dex.get_string(id).expect("bad string"));
+        let jtype = |id| Some(dex.get_type(id).expect("bad type"));
+        let variable =
+            |register, name, jtype, signature, start_address, end_address| LocalVariable {
+                register,
+                name,
+                jtype,
+                signature,
+                start_address,
+                end_address,
+            };
+        let table = debug_info.local_variables();
+        assert_eq!(
+            table.variables(),
+            &vec![
+                variable(1, string(0), jtype(0), None, 0, Some(2)),
+                variable(2, string(1), jtype(1), string(2), 2, Some(6)),
+                variable(1, string(0), jtype(0), None, 5, None),
+                variable(2, None, None, None, 6, None),
+            ]
+        );
+        assert_eq!(table.variable_at(1, 1), Some(&table.variables()[0]));
+        assert_eq!(table.variable_at(1, 3), None);
+        assert_eq!(table.variable_at(1, 100), Some(&table.variables()[2]));
+        assert_eq!(table.variable_at(2, 5), Some(&table.variables()[1]));
+        assert_eq!(table.variable_at(3, 5), None);
+    }
+
+    #[test]
+    fn test_argument_locals() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let jtype = |descriptor| {
+            dex.get_type_from_descriptor(descriptor)
+                .expect("error finding type")
+                .expect("type not found")
+        };
+        let (this, long, int) = (jtype("Ljava/lang/Object;"), jtype("J"), jtype("I"));
+        let parameters = [long.clone(), int.clone()];
+        let arguments = Arguments {
+            this: Some(&this),
+            parameters: &parameters,
+        };
+        let program = [
+            1, 2, // line_start and parameters_size
+            0x01, 0x00, // names of the parameters, string 0 and none
+            0x01, 0x02, // DBG_ADVANCE_PC 2
+            0x05, 0x04, // DBG_END_LOCAL v4
+            0x01, 0x01, // DBG_ADVANCE_PC 1
+            0x06, 0x04, // DBG_RESTART_LOCAL v4
+            0x00, // DBG_END_SEQUENCE
+        ];
+        let debug_info: DebugInfoItem = program
+            .pread_with(0, (&dex, Some((1, arguments))))
+            .expect("cannot parse debug info");
+        let variable = |register, name, jtype: &crate::jtype::Type, start_address, end_address| {
+            LocalVariable {
+                register,
+                name,
+                jtype: Some(jtype.clone()),
+                signature: None,
+                start_address,
+                end_address,
+            }
+        };
+        let name = Some(dex.get_string(0).expect("bad string"));
+        assert_eq!(
+            debug_info.local_variables().variables(),
+            &vec![
+                variable(1, Some("this".to_string().into()), &this, 0, None),
+                variable(2, name, &long, 0, None),
+                variable(4, None, &int, 0, Some(2)),
+                variable(4, None, &int, 3, None),
+            ]
+        );
+        // without the arguments, the restart of v4 is ignored.
+        let debug_info: DebugInfoItem = program
+            .pread_with(0, &dex)
+            .expect("cannot parse debug info");
+        assert!(debug_info.local_variables().variables().is_empty());
+
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|method| method.name() == "setText")
+            .expect("method not found");
+        let code = method.code().expect("method has no code");
+        let table = code.local_variables().expect("no debug info");
+        let first = code.registers_size() - code.ins_size();
+        let this = table.variable_at(first, 0).expect("this is not a local");
+        assert_eq!(
+            this.name().as_ref().map(ToString::to_string),
+            Some("this".to_string())
+        );
+        assert_eq!(this.jtype().as_ref(), Some(class.jtype()));
+        let parameter = table
+            .variable_at(first + 1, 0)
+            .expect("parameter is not a local");
+        assert_eq!(parameter.jtype().as_ref(), Some(&method.params()[0]));
+        assert_eq!(parameter.name(), &method.parameter_names()[0]);
+    }
+}
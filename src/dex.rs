@@ -1,33 +1,61 @@
-use std::{fs::File, io::BufReader, ops::Range};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cell::OnceCell, ops::Range};
+#[cfg(feature = "std")]
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
 
 use adler32;
 use getset::{CopyGetters, Getters};
-use memmap::{Mmap, MmapOptions};
+#[cfg(feature = "std")]
+use memmap2::{Mmap, MmapOptions};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use scroll::{ctx, Pread};
+use scroll::{ctx, Pread, Uleb128};
+use sha1::{Digest, Sha1};
 
 use super::Result;
 use crate::{
     annotation::{
-        AnnotationItem, AnnotationSetItem, AnnotationSetRefList, AnnotationsDirectoryItem,
+        AnnotationItem, AnnotationSetItem, AnnotationSetRefList, AnnotationTarget,
+        AnnotationsDirectoryItem, EncodedAnnotation,
     },
-    class::{Class, ClassDataItem, ClassDefItem, ClassDefItemIter},
-    code::{CodeItem, DebugInfoItem},
+    cache::CacheStats,
+    callgraph::CallGraph,
+    class::{Class, ClassDataItem, ClassDefItem, ClassDefItemIter, ClassId, HierarchyEdge},
+    code::{Arguments, CodeItem, DebugInfoItem},
     encoded_value::{EncodedArray, EncodedValue},
     error::{self, Error},
-    field::{EncodedField, Field, FieldId, FieldIdItem},
-    jtype::{Type, TypeId},
+    field::{EncodedField, Field, FieldId, FieldIdItem, FieldRef},
+    hiddenapi::{self, HiddenApiFlags},
+    index::DexIndex,
+    instruction::{Instruction, InstructionIterator},
+    jtype::{self, Type, TypeId},
     method::{
-        EncodedMethod, Method, MethodHandleId, MethodHandleItem, MethodId, MethodIdItem, ProtoId,
-        ProtoIdItem,
+        EncodedMethod, Method, MethodHandleId, MethodHandleItem, MethodId, MethodIdItem,
+        MethodPattern, MethodRef, ProtoId, ProtoIdItem, Prototype,
     },
     search::Section,
     source::Source,
-    string::{DexString, StringId, Strings, StringsIter},
-    ubyte, uint, ulong, ushort, utils, Endian, ENDIAN_CONSTANT, NO_INDEX, REVERSE_ENDIAN_CONSTANT,
+    string::{
+        encode_mutf8, redact_mutf8, shannon_entropy, DexString, StringId, Strings, StringsIter,
+    },
+    ubyte, uint, ulong, ushort, utils, Endian, HashMap, HashSet, ENDIAN_CONSTANT, NO_INDEX,
+    REVERSE_ENDIAN_CONSTANT,
 };
-use std::path::Path;
+
+#[cfg(feature = "regex")]
+use crate::indicators::{IndicatorPatterns, NetworkIndicators};
+#[cfg(feature = "apk")]
+use crate::multidex::MultiDex;
+#[cfg(feature = "std")]
+use crate::profile::ArtProfile;
 
 /// Dex file header
 #[derive(Debug, Pread, CopyGetters)]
@@ -94,8 +122,42 @@ pub struct Header {
 }
 
 impl Header {
+    /// Version of the dex format, from the `magic`, e.g. `35` for `dex\n035\0`. The
+    /// version determines the instructions and sections which can appear in the file.
+    pub fn version(&self) -> uint {
+        self.magic[4..7]
+            .iter()
+            .fold(0, |version, digit| version * 10 + uint::from(digit - b'0'))
+    }
+
     fn data_section(&self) -> Range<uint> {
-        (self.data_off..self.data_off + self.data_size)
+        (self.data_off..self.data_off.saturating_add(self.data_size))
+    }
+
+    /// Checks that the sections described by the header are within the first `len`
+    /// bytes of the file, so that the ids below the section sizes can be read.
+    fn check_sections(&self, len: usize) -> Result<()> {
+        let sections = [
+            ("string_ids", self.string_ids_off, self.string_ids_size, 4),
+            ("type_ids", self.type_ids_off, self.type_ids_size, 4),
+            ("proto_ids", self.proto_ids_off, self.proto_ids_size, 12),
+            ("field_ids", self.field_ids_off, self.field_ids_size, 8),
+            ("method_ids", self.method_ids_off, self.method_ids_size, 8),
+            ("class_defs", self.class_defs_off, self.class_defs_size, 32),
+            ("data", self.data_off, self.data_size, 1),
+            ("link", self.link_off, self.link_size, 1),
+        ];
+        for (name, offset, size, item_size) in sections.iter() {
+            let end = ulong::from(*offset) + ulong::from(*size) * item_size;
+            if end > len as ulong {
+                debug!(target: "header", "{} section past the end of the file", name);
+                return Err(Error::OutOfBounds {
+                    offset: end,
+                    file_size: len,
+                });
+            }
+        }
+        Ok(())
     }
 }
 
@@ -172,6 +234,51 @@ impl DexInner {
     fn method_handles_len(&self) -> Option<uint> {
         self.map_list.get_len(ItemType::MethodHandleItem)
     }
+
+    fn hidden_api_offset(&self) -> Option<uint> {
+        self.map_list.get_offset(ItemType::HiddenapiClassDataItem)
+    }
+}
+
+/// Versions of the dex format which can be read, from the `magic` of the header.
+const SUPPORTED_VERSIONS: &[&[ubyte; 3]] = &[b"035", b"037", b"038", b"039", b"040", b"041"];
+
+/// Checks that `source` starts with the magic of a dex file of a supported version.
+/// CompactDex files, as found in the `.vdex` files generated by ART, are reported as
+/// `Error::UnsupportedFormat`: their offsets into the data section are relative to a
+/// data section which may be shared between several files, and their code items and
+/// debug info use a different encoding, so they can't be read as dex files.
+fn check_magic(source: &[u8]) -> Result<()> {
+    if source.starts_with(b"cdex") {
+        return Err(Error::UnsupportedFormat(format!(
+            "CompactDex files are not supported, magic: {}",
+            String::from_utf8_lossy(&source[..7])
+        )));
+    }
+    if &source[..4] != b"dex\n" || source[7] != 0 {
+        return Err(Error::MalFormed("Bad dex magic".to_string()));
+    }
+    let version = &source[4..7];
+    if !SUPPORTED_VERSIONS
+        .iter()
+        .any(|supported| &supported[..] == version)
+    {
+        return Err(Error::UnsupportedFormat(format!(
+            "Unsupported dex version: {}",
+            String::from_utf8_lossy(version)
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the endianness declared by the endian tag of the header in `source`.
+fn header_endian(source: &[u8]) -> Result<Endian> {
+    let endian_tag = &source[40..44];
+    match (endian_tag[0], endian_tag[1], endian_tag[2], endian_tag[3]) {
+        ENDIAN_CONSTANT => Ok(scroll::BE),
+        REVERSE_ENDIAN_CONSTANT => Ok(scroll::LE),
+        _ => Err(error::Error::MalFormed("Bad endian tag".to_string())),
+    }
 }
 
 // TODO: this should be try_from_dex
@@ -184,13 +291,10 @@ impl<'a> ctx::TryFromCtx<'a, ()> for DexInner {
             debug!("malformed dex: size < minimum header size");
             return Err(Error::MalFormed("Invalid dex file".to_string()));
         }
-        let endian_tag = &source[40..44];
-        let endian = match (endian_tag[0], endian_tag[1], endian_tag[2], endian_tag[3]) {
-            ENDIAN_CONSTANT => scroll::BE,
-            REVERSE_ENDIAN_CONSTANT => scroll::LE,
-            _ => return Err(error::Error::MalFormed("Bad endian tag".to_string())),
-        };
+        check_magic(source)?;
+        let endian = header_endian(source)?;
         let header = source.pread_with::<Header>(0, endian)?;
+        header.check_sections(source.len())?;
         if !header.data_section().contains(&header.map_off) {
             return Err(error::Error::BadOffset(
                 header.map_off as usize,
@@ -198,7 +302,7 @@ impl<'a> ctx::TryFromCtx<'a, ()> for DexInner {
             ));
         }
         let found = header.checksum();
-        let computed = adler32::adler32(BufReader::new(&source[12..]))?;
+        let computed = adler32::RollingAdler32::from_buffer(&source[12..]).hash();
         if computed != found {
             return Err(Error::MalFormed(format!(
                 "File corrupted, adler32 checksum doesn't match: computed: {}, found: {}",
@@ -206,6 +310,8 @@ impl<'a> ctx::TryFromCtx<'a, ()> for DexInner {
             )));
         }
 
+        let map_size: uint = source.pread_with(header.map_off as usize, endian)?;
+        utils::check_list_bounds(header.map_off as usize + 4, map_size, 12, source.len())?;
         let map_list = source.pread_with(header.map_off as usize, endian)?;
         debug!(target: "initialization", "header: {:?}, endian-ness: {:?}", header, endian);
         debug!(target: "initialization", "map_list: {:?}", map_list);
@@ -244,6 +350,11 @@ impl<'a> ctx::TryFromCtx<'a, Endian> for MapList {
 }
 
 impl MapList {
+    /// Items of the map, ordered by offset.
+    pub fn items(&self) -> &[MapItem] {
+        &self.map_items
+    }
+
     /// Returns the `MapItem` corresponding to the `ItemType`.
     pub fn get(&self, item_type: ItemType) -> Option<MapItem> {
         self.map_items
@@ -286,6 +397,7 @@ pub enum ItemType {
     AnnotationItem = 0x2004,
     EncodedArrayItem = 0x2005,
     AnnotationsDirectoryItem = 0x2006,
+    HiddenapiClassDataItem = 0xF000,
 }
 
 /// Single item of the MapList.
@@ -324,6 +436,111 @@ impl<'a> ctx::TryFromCtx<'a, Endian> for MapItem {
     }
 }
 
+/// Toolchain which likely produced a dex file, see `Dex::likely_compiler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompilerHint {
+    /// The D8 dexer.
+    D8,
+    /// The legacy dx dexer.
+    Dx,
+    /// The R8 shrinker.
+    R8,
+    /// None of the signals matched.
+    Unknown,
+}
+
+/// Histograms of the number of members of the classes of a dex, see
+/// `Dex::member_count_distribution`. Each histogram maps a member count to the number
+/// of classes with that many members.
+#[derive(Debug, Default, Getters)]
+#[get = "pub"]
+pub struct MemberDistribution {
+    /// Classes by their number of direct and virtual methods.
+    methods_per_class: BTreeMap<usize, usize>,
+    /// Classes by their number of static and instance fields.
+    fields_per_class: BTreeMap<usize, usize>,
+}
+
+impl MemberDistribution {
+    /// Largest number of methods defined in a class.
+    pub fn max_methods(&self) -> usize {
+        self.methods_per_class.keys().last().cloned().unwrap_or(0)
+    }
+
+    /// Largest number of fields defined in a class.
+    pub fn max_fields(&self) -> usize {
+        self.fields_per_class.keys().last().cloned().unwrap_or(0)
+    }
+}
+
+/// Summary counts of a dex, see `Dex::stats`. The `referenced_*` counts are the
+/// sizes of the id sections, which are limited to `MAX_REFERENCES` entries, and include
+/// the members defined in other dex files or in the framework.
+#[derive(Debug, Default, Clone, Copy, PartialEq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct DexStats {
+    /// Version of the dex format, see `Header::version`.
+    version: uint,
+    /// Number of classes defined in the dex.
+    classes: usize,
+    /// Number of methods defined by the classes of the dex.
+    defined_methods: usize,
+    /// Number of methods in the method_ids section.
+    referenced_methods: usize,
+    /// Number of fields defined by the classes of the dex.
+    defined_fields: usize,
+    /// Number of fields in the field_ids section.
+    referenced_fields: usize,
+    /// Number of strings in the string_ids section.
+    strings: usize,
+    /// Number of types in the type_ids section.
+    types: usize,
+    /// Total size, in bytes, of the instructions of the methods defined in the dex.
+    bytecode_size: usize,
+}
+
+/// A non-fatal issue found while reading a dex file with `DexReader::lenient`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    /// The size of the file is not the `file_size` of the header. The file is
+    /// truncated if `actual` is smaller.
+    FileSizeMismatch { declared: uint, actual: usize },
+    /// The access flags of the class have bits which are not class flags. These
+    /// bits are ignored when parsing the class.
+    UnknownAccessFlags {
+        class_id: ClassId,
+        access_flags: uint,
+    },
+    /// The access flags of the field have bits which are not field flags. These
+    /// bits are ignored when parsing the field.
+    UnknownFieldAccessFlags {
+        field_id: FieldId,
+        access_flags: ulong,
+    },
+    /// The access flags of the method have bits which are not method flags. These
+    /// bits are ignored when parsing the method.
+    UnknownMethodAccessFlags {
+        method_id: MethodId,
+        access_flags: ulong,
+    },
+    /// The debug info of the method can't be read, e.g. it is truncated. The code
+    /// of the method is parsed without it.
+    InvalidDebugInfo {
+        method_id: MethodId,
+        debug_info_off: uint,
+    },
+    /// The hidden API flags of the class can't be read, e.g. its
+    /// `hiddenapi_class_data_item` is truncated. The members of the class are parsed
+    /// without them.
+    InvalidHiddenApiFlags { class_id: ClassId },
+    /// The strings are not sorted by contents, starting with this string. Lookups
+    /// by name may fail.
+    UnsortedStrings { string_id: StringId },
+    /// The type ids are not sorted by string index, starting with this type. Lookups
+    /// by descriptor may fail.
+    UnsortedTypeIds { type_id: TypeId },
+}
+
 /// Represents a Dex file
 pub struct Dex<T> {
     /// Source from which this Dex file is loaded from.
@@ -331,6 +548,12 @@ pub struct Dex<T> {
     /// Items in string_ids section are cached here.
     pub(crate) strings: Strings<T>,
     pub(crate) inner: DexInner,
+    /// Whether the file was read with `DexReader::lenient`.
+    pub(crate) lenient: bool,
+    warnings: Vec<ParseWarning>,
+    /// Methods loading each string with a `const-string` instruction, indexed on the
+    /// first call to `string_xrefs`.
+    string_xrefs: OnceCell<HashMap<StringId, Vec<MethodId>>>,
 }
 
 impl<T> Dex<T>
@@ -342,6 +565,83 @@ where
         self.inner.header()
     }
 
+    /// Adler32 checksum of the file, as recorded in the header.
+    pub fn checksum(&self) -> uint {
+        self.header().checksum()
+    }
+
+    /// SHA-1 signature of the file, as recorded in the header.
+    pub fn signature(&self) -> [ubyte; 20] {
+        self.header().signature()
+    }
+
+    /// Returns `true` if the checksum in the header matches the adler32 checksum of the
+    /// file after the checksum field. The checksum is also checked when the file is
+    /// read, so this only fails if the source was modified since.
+    pub fn verify_checksum(&self) -> Result<bool> {
+        let contents = self
+            .source
+            .as_ref()
+            .get(12..)
+            .ok_or_else(|| Error::MalFormed("Invalid dex file".to_string()))?;
+        Ok(adler32::RollingAdler32::from_buffer(contents).hash() == self.checksum())
+    }
+
+    /// Returns `true` if the signature in the header matches the SHA-1 digest of the
+    /// file after the signature field.
+    pub fn verify_signature(&self) -> Result<bool> {
+        let contents = self
+            .source
+            .as_ref()
+            .get(32..)
+            .ok_or_else(|| Error::MalFormed("Invalid dex file".to_string()))?;
+        Ok(Sha1::digest(contents).as_slice() == self.signature())
+    }
+
+    /// Non-fatal issues found when reading the file, including the recoveries made
+    /// when parsing its classes. Always empty unless the file was read with
+    /// `DexReader::lenient`.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Checks the file for the issues reported by `warnings`, then parses every class
+    /// to collect the recoveries made along the way. Classes which fail to parse are
+    /// skipped, their error is returned when they are parsed again.
+    fn collect_warnings(&self) -> Result<Vec<ParseWarning>> {
+        let mut warnings = Vec::new();
+        let (declared, actual) = (self.header().file_size(), self.source.as_ref().len());
+        if declared as usize != actual {
+            warnings.push(ParseWarning::FileSizeMismatch { declared, actual });
+        }
+        let mut previous: Option<Vec<ubyte>> = None;
+        for string_id in 0..self.inner.strings_len() {
+            let string = encode_mutf8(&self.get_string(string_id)?).into_owned();
+            if previous.as_ref().is_some_and(|previous| *previous > string) {
+                warnings.push(ParseWarning::UnsortedStrings { string_id });
+                break;
+            }
+            previous = Some(string);
+        }
+        let type_ids_offset = self.inner.type_ids_offset() as usize;
+        let mut previous = None;
+        for type_id in 0..self.inner.type_ids_len() {
+            let string_id: StringId = self
+                .source
+                .as_ref()
+                .pread_with(type_ids_offset + type_id as usize * 4, self.get_endian())?;
+            if previous.is_some_and(|previous| previous >= string_id) {
+                warnings.push(ParseWarning::UnsortedTypeIds { type_id });
+                break;
+            }
+            previous = Some(string_id);
+        }
+        for (index, class_def) in self.class_defs().enumerate() {
+            let _ = Class::parse(self, &class_def?, index, &mut warnings);
+        }
+        Ok(warnings)
+    }
+
     pub fn map_list(&self) -> &MapList {
         &self.inner.map_list
     }
@@ -362,14 +662,124 @@ where
     /// Returns a reference to the `DexString` represented by the given id.
     pub fn get_string(&self, string_id: StringId) -> Result<DexString> {
         if self.inner.strings_len() <= string_id {
-            return Err(Error::InvalidId(format!(
-                "Invalid string id: {}",
-                string_id
-            )));
+            return Err(Error::InvalidStringIndex {
+                index: string_id,
+                max: self.inner.strings_len(),
+            });
         }
         self.strings.get(string_id)
     }
 
+    /// Returns the offset from the start of the file to the string data of the
+    /// string represented by the given id.
+    pub fn string_data_offset(&self, string_id: StringId) -> Result<uint> {
+        if self.inner.strings_len() <= string_id {
+            return Err(Error::InvalidStringIndex {
+                index: string_id,
+                max: self.inner.strings_len(),
+            });
+        }
+        self.strings.data_offset(string_id)
+    }
+
+    /// Returns a copy of the dex file in which every string matching the predicate
+    /// is redacted. Each character of a redacted string is replaced by a placeholder
+    /// (`*`, `¤` or `■`) with the same encoded length, so the layout of the file
+    /// doesn't change. The checksum and signature of the copy are updated.
+    ///
+    /// Redacted strings are not re-sorted, so the string_ids section may no longer be
+    /// sorted by contents, which breaks lookups by name such as `find_class_by_name`.
+    pub fn redact_strings<P>(&self, mut predicate: P) -> Result<Vec<ubyte>>
+    where
+        P: FnMut(&DexString) -> bool,
+    {
+        let mut contents = self.source.as_ref().to_vec();
+        for string_id in 0..self.inner.strings_len() {
+            let string = self.get_string(string_id)?;
+            if !predicate(&string) {
+                continue;
+            }
+            let offset = &mut (self.string_data_offset(string_id)? as usize);
+            let _ = Uleb128::read(&contents, offset)?;
+            let len = encode_mutf8(&string).len();
+            redact_mutf8(&mut contents[*offset..*offset + len]);
+        }
+        utils::update_checksums(&mut contents, self.get_endian())?;
+        Ok(contents)
+    }
+
+    /// Returns the methods of this dex which are present in the profile. The profile
+    /// data of this dex is found using the checksum of the file.
+    #[cfg(feature = "std")]
+    pub fn apply_profile(&self, profile: &ArtProfile) -> Result<HashSet<MethodId>> {
+        let mut methods = HashSet::new();
+        let checksum = self.header().checksum();
+        for dex_data in profile
+            .dex_files()
+            .iter()
+            .filter(|d| d.checksum() == checksum)
+        {
+            if dex_data.num_method_ids() != self.inner.method_ids_len() {
+                return Err(Error::MalFormed(format!(
+                    "Profile for {} has {} method ids, dex has {}",
+                    dex_data.profile_key(),
+                    dex_data.num_method_ids(),
+                    self.inner.method_ids_len()
+                )));
+            }
+            methods.extend(dex_data.methods());
+        }
+        Ok(methods)
+    }
+
+    /// Returns the strings which look like encrypted data rather than text. A string
+    /// is flagged if it is made of control characters for the most part, or if it has
+    /// at least `ENCRYPTED_STRING_MIN_LEN` characters, no whitespace and a Shannon
+    /// entropy of at least `ENCRYPTED_STRING_ENTROPY` times the entropy of a random
+    /// string of the same length and alphabet (hexadecimal digits or base64).
+    pub fn likely_encrypted_strings(&self) -> Result<Vec<DexString>> {
+        let mut strings = Vec::new();
+        for string in self.strings() {
+            let string = string?;
+            let len = string.chars().count();
+            let non_printable = string
+                .chars()
+                .filter(|c| c.is_control() || *c == core::char::REPLACEMENT_CHARACTER)
+                .count();
+            let is_encrypted = if non_printable * 2 > len {
+                true
+            } else if len < ENCRYPTED_STRING_MIN_LEN || string.chars().any(char::is_whitespace) {
+                false
+            } else {
+                let alphabet = if string.chars().all(|c| c.is_ascii_hexdigit()) {
+                    16
+                } else {
+                    64
+                };
+                let max_entropy = (len.min(alphabet) as f64).log2();
+                shannon_entropy(&string) >= ENCRYPTED_STRING_ENTROPY * max_entropy
+            };
+            if is_encrypted {
+                strings.push(string);
+            }
+        }
+        Ok(strings)
+    }
+
+    /// Returns the strings naming an Android permission, i.e. starting with
+    /// `android.permission.`. These are the permissions referenced from code, which
+    /// may differ from the ones requested in the manifest.
+    pub fn permission_strings(&self) -> Result<Vec<DexString>> {
+        let mut strings = Vec::new();
+        for string in self.strings() {
+            let string = string?;
+            if string.starts_with("android.permission.") {
+                strings.push(string);
+            }
+        }
+        Ok(strings)
+    }
+
     /// Returns the `Type` corresponding to the descriptor.
     pub fn get_type_from_descriptor(&self, descriptor: &str) -> Result<Option<Type>> {
         if let Some(string_id) = self.strings.get_id(descriptor)? {
@@ -382,21 +792,33 @@ where
 
     /// Returns the `Type` represented by the give type_id.
     pub fn get_type(&self, type_id: TypeId) -> Result<Type> {
-        let max_offset = self.inner.type_ids_offset() + (self.inner.type_ids_len() - 1) * 4;
-        let offset = self.inner.type_ids_offset() + type_id * 4;
-        if offset > max_offset {
-            return Err(Error::InvalidId(format!("Invalid type id: {}", type_id)));
+        let type_descriptor = self.type_descriptor(type_id)?;
+        if !jtype::is_valid_descriptor(&type_descriptor) {
+            return Err(Error::InvalidDescriptor(format!(
+                "type id: {}, descriptor: {}",
+                type_id, type_descriptor
+            )));
         }
-        let string_id = self
-            .source
-            .as_ref()
-            .pread_with(offset as usize, self.get_endian())?;
-        self.get_string(string_id).map(|type_descriptor| Type {
+        Ok(Type {
             id: type_id,
             type_descriptor,
         })
     }
 
+    /// Returns the descriptor of the type without building a `Type`. Unlike `get_type`,
+    /// the syntax of the descriptor is not checked.
+    pub fn type_descriptor(&self, type_id: TypeId) -> Result<DexString> {
+        if type_id >= self.inner.type_ids_len() {
+            return Err(Error::InvalidTypeIndex {
+                index: type_id,
+                max: self.inner.type_ids_len(),
+            });
+        }
+        let offset = self.inner.type_ids_offset() as usize + type_id as usize * 4;
+        let string_id = self.source.as_ref().pread_with(offset, self.get_endian())?;
+        self.get_string(string_id)
+    }
+
     pub(crate) fn get_type_id(&self, string_id: StringId) -> Result<Option<TypeId>> {
         let types_section = self.type_ids_section();
         Ok(types_section
@@ -429,20 +851,63 @@ where
         Section::new(class_defs_section)
     }
 
-    pub(crate) fn find_class_by_type(&self, type_id: TypeId) -> Result<Option<Class>> {
-        for class_def in self.class_defs() {
+    /// Returns the class_def_item defining the type, along with its index in the
+    /// class_defs section.
+    pub(crate) fn find_class_def_by_type(
+        &self,
+        type_id: TypeId,
+    ) -> Result<Option<(usize, ClassDefItem)>> {
+        for (index, class_def) in self.class_defs().enumerate() {
             let class_def = class_def?;
             if class_def.class_idx == type_id {
-                return Ok(Some(Class::try_from_dex(self, &class_def)?));
+                return Ok(Some((index, class_def)));
             }
         }
         Ok(None)
     }
 
-    /// Finds `Class` by the given class name. The name should be in smali format.
+    pub(crate) fn find_class_by_type(&self, type_id: TypeId) -> Result<Option<Class>> {
+        self.find_class_def_by_type(type_id)?
+            .map(|(index, class_def)| Class::try_from_dex(self, &class_def, index))
+            .transpose()
+    }
+
+    /// Returns the `Class` defined in this dex for the class type `id`, or `None` if the
+    /// type has no definition in this dex. Returns an error if `id` is not a valid
+    /// `TypeId` or if it is not a class type.
+    pub fn class_from_id(&self, id: ClassId) -> Result<Option<Class>> {
+        let jtype = self.get_type(id)?;
+        if !jtype.is_class() {
+            return Err(Error::InvalidId(format!(
+                "Type {} is not a class type: {}",
+                id, jtype
+            )));
+        }
+        self.find_class_by_type(id)
+    }
+
+    /// Finds `Class` by the given class name, either in smali format
+    /// (`Lcom/example/Foo;`) or in Java format (`com.example.Foo`).
     /// This method uses binary search to find the class definition using the property
     /// that the strings, type ids and class defs sections are in sorted.
-    pub fn find_class_by_name(&self, type_descriptor: &str) -> Result<Option<Class>> {
+    /// If the class is defined more than once, the first definition in the class_defs
+    /// section is returned. See `duplicate_class_defs`.
+    pub fn find_class_by_name(&self, name: &str) -> Result<Option<Class>> {
+        match self.find_class_type_id(name)? {
+            Some(type_id) => self.find_class_by_type(type_id),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the `TypeId` of the class type with the given name, in either of the
+    /// formats of `find_class_by_name`, or `None` if there is no such type.
+    fn find_class_type_id(&self, name: &str) -> Result<Option<TypeId>> {
+        let type_descriptor = if name.starts_with('L') && name.ends_with(';') {
+            alloc::borrow::Cow::Borrowed(name)
+        } else {
+            alloc::borrow::Cow::Owned(format!("L{};", name.replace('.', "/")))
+        };
+        let type_descriptor = &*type_descriptor;
         let string_id = self.strings.get_id(type_descriptor)?;
         if string_id.is_none() {
             debug!(target: "find-class-by-name", "class name: {} not found in strings", type_descriptor);
@@ -451,86 +916,251 @@ where
         let type_id = self.get_type_id(string_id.unwrap())?;
         if type_id.is_none() {
             debug!(target: "find-class-by-name", "no type id found for string id: {}", string_id.unwrap());
-            return Ok(None);
         }
-        self.find_class_by_type(type_id.unwrap())
+        Ok(type_id)
+    }
+
+    /// Returns the classes defined in this dex whose direct super class is the class
+    /// with the given name, in either of the formats of `find_class_by_name`. The
+    /// named class doesn't need to be defined in this dex.
+    pub fn subclasses_of(&self, class_name: &str) -> Result<Vec<Class>> {
+        let type_id = match self.find_class_type_id(class_name)? {
+            Some(type_id) => type_id,
+            None => return Ok(Vec::new()),
+        };
+        self.classes_filtered(|class_def| class_def.superclass_idx == type_id)
+            .collect()
+    }
+
+    /// Returns the super types of the class with the given name, in either of the
+    /// formats of `find_class_by_name`, from its super class up to `java.lang.Object`
+    /// or to the first super type which is not defined in this dex. The list is empty
+    /// if the class is not defined in this dex. The classes are not parsed. Returns
+    /// `Error::CyclicHierarchy` if a class is its own ancestor.
+    pub fn ancestors_of(&self, class_name: &str) -> Result<Vec<Type>> {
+        let type_id = match self.find_class_type_id(class_name)? {
+            Some(type_id) => type_id,
+            None => return Ok(Vec::new()),
+        };
+        let mut visited = HashSet::new();
+        visited.insert(type_id);
+        let mut ancestors = Vec::new();
+        let mut class_def = self.find_class_def_by_type(type_id)?;
+        while let Some((_, def)) = class_def {
+            if def.superclass_idx == NO_INDEX {
+                break;
+            }
+            let super_type = self.get_type(def.superclass_idx)?;
+            if !visited.insert(def.superclass_idx) {
+                return Err(Error::CyclicHierarchy(
+                    super_type.type_descriptor().to_string(),
+                ));
+            }
+            class_def = self.find_class_def_by_type(def.superclass_idx)?;
+            ancestors.push(super_type);
+        }
+        Ok(ancestors)
+    }
+
+    /// Returns the classes defined in this dex which directly implement the interface
+    /// with the given name, in either of the formats of `find_class_by_name`. The
+    /// interface doesn't need to be defined in this dex.
+    pub fn implementors_of(&self, interface_name: &str) -> Result<Vec<Class>> {
+        let type_id = match self.find_class_type_id(interface_name)? {
+            Some(type_id) => type_id,
+            None => return Ok(Vec::new()),
+        };
+        let mut implementors = Vec::new();
+        for (index, class_def) in self.class_defs().enumerate() {
+            let class_def = class_def?;
+            if self
+                .get_type_id_list(class_def.interfaces_off)?
+                .contains(&type_id)
+            {
+                implementors.push(Class::try_from_dex(self, &class_def, index)?);
+            }
+        }
+        Ok(implementors)
+    }
+
+    /// Finds the `Method` with the given signature, e.g. `Lcom/example/Foo;->bar(I)V`.
+    /// Returns `None` if the class or the method is not defined in this dex.
+    pub fn find_method_by_signature(&self, signature: &str) -> Result<Option<Method>> {
+        let invalid =
+            || Error::InvalidDescriptor(format!("Invalid method signature: {}", signature));
+        let (class_name, rest) = signature.split_once("->").ok_or_else(invalid)?;
+        let (name, rest) = rest.split_once('(').ok_or_else(invalid)?;
+        let (params, return_type) = rest.split_once(')').ok_or_else(invalid)?;
+        let params = jtype::split_descriptors(params).ok_or_else(invalid)?;
+        if name.is_empty()
+            || !jtype::is_valid_descriptor(class_name)
+            || !jtype::is_valid_descriptor(return_type)
+        {
+            return Err(invalid());
+        }
+        let class = match self.find_class_by_name(class_name)? {
+            Some(class) => class,
+            None => return Ok(None),
+        };
+        Ok(class
+            .direct_methods
+            .into_iter()
+            .chain(class.virtual_methods)
+            .find(|method| {
+                method.name() == name
+                    && method.return_type() == return_type
+                    && method.params().len() == params.len()
+                    && method
+                        .params()
+                        .iter()
+                        .zip(&params)
+                        .all(|(param, descriptor)| param == descriptor)
+            }))
+    }
+
+    /// Returns the methods defined in this dex which match `pattern`, in the order of
+    /// the classes. Returns `Error::InvalidDescriptor` if the parameters of the pattern
+    /// have a `..` which is not the last one.
+    pub fn find_methods(&self, pattern: &MethodPattern) -> Result<Vec<MethodRef>> {
+        pattern.validate()?;
+        let mut methods = Vec::new();
+        for class in self.classes() {
+            for method in class?.methods().filter(|method| pattern.matches(method)) {
+                methods.push(self.resolve_method(method.id())?);
+            }
+        }
+        Ok(methods)
     }
 
     /// Returns the list of types which represent the interfaces of a class.
     pub fn get_interfaces(&self, offset: uint) -> Result<Vec<Type>> {
         debug!(target: "interfaces", "interfaces offset: {}", offset);
+        self.get_type_list(offset)
+    }
+
+    /// Returns the types of the `type_list` at `offset`, as used for the interfaces of
+    /// a class and the parameters of a prototype. An offset of `0` denotes an empty list.
+    pub fn get_type_list(&self, offset: uint) -> Result<Vec<Type>> {
+        self.get_type_id_list(offset)?
+            .into_iter()
+            .map(|type_id| self.get_type(type_id))
+            .collect()
+    }
+
+    /// Returns the `TypeId`s of the `type_list` at `offset`, without resolving them.
+    /// See `get_type_list`.
+    pub fn get_type_id_list(&self, offset: uint) -> Result<Vec<TypeId>> {
         if offset == 0 {
             return Ok(Default::default());
         }
         if !self.is_offset_in_data_section(offset) {
             return Err(Error::BadOffset(
                 offset as usize,
-                "Interfaces offset not in data section".to_string(),
+                "Type list offset not in data section".to_string(),
             ));
         }
+        if !offset.is_multiple_of(4) {
+            return Err(Error::MalFormed(format!(
+                "Type list offset {} is not 4-byte aligned",
+                offset
+            )));
+        }
         let mut offset = offset as usize;
-        let source = &self.source;
+        let source = self.source.as_ref();
         let endian = self.get_endian();
         let len = source.gread_with::<uint>(&mut offset, endian)?;
-        debug!(target: "interfaces", "interfaces length: {}", len);
+        debug!(target: "type-list", "type list length: {}", len);
+        utils::check_list_bounds(offset, len, 2, source.len())?;
         let offset = &mut offset;
         let type_ids: Vec<ushort> = try_gread_vec_with!(source, offset, len, endian);
-        utils::get_types(self, &type_ids)
+        Ok(type_ids.into_iter().map(TypeId::from).collect())
     }
 
     /// Returns the `FieldIdItem` represented by a `FieldId`.
     pub fn get_field_item(&self, field_id: FieldId) -> Result<FieldIdItem> {
-        let offset = ulong::from(self.inner.field_ids_offset()) + field_id * 8;
-        let max_offset = self.inner.field_ids_offset() + (self.inner.field_ids_len() - 1) * 8;
-        let max_offset = ulong::from(max_offset);
-        debug!(target: "field-id-item", "current offset: {}, min_offset: {}, max_offset: {}",
-                offset, self.inner.field_ids_offset(), max_offset);
-        if offset > max_offset {
-            return Err(error::Error::InvalidId(format!(
-                "Invalid field id: {}",
-                field_id
-            )));
+        if field_id >= ulong::from(self.inner.field_ids_len()) {
+            return Err(Error::InvalidFieldIndex {
+                index: field_id,
+                max: self.inner.field_ids_len(),
+            });
         }
+        let offset = ulong::from(self.inner.field_ids_offset()) + field_id * 8;
+        debug!(target: "field-id-item", "current offset: {}", offset);
         FieldIdItem::try_from_dex(self, offset)
     }
 
     /// Returns the `ProtoIdItem` represented by `ProtoId`.
     pub fn get_proto_item(&self, proto_id: ProtoId) -> Result<ProtoIdItem> {
-        let offset = ulong::from(self.inner.proto_ids_offset()) + proto_id * 12;
-        let max_offset = ulong::from(self.inner.proto_ids_offset())
-            + ulong::from((self.inner.proto_ids_len() - 1) * 12);
-        debug!(target: "proto-item", "proto item current offset: {}, min_offset: {}, max_offset: {}",
-            offset, self.inner.proto_ids_offset(), max_offset);
-        if offset > max_offset {
-            return Err(error::Error::InvalidId(format!(
-                "Invalid proto id: {}",
-                proto_id
-            )));
+        if proto_id >= ulong::from(self.inner.proto_ids_len()) {
+            return Err(Error::InvalidProtoIndex {
+                index: proto_id,
+                max: self.inner.proto_ids_len(),
+            });
         }
+        let offset = ulong::from(self.inner.proto_ids_offset()) + proto_id * 12;
+        debug!(target: "proto-item", "proto item current offset: {}", offset);
         ProtoIdItem::try_from_dex(self, offset)
     }
 
+    /// Returns the `Prototype` represented by `ProtoId`.
+    pub fn get_prototype(&self, proto_id: ProtoId) -> Result<Prototype> {
+        Prototype::try_from_dex(self, &self.get_proto_item(proto_id)?)
+    }
+
     /// Returns the `MethodIdItem` represented by `MethodId`.
     pub fn get_method_item(&self, method_id: MethodId) -> Result<MethodIdItem> {
-        let offset = ulong::from(self.inner.method_ids_offset()) + method_id * 8;
-        let max_offset = self.inner.method_ids_offset() + (self.inner.method_ids_len() - 1) * 8;
-        let max_offset = ulong::from(max_offset);
-        debug!(target: "method-item", "method item current offset: {}, min_offset: {}, max_offset: {}",
-            offset, self.inner.method_ids_offset(), max_offset);
-        if offset > max_offset {
-            return Err(error::Error::InvalidId(format!(
-                "Invalid method id: {}",
-                method_id
-            )));
+        if method_id >= ulong::from(self.inner.method_ids_len()) {
+            return Err(Error::InvalidMethodIndex {
+                index: method_id,
+                max: self.inner.method_ids_len(),
+            });
         }
+        let offset = ulong::from(self.inner.method_ids_offset()) + method_id * 8;
+        debug!(target: "method-item", "method item current offset: {}", offset);
         MethodIdItem::try_from_dex(self, offset)
     }
 
-    /// Iterator over the strings
+    /// Resolves a `FieldId`, e.g. from an instruction, to the declaring class, name and
+    /// type of the field, whether or not the field is defined in this dex.
+    pub fn resolve_field(&self, field_id: FieldId) -> Result<FieldRef> {
+        FieldRef::try_from_dex(self, field_id)
+    }
+
+    /// Resolves a `MethodId`, e.g. from an invoke instruction, to the declaring class,
+    /// name and prototype of the method, whether or not the method is defined in this
+    /// dex. The `Method` defining it can be found by passing the signature the
+    /// `MethodRef` displays as to `find_method_by_signature`.
+    pub fn resolve_method(&self, method_id: MethodId) -> Result<MethodRef> {
+        MethodRef::try_from_dex(self, method_id)
+    }
+
+    /// Number of string lookups which were served from the string cache and which had
+    /// to decode the string, since the dex was read. See `DexReader::cache_capacity`.
+    pub fn string_cache_stats(&self) -> CacheStats {
+        self.strings.cache_stats()
+    }
+
+    /// Iterator over the strings, in the order of the string_ids section. A string
+    /// which can't be read is returned as an error without ending the iteration.
     pub fn strings(&self) -> impl Iterator<Item = Result<DexString>> {
         StringsIter::new(self.strings.clone(), self.inner.strings_len() as usize)
     }
 
+    /// Returns the decoded strings in the order of the string_ids section, which is
+    /// sorted by UTF-16 code units and has no duplicates in a valid dex file. Unlike
+    /// `strings`, the first string which can't be read fails the whole table.
+    pub fn string_table(&self) -> Result<Vec<String>> {
+        self.strings()
+            .map(|string| string.map(|string| string.to_string()))
+            .collect()
+    }
+
+    /// Returns `true` if the string_ids section contains `string`. The section is
+    /// binary searched, without decoding the strings.
+    pub fn contains_string(&self, string: &str) -> Result<bool> {
+        Ok(self.strings.get_id(string)?.is_some())
+    }
+
     /// Returns a `Field` given its component items.
     pub fn get_field(
         &self,
@@ -538,7 +1168,13 @@ where
         initial_value: Option<EncodedValue>,
         annotations: AnnotationSetItem,
     ) -> Result<Field> {
-        Field::try_from_dex(self, encoded_field, initial_value, annotations)
+        Field::try_from_dex(
+            self,
+            encoded_field,
+            initial_value,
+            annotations,
+            &mut Vec::new(),
+        )
     }
 
     /// Returns a `Method` given its component items.
@@ -553,9 +1189,31 @@ where
             encoded_method,
             method_annotations,
             parameter_annotations,
+            &mut Vec::new(),
         )
     }
 
+    /// Returns the hidden API flags of the first `count` members of the class defined by
+    /// the `class_def_index`th class_def_item, in the order of its `ClassDataItem`.
+    /// `None` if the dex file has no `hiddenapi_class_data_item` or no flags for the
+    /// class.
+    pub(crate) fn get_hidden_api_flags(
+        &self,
+        class_def_index: usize,
+        count: usize,
+    ) -> Result<Option<Vec<HiddenApiFlags>>> {
+        match self.inner.hidden_api_offset() {
+            Some(section_offset) => hiddenapi::class_flags(
+                self.source.as_ref(),
+                section_offset as usize,
+                class_def_index,
+                count,
+                self.get_endian(),
+            ),
+            None => Ok(None),
+        }
+    }
+
     /// Returns the `ClassDataItem` at the given offset.
     pub fn get_class_data(&self, offset: uint) -> Result<Option<ClassDataItem>> {
         debug!(target: "class-data", "class data offset: {}", offset);
@@ -571,20 +1229,142 @@ where
         Ok(Some(self.source.pread_with(offset as usize, self)?))
     }
 
+    /// Returns the histograms of the number of methods and fields per class. Only the
+    /// member counts at the start of the class data items are read, the members
+    /// themselves are not parsed. Classes without class data have no members.
+    pub fn member_count_distribution(&self) -> Result<MemberDistribution> {
+        let mut distribution = MemberDistribution::default();
+        for class_def in self.class_defs() {
+            let offset = class_def?.class_data_off;
+            let mut counts = [0; 4];
+            if offset != 0 {
+                if !self.is_offset_in_data_section(offset) {
+                    return Err(Error::BadOffset(
+                        offset as usize,
+                        "ClassData offset not in data section".to_string(),
+                    ));
+                }
+                let offset = &mut (offset as usize);
+                for count in counts.iter_mut() {
+                    *count = Uleb128::read(self.source.as_ref(), offset)? as usize;
+                }
+            }
+            // static fields, instance fields, direct methods, virtual methods
+            *distribution
+                .fields_per_class
+                .entry(counts[0] + counts[1])
+                .or_insert(0) += 1;
+            *distribution
+                .methods_per_class
+                .entry(counts[2] + counts[3])
+                .or_insert(0) += 1;
+        }
+        Ok(distribution)
+    }
+
+    /// Number of methods referenced by the dex, defined in it or not, from the header.
+    /// It can't exceed `MAX_REFERENCES`.
+    pub fn method_ref_count(&self) -> usize {
+        self.inner.method_ids_len() as usize
+    }
+
+    /// Number of fields referenced by the dex, defined in it or not, from the header.
+    /// It can't exceed `MAX_REFERENCES`.
+    pub fn field_ref_count(&self) -> usize {
+        self.inner.field_ids_len() as usize
+    }
+
+    /// Returns `true` if the method or the field reference count reaches `threshold`,
+    /// a fraction of `MAX_REFERENCES`, e.g. `0.95` to warn before adding code to the
+    /// dex makes it exceed the limit.
+    pub fn is_near_reference_limit(&self, threshold: f32) -> bool {
+        let limit = f64::from(threshold) * MAX_REFERENCES as f64;
+        self.method_ref_count().max(self.field_ref_count()) as f64 >= limit
+    }
+
+    /// Returns the counts of classes, members, strings and types of the dex, along
+    /// with the size of its bytecode, in one pass over the class data.
+    pub fn stats(&self) -> Result<DexStats> {
+        let header = self.header();
+        let mut stats = DexStats {
+            version: header.version(),
+            classes: header.class_defs_size() as usize,
+            referenced_methods: header.method_ids_size() as usize,
+            referenced_fields: header.field_ids_size() as usize,
+            strings: header.string_ids_size() as usize,
+            types: header.type_ids_size() as usize,
+            ..DexStats::default()
+        };
+        for class_def in self.class_defs() {
+            let class_data = match self.get_class_data(class_def?.class_data_off)? {
+                Some(class_data) => class_data,
+                None => continue,
+            };
+            let fields = class_data.static_fields().into_iter();
+            stats.defined_fields += fields
+                .chain(class_data.instance_fields())
+                .map(|fields| fields.len())
+                .sum::<usize>();
+            let methods = class_data.direct_methods().into_iter();
+            for method in methods
+                .chain(class_data.virtual_methods())
+                .flat_map(|methods| methods.as_slice())
+            {
+                stats.defined_methods += 1;
+                let code_offset = *method.code_offset();
+                if code_offset == 0 {
+                    continue;
+                }
+                if !self.is_offset_in_data_section(code_offset) {
+                    return Err(Error::BadOffset(
+                        code_offset as usize,
+                        "CodeItem offset not in data section".to_string(),
+                    ));
+                }
+                // insns_size, in 16-bit code units, follows the register and try
+                // counts and the debug_info_off of the code_item.
+                let insns_size: uint = self
+                    .source
+                    .as_ref()
+                    .pread_with(code_offset as usize + 12, self.get_endian())?;
+                stats.bytecode_size += insns_size as usize * 2;
+            }
+        }
+        Ok(stats)
+    }
+
     /// Returns the `MethodHandleItem` represented by the `MethodHandleId`.
     pub fn get_method_handle_item(
         &self,
         method_handle_id: MethodHandleId,
     ) -> Result<MethodHandleItem> {
-        let err = || Error::InvalidId(format!("Invalid method handle id: {}", method_handle_id));
-        let offset = self.inner.method_handles_offset().ok_or_else(err)?;
-        let len = self.inner.method_handles_len().ok_or_else(err)?;
-        let max_offset = offset + (len - 1) * 8;
-        let offset = offset + method_handle_id * 8;
-        if offset > max_offset {
-            return Err(err());
+        let len = self.inner.method_handles_len().unwrap_or(0);
+        let offset = match self.inner.method_handles_offset() {
+            Some(offset) if method_handle_id < len => offset,
+            _ => {
+                return Err(Error::InvalidMethodHandleIndex {
+                    index: method_handle_id,
+                    max: len,
+                })
+            }
+        };
+        let offset = &mut (offset as usize + method_handle_id as usize * 8);
+        self.source.gread_with(offset, self)
+    }
+
+    /// Returns the `len` bytes of the file starting at `offset`, or
+    /// `Error::OutOfBounds` with the end of the range if it extends past the end of
+    /// the file.
+    pub fn slice_at(&self, offset: uint, len: usize) -> Result<&[ubyte]> {
+        let start = offset as usize;
+        let source = self.source.as_ref();
+        match start.checked_add(len) {
+            Some(end) if end <= source.len() => Ok(&source[start..end]),
+            _ => Err(Error::OutOfBounds {
+                offset: ulong::from(offset).saturating_add(len as ulong),
+                file_size: source.len(),
+            }),
         }
-        self.source.gread_with(&mut (offset as usize), self)
     }
 
     /// Returns the endianness in the header section.
@@ -601,7 +1381,9 @@ where
         ClassDefItemIter::new(source, defs_offset, defs_len, endian)
     }
 
-    /// Iterator over the type_ids section.
+    /// Iterator over the type_ids section, in `TypeId` order. A type which can't be
+    /// read is returned as an error without ending the iteration. The descriptors are
+    /// resolved through the string cache.
     pub fn types(&self) -> impl Iterator<Item = Result<Type>> + '_ {
         let type_ids_len = self.inner.type_ids_len();
         (0..type_ids_len).map(move |type_id| self.get_type(type_id))
@@ -613,6 +1395,14 @@ where
         (0..proto_ids_len).map(move |proto_id| self.get_proto_item(ProtoId::from(proto_id)))
     }
 
+    /// Iterator over the prototypes of the proto_ids section, in `ProtoId` order. See
+    /// `get_prototype`. A prototype which can't be read is returned as an error
+    /// without ending the iteration.
+    pub fn prototypes(&self) -> impl Iterator<Item = Result<Prototype>> + '_ {
+        let proto_ids_len = self.inner.proto_ids_len();
+        (0..proto_ids_len).map(move |proto_id| self.get_prototype(ProtoId::from(proto_id)))
+    }
+
     /// Iterator over the field_ids section.
     pub fn field_ids(&self) -> impl Iterator<Item = Result<FieldIdItem>> + '_ {
         let field_ids_len = self.inner.field_ids_len();
@@ -633,76 +1423,714 @@ where
         })
     }
 
-    /// Iterator over the classes
+    /// Iterator over the classes. A class which cannot be parsed is yielded as an `Err`
+    /// and the iteration continues with the next class def, so the healthy classes of a
+    /// partially corrupt dex file can still be recovered by skipping the errors.
     pub fn classes(&self) -> impl Iterator<Item = Result<Class>> + '_ {
         self.class_defs()
-            .map(move |class_def_item| Class::try_from_dex(&self, &class_def_item?))
+            .enumerate()
+            .map(move |(index, class_def_item)| Class::try_from_dex(self, &class_def_item?, index))
     }
 
-    /// Returns the `CodeItem` at the offset.
-    pub fn get_code_item(&self, code_off: ulong) -> Result<Option<CodeItem>> {
-        if code_off == 0 {
-            return Ok(None);
-        }
-        if !self.is_offset_in_data_section(code_off as uint) {
-            return Err(Error::BadOffset(
-                code_off as usize,
-                "CodeItem offset not in data section".to_string(),
-            ));
-        }
-        Ok(Some(self.source.pread_with(code_off as usize, self)?))
+    /// Number of classes defined in this dex, i.e. the size of the class_defs section.
+    pub fn num_classes(&self) -> usize {
+        self.inner.class_defs_len() as usize
     }
 
-    /// Returns the `AnnotationItem` at the offset.
-    pub fn get_annotation_item(&self, annotation_off: uint) -> Result<AnnotationItem> {
-        debug!(target: "annotaion-item", "annotation item offset: {}", annotation_off);
-        if !self.is_offset_in_data_section(annotation_off) {
-            return Err(Error::BadOffset(
-                annotation_off as usize,
-                "AnnotationItem offset not in data section".to_string(),
-            ));
+    /// Returns the class of the `index`th class_def_item, in the order of `classes`.
+    /// Only that class is parsed, the class_def_items being of fixed size. Returns an
+    /// error if `index` is not less than `num_classes`.
+    pub fn class_at(&self, index: usize) -> Result<Class> {
+        if index >= self.num_classes() {
+            return Err(Error::InvalidClassDefIndex {
+                index,
+                max: self.inner.class_defs_len(),
+            });
         }
-        Ok(self.source.pread_with(annotation_off as usize, self)?)
+        let offset = self.inner.class_defs_offset() as usize + index * 32;
+        let class_def: ClassDefItem = self.source.as_ref().pread_with(offset, self.get_endian())?;
+        Class::try_from_dex(self, &class_def, index)
     }
 
-    /// Returns the `AnnotationSetItem` at the offset.
-    pub fn get_annotation_set_item(
+    /// Iterator over the methods which have code, i.e. which are neither abstract nor
+    /// native, whose `CodeItem` is `Method::code`. Classes are parsed one at a time as
+    /// the iterator advances, and their methods are moved out of them.
+    pub fn methods_with_code(&self) -> impl Iterator<Item = Result<Method>> + '_ {
+        self.classes().flat_map(|class| {
+            let (error, methods) = match class {
+                Ok(class) => (
+                    None,
+                    class
+                        .direct_methods
+                        .into_iter()
+                        .chain(class.virtual_methods),
+                ),
+                Err(e) => (Some(e), Vec::new().into_iter().chain(Vec::new())),
+            };
+            error
+                .into_iter()
+                .map(Err)
+                .chain(methods.filter(|method| method.code().is_some()).map(Ok))
+        })
+    }
+
+    /// Iterator over the type descriptors of the classes defined in this dex. The
+    /// class data is not parsed.
+    pub fn class_names(&self) -> impl Iterator<Item = Result<DexString>> + '_ {
+        self.class_defs().map(move |class_def_item| {
+            Ok(self
+                .get_type(class_def_item?.class_idx)?
+                .type_descriptor
+                .clone())
+        })
+    }
+
+    /// Iterator over the classes whose `ClassDefItem` satisfies the predicate.
+    /// Classes which don't match are skipped without being parsed.
+    pub fn classes_filtered<'a, P>(
+        &'a self,
+        mut predicate: P,
+    ) -> impl Iterator<Item = Result<Class>> + 'a
+    where
+        P: FnMut(&ClassDefItem) -> bool + 'a,
+    {
+        self.class_defs()
+            .enumerate()
+            .filter(move |(_, class_def_item)| match class_def_item {
+                Ok(class_def_item) => predicate(class_def_item),
+                Err(_) => true,
+            })
+            .map(move |(index, class_def_item)| Class::try_from_dex(self, &class_def_item?, index))
+    }
+
+    /// Returns the first class whose `ClassDefItem` satisfies the predicate. The predicate
+    /// runs before the classes are parsed, so it only sees the fields of the
+    /// `ClassDefItem`s, and only the matching class is parsed.
+    pub fn find_class<P>(&self, predicate: P) -> Result<Option<Class>>
+    where
+        P: FnMut(&ClassDefItem) -> bool,
+    {
+        self.classes_filtered(predicate).next().transpose()
+    }
+
+    /// Returns the URLs, domain names and IP addresses found in the strings of this dex,
+    /// using the default patterns.
+    #[cfg(feature = "regex")]
+    pub fn extract_network_indicators(&self) -> Result<NetworkIndicators> {
+        self.extract_network_indicators_with(&IndicatorPatterns::default())
+    }
+
+    /// Returns the URLs, domain names and IP addresses found in the strings of this dex,
+    /// using the given patterns.
+    #[cfg(feature = "regex")]
+    pub fn extract_network_indicators_with(
         &self,
-        annotation_set_item_off: uint,
-    ) -> Result<AnnotationSetItem> {
-        debug!(target: "annotation-set-item", "annotation set item offset: {}", annotation_set_item_off);
-        if annotation_set_item_off == 0 {
-            return Ok(Default::default());
+        patterns: &IndicatorPatterns,
+    ) -> Result<NetworkIndicators> {
+        let mut indicators = NetworkIndicators::default();
+        for string in self.strings() {
+            indicators.scan(&string?, patterns);
         }
-        if !self.is_offset_in_data_section(annotation_set_item_off) {
-            return Err(Error::BadOffset(
-                annotation_set_item_off as usize,
-                "AnnotationSetItem offset not in data section".to_string(),
-            ));
-        }
-        self.source
-            .pread_with(annotation_set_item_off as usize, self)
+        Ok(indicators)
     }
 
-    /// Returns the `AnnotationSetRefList` at the offset.
-    pub fn get_annotation_set_ref_list(
+    /// Returns the classes with an annotation of type `annotation` whose element `key`
+    /// has the given value, e.g. the classes annotated with `@Route(path = "/login")`.
+    pub fn find_classes_with_annotation_element(
         &self,
-        annotation_set_ref_list_off: uint,
-    ) -> Result<AnnotationSetRefList> {
-        if !self.is_offset_in_data_section(annotation_set_ref_list_off) {
-            return Err(Error::BadOffset(
-                annotation_set_ref_list_off as usize,
-                "AnnotationSetRefList offset not in data section".to_string(),
-            ));
+        annotation: &str,
+        key: &str,
+        value: &EncodedValue,
+    ) -> Result<Vec<Class>> {
+        let mut classes = Vec::new();
+        for class in self.classes() {
+            let class = class?;
+            let matches = class.annotations().iter().any(|item| {
+                item.jtype() == annotation
+                    && item
+                        .annotation()
+                        .find_element(key)
+                        .is_some_and(|element| element.value() == value)
+            });
+            if matches {
+                classes.push(class);
+            }
         }
-        Ok(self
-            .source
-            .pread_with(annotation_set_ref_list_off as usize, self)?)
+        Ok(classes)
     }
 
-    /// Returns the `EncodedArray` representing the static values of a class at the given offset.
-    pub fn get_static_values(&self, static_values_off: uint) -> Result<EncodedArray> {
-        debug!(target: "class", "static values offset: {}", static_values_off);
+    /// Returns the classes annotated with the annotation type `type_name`, in either of
+    /// the formats of `find_class_by_name`, e.g. `androidx.annotation.Keep`, whatever
+    /// the visibility of the annotation. Only the matching classes are parsed.
+    pub fn classes_with_annotation(&self, type_name: &str) -> Result<Vec<Class>> {
+        let type_id = match self.find_class_type_id(type_name)? {
+            Some(type_id) => type_id,
+            None => return Ok(Vec::new()),
+        };
+        let mut classes = Vec::new();
+        for (index, class_def) in self.class_defs().enumerate() {
+            let class_def = class_def?;
+            let directory = self.get_annotations_directory_item(class_def.annotations_off)?;
+            if directory
+                .class_annotations
+                .iter()
+                .any(|item| item.jtype().id() == type_id)
+            {
+                classes.push(Class::try_from_dex(self, &class_def, index)?);
+            }
+        }
+        Ok(classes)
+    }
+
+    /// Returns the methods defined in this dex which are annotated with the annotation
+    /// type `type_name`, in either of the formats of `find_class_by_name`, e.g.
+    /// `android.webkit.JavascriptInterface`. The annotations directories are read
+    /// without parsing the classes.
+    pub fn methods_with_annotation(&self, type_name: &str) -> Result<Vec<MethodRef>> {
+        let type_id = match self.find_class_type_id(type_name)? {
+            Some(type_id) => type_id,
+            None => return Ok(Vec::new()),
+        };
+        let mut methods = Vec::new();
+        for class_def in self.class_defs() {
+            let directory = self.get_annotations_directory_item(class_def?.annotations_off)?;
+            for method in &directory.method_annotations {
+                if method
+                    .annotations
+                    .iter()
+                    .any(|item| item.jtype().id() == type_id)
+                {
+                    methods.push(self.resolve_method(method.method_idx())?);
+                }
+            }
+        }
+        Ok(methods)
+    }
+
+    /// Returns the annotations visible at runtime, i.e. through reflection, of all the
+    /// classes, fields, methods and method parameters defined in this dex, along with
+    /// the item each one is attached to.
+    pub fn runtime_visible_annotations(
+        &self,
+    ) -> Result<Vec<(AnnotationTarget, EncodedAnnotation)>> {
+        let mut annotations = Vec::new();
+        for class in self.classes() {
+            let class = class?;
+            let class_id = class.id();
+            annotations.extend(
+                class
+                    .annotations
+                    .into_runtime_visible()
+                    .map(|annotation| (AnnotationTarget::Class(class_id), annotation)),
+            );
+            for field in class.static_fields.into_iter().chain(class.instance_fields) {
+                let field_id = field.id();
+                annotations.extend(
+                    field
+                        .annotations
+                        .into_runtime_visible()
+                        .map(|annotation| (AnnotationTarget::Field(field_id), annotation)),
+                );
+            }
+            for method in class
+                .direct_methods
+                .into_iter()
+                .chain(class.virtual_methods)
+            {
+                let method_id = method.id();
+                annotations.extend(
+                    method
+                        .annotations
+                        .into_runtime_visible()
+                        .map(|annotation| (AnnotationTarget::Method(method_id), annotation)),
+                );
+                for (index, param) in method
+                    .param_annotations
+                    .into_inner()
+                    .into_iter()
+                    .enumerate()
+                {
+                    annotations.extend(param.into_runtime_visible().map(|annotation| {
+                        (AnnotationTarget::Parameter(method_id, index), annotation)
+                    }));
+                }
+            }
+        }
+        Ok(annotations)
+    }
+
+    /// Guesses the toolchain which produced this dex. The signals are checked in order,
+    /// the first one found decides:
+    /// * D8 and R8 append a marker string (`~~D8{...}`, `~~R8{...}`) to the string pool,
+    ///   which is a reliable signal unless it was stripped.
+    /// * Classes synthesized by D8 when desugaring lambdas are named `-$$Lambda$...` or
+    ///   `...$$ExternalSyntheticLambda...`. R8 output has the same classes, so this can
+    ///   also be R8 with the marker removed.
+    /// * dx places the annotation sets before the code items and the annotations
+    ///   directories before the string data, while D8 writes them after.
+    ///
+    /// This is a heuristic: files rewritten by other tools may be reported wrongly.
+    pub fn likely_compiler(&self) -> Result<CompilerHint> {
+        for string in self.strings() {
+            let string = string?;
+            if string.starts_with("~~R8{") {
+                return Ok(CompilerHint::R8);
+            }
+            if string.starts_with("~~D8{") {
+                return Ok(CompilerHint::D8);
+            }
+        }
+        for name in self.class_names() {
+            let name = name?;
+            if name.contains("-$$Lambda$") || name.contains("$$ExternalSynthetic") {
+                return Ok(CompilerHint::D8);
+            }
+        }
+        let offset = |item_type| self.map_list().get_offset(item_type);
+        let before = |first, second| match (offset(first), offset(second)) {
+            (Some(first), Some(second)) => first < second,
+            _ => false,
+        };
+        if before(ItemType::AnnotationSetItem, ItemType::CodeItem)
+            || before(ItemType::AnnotationsDirectoryItem, ItemType::StringDataItem)
+        {
+            return Ok(CompilerHint::Dx);
+        }
+        Ok(CompilerHint::Unknown)
+    }
+
+    /// Returns the ids of the classes defined more than once in the class_defs section,
+    /// in ascending order. The runtime only loads the first definition.
+    pub fn duplicate_class_defs(&self) -> Result<Vec<ClassId>> {
+        let mut seen = HashSet::new();
+        let mut duplicates = BTreeSet::new();
+        for class_def in self.class_defs() {
+            let class_id = class_def?.class_idx;
+            if !seen.insert(class_id) {
+                duplicates.insert(class_id);
+            }
+        }
+        Ok(duplicates.into_iter().collect())
+    }
+
+    /// Returns the classes which have a static initializer.
+    pub fn classes_with_clinit(&self) -> Result<Vec<Class>> {
+        let mut classes = Vec::new();
+        for class in self.classes() {
+            let class = class?;
+            if class.static_initializer().is_some() {
+                classes.push(class);
+            }
+        }
+        Ok(classes)
+    }
+
+    /// Returns the classes ordered so that the super class and the interfaces of a
+    /// class come before it, when they are defined in this dex. See
+    /// `classes_topological_with_cuts` for the handling of cycles.
+    pub fn classes_topological(&self) -> Result<Vec<Class>> {
+        Ok(self.classes_topological_with_cuts()?.0)
+    }
+
+    /// Same as `classes_topological` but also returns the edges, as pairs of a class
+    /// and its super class or interface, which were cut to break the cycles of a
+    /// malformed hierarchy. Classes are visited in the order of the class_defs
+    /// section, parents in the order super class then interfaces, and the edge
+    /// closing a cycle is cut, so the result is deterministic.
+    pub fn classes_topological_with_cuts(&self) -> Result<(Vec<Class>, Vec<HierarchyEdge>)> {
+        let classes = self.classes().collect::<Result<Vec<_>>>()?;
+        let mut index = HashMap::new();
+        for (i, class) in classes.iter().enumerate() {
+            index.entry(class.id()).or_insert(i);
+        }
+        let parents = |class: &Class| -> Result<Vec<usize>> {
+            Ok(class
+                .super_class()
+                .into_iter()
+                .chain(class.interfaces().iter().map(|jtype| jtype.id()))
+                .filter_map(|id| index.get(&id).copied())
+                .collect())
+        };
+        // 0: not visited, 1: on the stack, 2: done
+        let mut state = vec![0u8; classes.len()];
+        let mut order = Vec::with_capacity(classes.len());
+        let mut cuts = Vec::new();
+        for root in 0..classes.len() {
+            if state[root] != 0 {
+                continue;
+            }
+            state[root] = 1;
+            let mut stack = vec![(root, parents(&classes[root])?, 0)];
+            while let Some((node, node_parents, next)) = stack.last_mut() {
+                if let Some(&parent) = node_parents.get(*next) {
+                    *next += 1;
+                    match state[parent] {
+                        0 => {
+                            state[parent] = 1;
+                            let grand_parents = parents(&classes[parent])?;
+                            stack.push((parent, grand_parents, 0));
+                        }
+                        1 => cuts.push((classes[*node].id(), classes[parent].id())),
+                        _ => {}
+                    }
+                } else {
+                    state[*node] = 2;
+                    order.push(*node);
+                    stack.pop();
+                }
+            }
+        }
+        let mut classes = classes.into_iter().map(Some).collect::<Vec<_>>();
+        let classes = order
+            .into_iter()
+            .filter_map(|i| classes[i].take())
+            .collect();
+        Ok((classes, cuts))
+    }
+
+    /// Returns the methods which have a catch-all handler. See `Method::has_finally`.
+    pub fn methods_with_finally(&self) -> Result<Vec<Method>> {
+        let mut methods = Vec::new();
+        for class in self.classes() {
+            let class = class?;
+            methods.extend(
+                class
+                    .direct_methods
+                    .into_iter()
+                    .chain(class.virtual_methods)
+                    .filter(|method| method.has_finally()),
+            );
+        }
+        Ok(methods)
+    }
+
+    /// Returns the methods which invoke one of the `DYNAMIC_LOADING_APIS`, i.e. which may
+    /// load code at runtime.
+    pub fn dynamic_loading_sites(&self) -> Result<Vec<Method>> {
+        self.methods_invoking(DYNAMIC_LOADING_APIS)
+    }
+
+    /// Returns the methods which invoke one of the `REFLECTIVE_FIELD_ACCESS_APIS`, i.e.
+    /// which read or write fields through reflection, possibly bypassing their access
+    /// controls. Returns an error if the code of a method can't be decoded.
+    pub fn reflective_field_access(&self) -> Result<Vec<Method>> {
+        self.methods_invoking(REFLECTIVE_FIELD_ACCESS_APIS)
+    }
+
+    /// Returns the methods which invoke one of the `targets`, given as `Lclass;->name`.
+    /// All overloads of a target method match. Returns an error if the code of a method
+    /// can't be decoded.
+    pub fn methods_invoking(&self, targets: &[&str]) -> Result<Vec<Method>> {
+        let mut target_ids = HashSet::new();
+        for (method_id, method_item) in self.method_ids().enumerate() {
+            let method_item = method_item?;
+            let class = self.get_type(method_item.class_idx().into())?;
+            let name = self.get_string(method_item.name_idx())?;
+            let is_target = targets.iter().any(|target| {
+                target
+                    .split_once("->")
+                    .is_some_and(|(target_class, target_name)| {
+                        class == target_class && name == target_name
+                    })
+            });
+            if is_target {
+                target_ids.insert(method_id as MethodId);
+            }
+        }
+        let mut methods = Vec::new();
+        if target_ids.is_empty() {
+            return Ok(methods);
+        }
+        for method in self.methods_with_code() {
+            let method = method?;
+            let mut invokes = false;
+            if let Some(code) = method.code() {
+                for instruction in code.instructions() {
+                    match instruction? {
+                        (_, Instruction::Invoke { method_idx, .. })
+                        | (_, Instruction::InvokePolymorphic { method_idx, .. })
+                            if target_ids.contains(&method_idx) =>
+                        {
+                            invokes = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if invokes {
+                methods.push(method);
+            }
+        }
+        Ok(methods)
+    }
+
+    /// Returns the ids of the methods whose code loads `string` with a `const-string` or
+    /// `const-string/jumbo` instruction, in the order of the classes. The list is empty
+    /// if the string is not in this dex. The code of all the methods is scanned once,
+    /// on the first call, and the later calls look the string up in the index.
+    pub fn string_xrefs(&self, string: &str) -> Result<Vec<MethodId>> {
+        let string_id = match self.strings.get_id(string)? {
+            Some(string_id) => string_id,
+            None => return Ok(Vec::new()),
+        };
+        Ok(self
+            .string_xref_index()?
+            .get(&string_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Returns the methods loading each string, see `string_xrefs`.
+    fn string_xref_index(&self) -> Result<&HashMap<StringId, Vec<MethodId>>> {
+        if let Some(index) = self.string_xrefs.get() {
+            return Ok(index);
+        }
+        let mut index: HashMap<StringId, Vec<MethodId>> = HashMap::new();
+        for method in self.methods_with_code() {
+            let method = method?;
+            if let Some(code) = method.code() {
+                for string_id in const_string_ids(code)? {
+                    index.entry(string_id).or_default().push(method.id());
+                }
+            }
+        }
+        Ok(self.string_xrefs.get_or_init(|| index))
+    }
+
+    /// Returns the strings loaded by the `const-string` and `const-string/jumbo`
+    /// instructions of the method `method_id`, in the order of their first load. The
+    /// list is empty if the method is not defined in this dex or has no code.
+    pub fn method_string_constants(&self, method_id: MethodId) -> Result<Vec<DexString>> {
+        let method_item = self.get_method_item(method_id)?;
+        let class = match self.find_class_by_type(TypeId::from(method_item.class_idx()))? {
+            Some(class) => class,
+            None => return Ok(Vec::new()),
+        };
+        let constants = match class.methods().find(|method| method.id() == method_id) {
+            Some(method) => method
+                .code()
+                .map_or_else(|| Ok(Vec::new()), const_string_ids)?
+                .into_iter()
+                .map(|string_id| self.get_string(string_id))
+                .collect(),
+            None => Ok(Vec::new()),
+        };
+        constants
+    }
+
+    /// Returns the methods which build strings from literals with a `StringBuilder`, a
+    /// common way to hide string constants, along with the instructions of their
+    /// chains. A chain goes from the `new-instance` of the builder to the invocation of
+    /// `toString`, and is only reported if it appends at least two pieces, all loaded
+    /// by `const` instructions of the chain. The chains of a method follow each other,
+    /// each one starting with its `new-instance`.
+    pub fn string_builder_chains(&self) -> Result<Vec<(Method, Vec<Instruction>)>> {
+        let mut builder_type = None;
+        let mut calls = HashMap::new();
+        for (method_id, method_item) in self.method_ids().enumerate() {
+            let method_item = method_item?;
+            let class_idx = TypeId::from(method_item.class_idx());
+            if self.get_type(class_idx)? != "Ljava/lang/StringBuilder;" {
+                continue;
+            }
+            builder_type = Some(class_idx);
+            let call = match self
+                .get_string(method_item.name_idx())?
+                .to_string()
+                .as_str()
+            {
+                "<init>" => BuilderCall::Init,
+                "append" => BuilderCall::Append,
+                "toString" => BuilderCall::ToString,
+                _ => continue,
+            };
+            calls.insert(method_id as MethodId, call);
+        }
+        let mut methods = Vec::new();
+        let builder_type = match builder_type {
+            Some(builder_type) => builder_type,
+            None => return Ok(methods),
+        };
+        for method in self.methods_with_code() {
+            let method = method?;
+            let chains = match method.code() {
+                Some(code) => string_builder_chains_in(code.insns(), builder_type, &calls)?,
+                None => continue,
+            };
+            if !chains.is_empty() {
+                methods.push((method, chains));
+            }
+        }
+        Ok(methods)
+    }
+
+    /// Returns the call graph of the methods defined in this dex, built from the calls
+    /// of the reference index, see `index`.
+    pub fn call_graph(&self) -> Result<CallGraph> {
+        Ok(self.index()?.call_graph())
+    }
+
+    /// Returns an index of the classes, methods and fields of this dex along with the
+    /// methods and fields referenced by each method, see `DexIndex`.
+    pub fn index(&self) -> Result<DexIndex> {
+        DexIndex::try_from_dex(self)
+    }
+
+    /// Writes the index of this dex to `writer` in a compact binary format, which can
+    /// be loaded back with `Dex::import_index` without the dex file.
+    #[cfg(feature = "std")]
+    pub fn export_index<W: Write>(&self, writer: W) -> Result<()> {
+        self.index()?.export(writer)
+    }
+
+    /// Writes a CSV table of the methods defined in this dex to `writer`, one row per
+    /// method with the columns `class`, `name`, `signature`, `access_flags` and
+    /// `instructions`. Access flags are written in hexadecimal and methods without code
+    /// have 0 instructions. Rows are written as the classes are parsed, and an error is
+    /// returned if the code of a method can't be decoded.
+    #[cfg(feature = "std")]
+    pub fn export_methods_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "class,name,signature,access_flags,instructions")?;
+        for class in self.classes() {
+            let class = class?;
+            for method in class.methods() {
+                let params = method
+                    .params()
+                    .iter()
+                    .map(|param| param.to_string())
+                    .collect::<String>();
+                let signature = format!("({}){}", params, method.return_type());
+                let instructions = match method.code() {
+                    Some(code) => code
+                        .instructions()
+                        .try_fold(0, |count, instruction| instruction.map(|_| count + 1))?,
+                    None => 0,
+                };
+                writeln!(
+                    writer,
+                    "{},{},{},{:#x},{}",
+                    csv_field(&class.jtype().to_string()),
+                    csv_field(method.name()),
+                    csv_field(&signature),
+                    method.access_flags().bits(),
+                    instructions
+                )?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the `CodeItem` at the offset.
+    pub fn get_code_item(&self, code_off: uint) -> Result<Option<CodeItem>> {
+        self.code_item_at(code_off, None)
+    }
+
+    /// Returns the `CodeItem` at the offset, the local variables of its debug info
+    /// starting with the `arguments` of its method.
+    pub(crate) fn code_item_at(
+        &self,
+        code_off: uint,
+        arguments: Option<Arguments<'_>>,
+    ) -> Result<Option<CodeItem>> {
+        if code_off == 0 {
+            return Ok(None);
+        }
+        if !self.is_offset_in_data_section(code_off) {
+            return Err(Error::BadOffset(
+                code_off as usize,
+                "CodeItem offset not in data section".to_string(),
+            ));
+        }
+        Ok(Some(
+            self.source
+                .pread_with(code_off as usize, (self, arguments))?,
+        ))
+    }
+
+    /// Iterator over the code items listed in the map list, along with their offsets.
+    /// Unlike the code of the methods, this includes the code items which are not
+    /// referenced by any method. The iterator stops after the first error.
+    pub fn code_items(&self) -> impl Iterator<Item = Result<(uint, CodeItem)>> + '_ {
+        let (mut offset, mut remaining) = self
+            .map_list()
+            .get(ItemType::CodeItem)
+            .map_or((0, 0), |item| (item.offset() as usize, item.size()));
+        core::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            remaining -= 1;
+            let code_offset = offset as uint;
+            let code_item = if self.is_offset_in_data_section(code_offset) {
+                self.source
+                    .gread_with::<CodeItem>(&mut offset, (self, None))
+            } else {
+                Err(Error::BadOffset(
+                    offset,
+                    "CodeItem offset not in data section".to_string(),
+                ))
+            };
+            // code items are 4-byte aligned.
+            offset = (offset + 3) & !3;
+            if code_item.is_err() {
+                remaining = 0;
+            }
+            Some(code_item.map(|code_item| (code_offset, code_item)))
+        })
+    }
+
+    /// Returns the `AnnotationItem` at the offset.
+    pub fn get_annotation_item(&self, annotation_off: uint) -> Result<AnnotationItem> {
+        debug!(target: "annotaion-item", "annotation item offset: {}", annotation_off);
+        if !self.is_offset_in_data_section(annotation_off) {
+            return Err(Error::BadOffset(
+                annotation_off as usize,
+                "AnnotationItem offset not in data section".to_string(),
+            ));
+        }
+        Ok(self.source.pread_with(annotation_off as usize, self)?)
+    }
+
+    /// Returns the `AnnotationSetItem` at the offset.
+    pub fn get_annotation_set_item(
+        &self,
+        annotation_set_item_off: uint,
+    ) -> Result<AnnotationSetItem> {
+        debug!(target: "annotation-set-item", "annotation set item offset: {}", annotation_set_item_off);
+        if annotation_set_item_off == 0 {
+            return Ok(Default::default());
+        }
+        if !self.is_offset_in_data_section(annotation_set_item_off) {
+            return Err(Error::BadOffset(
+                annotation_set_item_off as usize,
+                "AnnotationSetItem offset not in data section".to_string(),
+            ));
+        }
+        self.source
+            .pread_with(annotation_set_item_off as usize, self)
+    }
+
+    /// Returns the `AnnotationSetRefList` at the offset.
+    pub fn get_annotation_set_ref_list(
+        &self,
+        annotation_set_ref_list_off: uint,
+    ) -> Result<AnnotationSetRefList> {
+        if !self.is_offset_in_data_section(annotation_set_ref_list_off) {
+            return Err(Error::BadOffset(
+                annotation_set_ref_list_off as usize,
+                "AnnotationSetRefList offset not in data section".to_string(),
+            ));
+        }
+        Ok(self
+            .source
+            .pread_with(annotation_set_ref_list_off as usize, self)?)
+    }
+
+    /// Returns the `EncodedArray` representing the static values of a class at the given offset.
+    pub fn get_static_values(&self, static_values_off: uint) -> Result<EncodedArray> {
+        debug!(target: "class", "static values offset: {}", static_values_off);
         if static_values_off == 0 {
             return Ok(Default::default());
         }
@@ -730,12 +2158,54 @@ where
                 "Annotations directory offset not in data section".to_string(),
             ));
         }
+        // field_annotations, method_annotations and parameter_annotations items are
+        // all 8 bytes.
+        let offset = &mut (annotations_directory_item_off as usize + 4);
+        let mut count: uint = 0;
+        for _ in 0..3 {
+            let size: uint = self.source.gread_with(offset, self.get_endian())?;
+            count = count.saturating_add(size);
+        }
+        utils::check_list_bounds(*offset, count, 8, self.source.as_ref().len())?;
         self.source
             .pread_with(annotations_directory_item_off as usize, self)
     }
 
+    /// Returns the distinct annotation types used on the classes, fields, methods
+    /// and method parameters defined in this dex, including annotations nested
+    /// in annotation element values.
+    pub fn annotation_types(&self) -> Result<HashSet<Type>> {
+        let mut types = HashSet::new();
+        for class_def in self.class_defs() {
+            let directory = self.get_annotations_directory_item(class_def?.annotations_off)?;
+            let annotation_sets = core::iter::once(&directory.class_annotations)
+                .chain(directory.field_annotations.iter().map(|f| &f.annotations))
+                .chain(directory.method_annotations.iter().map(|m| &m.annotations))
+                .chain(
+                    directory
+                        .parameter_annotations
+                        .iter()
+                        .flat_map(|p| p.annotations.iter()),
+                );
+            for annotation_item in annotation_sets.flat_map(|set| set.iter()) {
+                annotation_item.annotation().collect_types(&mut types);
+            }
+        }
+        Ok(types)
+    }
+
     /// Returns the `DebugInfoItem` at the offset.
     pub fn get_debug_info_item(&self, debug_info_off: uint) -> Result<DebugInfoItem> {
+        self.debug_info_item_at(debug_info_off, None)
+    }
+
+    /// Returns the `DebugInfoItem` at the offset, the local variables starting with
+    /// the `arguments` of its method in the given first register.
+    pub(crate) fn debug_info_item_at(
+        &self,
+        debug_info_off: uint,
+        arguments: Option<(ushort, Arguments<'_>)>,
+    ) -> Result<DebugInfoItem> {
         if !self.is_offset_in_data_section(debug_info_off) {
             return Err(Error::BadOffset(
                 debug_info_off as usize,
@@ -743,51 +2213,3566 @@ where
             ));
         }
 
-        Ok(self.source.pread_with(debug_info_off as usize, self)?)
+        Ok(self
+            .source
+            .pread_with(debug_info_off as usize, (self, arguments))?)
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+#[cfg(feature = "std")]
+fn csv_field(field: &str) -> alloc::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
     }
 }
 
 /// Reader facade for loading a `Dex`
-pub struct DexReader;
+#[derive(Debug, Clone)]
+pub struct DexReader {
+    /// Whether every string should be kept in memory once decoded.
+    intern: bool,
+    /// Number of decoded strings kept in memory when they are not interned.
+    cache_capacity: usize,
+    /// Whether non-fatal issues should be reported as warnings instead of errors.
+    lenient: bool,
+}
+
+/// Maximum number of entries of the method_ids and field_ids sections of a dex, as
+/// their indices are 16 bits wide in the instructions.
+pub const MAX_REFERENCES: usize = 0x10000;
+
+/// Minimum length of the printable strings returned by `Dex::likely_encrypted_strings`.
+pub const ENCRYPTED_STRING_MIN_LEN: usize = 24;
+/// Fraction of the entropy of a random string from which `Dex::likely_encrypted_strings`
+/// considers a string encrypted.
+pub const ENCRYPTED_STRING_ENTROPY: f64 = 0.95;
+
+/// Methods, as `Lclass;->name`, which load classes or native code at runtime. Used by
+/// `Dex::dynamic_loading_sites`, see `Dex::methods_invoking` for a custom list.
+pub const DYNAMIC_LOADING_APIS: &[&str] = &[
+    "Ldalvik/system/DexClassLoader;-><init>",
+    "Ldalvik/system/PathClassLoader;-><init>",
+    "Ldalvik/system/InMemoryDexClassLoader;-><init>",
+    "Ldalvik/system/DelegateLastClassLoader;-><init>",
+    "Ldalvik/system/DexFile;->loadDex",
+    "Ljava/lang/ClassLoader;->loadClass",
+    "Ljava/lang/Class;->forName",
+    "Ljava/lang/System;->load",
+    "Ljava/lang/System;->loadLibrary",
+    "Ljava/lang/Runtime;->load",
+    "Ljava/lang/Runtime;->loadLibrary",
+];
+
+/// Methods of `java.lang.reflect.Field`, as `Lclass;->name`, which make a field
+/// accessible or access its value. Used by `Dex::reflective_field_access`.
+pub const REFLECTIVE_FIELD_ACCESS_APIS: &[&str] = &[
+    "Ljava/lang/reflect/Field;->setAccessible",
+    "Ljava/lang/reflect/Field;->get",
+    "Ljava/lang/reflect/Field;->getBoolean",
+    "Ljava/lang/reflect/Field;->getByte",
+    "Ljava/lang/reflect/Field;->getChar",
+    "Ljava/lang/reflect/Field;->getShort",
+    "Ljava/lang/reflect/Field;->getInt",
+    "Ljava/lang/reflect/Field;->getLong",
+    "Ljava/lang/reflect/Field;->getFloat",
+    "Ljava/lang/reflect/Field;->getDouble",
+    "Ljava/lang/reflect/Field;->set",
+    "Ljava/lang/reflect/Field;->setBoolean",
+    "Ljava/lang/reflect/Field;->setByte",
+    "Ljava/lang/reflect/Field;->setChar",
+    "Ljava/lang/reflect/Field;->setShort",
+    "Ljava/lang/reflect/Field;->setInt",
+    "Ljava/lang/reflect/Field;->setLong",
+    "Ljava/lang/reflect/Field;->setFloat",
+    "Ljava/lang/reflect/Field;->setDouble",
+];
+
+/// Method of `StringBuilder` invoked in a chain built by `Dex::string_builder_chains`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BuilderCall {
+    Init,
+    Append,
+    ToString,
+}
+
+/// Returns the instructions of the `StringBuilder` chains of `insns`, see
+/// `Dex::string_builder_chains`. `calls` maps the ids of the methods of the builder
+/// type to the call they stand for.
+fn string_builder_chains_in(
+    insns: &[ushort],
+    builder_type: TypeId,
+    calls: &HashMap<MethodId, BuilderCall>,
+) -> Result<Vec<Instruction>> {
+    // (instructions, registers holding constants, pieces appended, all pieces constant)
+    let mut chain: Option<(Vec<Instruction>, HashSet<ushort>, usize, bool)> = None;
+    let mut chains = Vec::new();
+    for instruction in InstructionIterator::new(insns) {
+        let (_, instruction) = instruction?;
+        if let Instruction::NewInstance { type_idx, .. } = instruction {
+            if type_idx == builder_type {
+                chain = Some((vec![instruction], HashSet::new(), 0, true));
+                continue;
+            }
+        }
+        let (chain_insns, constants, pieces, all_constant) = match chain.as_mut() {
+            Some(chain) => chain,
+            None => continue,
+        };
+        match &instruction {
+            Instruction::Const { dst, .. } => {
+                constants.insert(*dst);
+            }
+            Instruction::ConstString { reg, .. } | Instruction::ConstClass { reg, .. } => {
+                constants.insert(*reg);
+            }
+            Instruction::ConstWide { dst, .. } => {
+                constants.insert(*dst);
+                constants.insert(dst.wrapping_add(1));
+            }
+            // The builder returned by append.
+            Instruction::MoveResult { dst, .. } => {
+                constants.remove(dst);
+            }
+            Instruction::Invoke {
+                method_idx, args, ..
+            } if calls.contains_key(method_idx) => {
+                let constant_args = args.iter().skip(1).all(|reg| constants.contains(reg));
+                match calls[method_idx] {
+                    BuilderCall::Init if args.len() == 1 => {}
+                    BuilderCall::Init | BuilderCall::Append => {
+                        *pieces += 1;
+                        *all_constant &= constant_args;
+                    }
+                    BuilderCall::ToString => {
+                        chain_insns.push(instruction);
+                        if *pieces >= 2 && *all_constant {
+                            chains.append(chain_insns);
+                        }
+                        chain = None;
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                chain = None;
+                continue;
+            }
+        }
+        chain_insns.push(instruction);
+    }
+    Ok(chains)
+}
+
+/// Ids of the strings loaded by the `const-string` instructions of `code`, in the
+/// order of their first load.
+fn const_string_ids(code: &CodeItem) -> Result<Vec<StringId>> {
+    let mut seen = HashSet::new();
+    let mut string_ids = Vec::new();
+    for instruction in code.instructions() {
+        if let Instruction::ConstString { string_idx, .. } = instruction?.1 {
+            if seen.insert(string_idx) {
+                string_ids.push(string_idx);
+            }
+        }
+    }
+    Ok(string_ids)
+}
+
+/// Size of the header of a dex file.
+#[cfg(feature = "std")]
+const HEADER_SIZE: usize = 0x70;
+
+/// Default capacity of the string cache when strings are not interned.
+const STRING_CACHE_SIZE: usize = 4096;
 
 impl DexReader {
-    /// Try to read a `Dex` from the given path, returns error if
-    /// the file is not a dex or in case of I/O errors
-    pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Dex<Mmap>> {
+    /// Returns a reader with the default options.
+    pub fn new() -> Self {
+        Self {
+            intern: false,
+            cache_capacity: STRING_CACHE_SIZE,
+            lenient: false,
+        }
+    }
+
+    /// Keep every decoded string in memory instead of evicting the least
+    /// recently used ones, so that all the `DexString`s and `Type`s for the
+    /// same index share a single allocation. The memory used is not bounded: it grows
+    /// with each distinct string accessed, up to every string of the file, and is only
+    /// released when the `Dex` is dropped. Use `cache_capacity` instead to bound it.
+    pub fn intern(mut self) -> Self {
+        self.intern = true;
+        self
+    }
+
+    /// Keep at most `capacity` decoded strings in memory, evicting the least recently
+    /// used ones. The default is 4096 strings. A capacity of 0 decodes the strings
+    /// every time they are accessed without retaining them, which keeps the memory
+    /// used flat when going through the classes once. Ignored if strings are interned.
+    /// See `Dex::string_cache_stats` to tune it.
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+
+    /// Recover from non-fatal issues instead of failing, and report them in
+    /// `Dex::warnings`. When the file is read, the `file_size` of the header and the
+    /// order of the strings and type ids are checked, then every class is parsed to
+    /// collect the warnings of its recoveries, which are:
+    ///
+    /// - unknown bits in the access flags of a class, a field or a method, which are
+    ///   ignored;
+    /// - debug info which can't be read, e.g. because it is truncated, which is
+    ///   dropped from the code of the method;
+    /// - hidden API flags of a class which can't be read, which are dropped from its
+    ///   members.
+    ///
+    /// Any other malformation is still an error.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Try to read a `Dex` from the given path with the options of this reader,
+    /// returns error if the file is not a dex or in case of I/O errors. The file is
+    /// memory mapped instead of being read, so only the pages of the sections which
+    /// are accessed are loaded in memory.
+    #[cfg(feature = "std")]
+    pub fn open<P: AsRef<Path>>(&self, file: P) -> Result<Dex<Mmap>> {
         let map = unsafe { MmapOptions::new().map(&File::open(file.as_ref())?)? };
-        let inner: DexInner = map.pread(0)?;
+        self.read(map)
+    }
+
+    /// Try to read a `Dex` from the given source with the options of this reader,
+    /// returns error if the source is not a dex
+    pub fn read<T: AsRef<[u8]>>(&self, source: T) -> Result<Dex<T>> {
+        let inner: DexInner = source.as_ref().pread(0)?;
         let endian = inner.endian();
-        let source = Source::new(map);
+        let source = Source::new(source);
+        let cache_size = if self.intern {
+            (inner.strings_len() as usize).max(1)
+        } else {
+            self.cache_capacity
+        };
         let cache = Strings::new(
             source.clone(),
             endian,
             inner.strings_offset(),
             inner.strings_len(),
-            4096,
+            cache_size,
             inner.data_section(),
         );
-        Ok(Dex {
+        let mut dex = Dex {
             source: source.clone(),
             strings: cache,
             inner,
-        })
+            lenient: self.lenient,
+            warnings: Vec::new(),
+            string_xrefs: OnceCell::new(),
+        };
+        if self.lenient {
+            dex.warnings = dex.collect_warnings()?;
+        }
+        Ok(dex)
+    }
+
+    /// Try to read a `Dex` from a reader which can't seek, e.g. a network stream, with
+    /// the options of this reader. The stream is read to the end into memory, then the
+    /// `file_size` of the header is checked against the number of bytes read: a shorter
+    /// stream is reported as truncated, and the bytes past `file_size` are dropped.
+    /// Prefer `open` for files, which maps them so that only the pages of the sections
+    /// which are accessed are loaded in memory.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: Read>(&self, mut reader: R) -> Result<Dex<Vec<ubyte>>> {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+        if contents.len() < HEADER_SIZE {
+            return Err(Error::MalFormed(format!(
+                "Truncated dex file: {} bytes read, shorter than the header",
+                contents.len()
+            )));
+        }
+        check_magic(&contents)?;
+        let file_size = contents
+            .pread_with::<Header>(0, header_endian(&contents)?)?
+            .file_size() as usize;
+        if file_size > contents.len() {
+            return Err(Error::MalFormed(format!(
+                "Truncated dex file: file size in header: {}, bytes read: {}",
+                file_size,
+                contents.len()
+            )));
+        }
+        contents.truncate(file_size);
+        self.read(contents)
+    }
+
+    /// Try to read a `Dex` from the given path, returns error if
+    /// the file is not a dex or in case of I/O errors
+    #[cfg(feature = "std")]
+    pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Dex<Mmap>> {
+        Self::new().open(file)
+    }
+
+    /// Try to read a `Dex` borrowing the given bytes, e.g. a file already mapped by
+    /// the caller, without copying them. Returns error if the bytes are not a dex.
+    pub fn from_bytes(bytes: &[ubyte]) -> Result<Dex<&[ubyte]>> {
+        Self::new().read(bytes)
     }
 }
 
-#[cfg(test)]
-mod tests {
+impl Default for DexReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    #[test]
-    fn test_find_class_by_name() {
-        let dex =
-            super::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
-        for class_def in dex.class_defs() {
-            let class_def = class_def.expect("can't load class");
-            let jtype = dex.get_type(class_def.class_idx()).expect("bad type");
-            let result = dex.find_class_by_name(&jtype.type_descriptor().to_string());
-            assert!(result.is_ok());
-            assert!(result.unwrap().is_some());
-        }
+#[cfg(feature = "apk")]
+impl Dex<Vec<ubyte>> {
+    /// Reads the `classes.dex`, `classes2.dex`, ... entries of the APK at the given
+    /// path into memory, see `MultiDex::from_apk`. Returns `Error::MissingDex` if the
+    /// APK has no dex file.
+    pub fn from_apk<P: AsRef<Path>>(path: P) -> Result<MultiDex<Vec<ubyte>>> {
+        MultiDex::from_apk(path)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Dex<Mmap> {
+    /// Reads the dex file at the given path by mapping it in memory, see
+    /// `DexReader::open`. Only the pages of the sections which are accessed, e.g. the
+    /// classes iterated over, are loaded, which keeps the memory used by large files low.
+    pub fn from_file_mmap<P: AsRef<Path>>(path: P) -> Result<Dex<Mmap>> {
+        DexReader::from_file(path)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Dex<Vec<ubyte>> {
+    /// Reads an index written by `Dex::export_index`, see `DexIndex::import`.
+    pub fn import_index<R: Read>(reader: R) -> Result<DexIndex> {
+        DexIndex::import(reader)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use std::{
+        collections::{BTreeMap, HashSet},
+        io::Write,
+    };
+
+    use memmap2::Mmap;
+    use tempfile::NamedTempFile;
+
+    use super::{Dex, DexReader, ParseWarning};
+    use crate::{
+        class::{AccessFlags, ClassId},
+        code::Opcode,
+        field::FieldId,
+        instruction::{Instruction, InstructionIterator},
+        jtype::{Type, TypeId},
+        method::MethodId,
+        uint, Result,
+    };
+
+    /// Loads the test dex after applying `patch` to its contents. The checksum
+    /// is recomputed so that the patched file passes the header validation.
+    pub(crate) fn patched_dex<F: FnOnce(&mut Vec<u8>)>(patch: F) -> (NamedTempFile, Dex<Mmap>) {
+        let mut contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        patch(&mut contents);
+        let checksum = adler32::RollingAdler32::from_buffer(&contents[12..]).hash();
+        contents[8..12].copy_from_slice(&checksum.to_le_bytes());
+        let mut file = NamedTempFile::new().expect("cannot create temporary file");
+        file.write_all(&contents).expect("cannot write patched dex");
+        let dex = DexReader::from_file(file.path()).expect("cannot open patched dex");
+        (file, dex)
+    }
+
+    /// Same as `patched_dex` for patches which append items to the test dex. Its data
+    /// section runs to the end of the file, so the file and data sizes of the header
+    /// are updated to cover them.
+    pub(crate) fn appended_dex<F: FnOnce(&mut Vec<u8>)>(patch: F) -> (NamedTempFile, Dex<Mmap>) {
+        patched_dex(|contents| {
+            patch(contents);
+            let len = contents.len() as u32;
+            let data_off = u32::from_le_bytes([
+                contents[0x6c],
+                contents[0x6d],
+                contents[0x6e],
+                contents[0x6f],
+            ]);
+            contents[0x20..0x24].copy_from_slice(&len.to_le_bytes());
+            contents[0x68..0x6c].copy_from_slice(&(len - data_off).to_le_bytes());
+        })
+    }
+
+    /// Loads the test dex with the final `return-void` of a `<clinit>` replaced by the
+    /// first code unit of an `invoke-virtual`, which is then truncated.
+    pub(crate) fn truncated_code_dex() -> (NamedTempFile, Dex<Mmap>) {
+        patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let offset = dex
+                .classes()
+                .map(|class| class.expect("error parsing class"))
+                .find_map(|class| {
+                    class.methods().find_map(|method| {
+                        let insns = method.code()?.insns();
+                        if method.name() == "<clinit>" && insns.last() == Some(&0x0e) {
+                            Some(method.code_offset() as usize + 16 + (insns.len() - 1) * 2)
+                        } else {
+                            None
+                        }
+                    })
+                })
+                .expect("no static initializer ending with return-void");
+            contents[offset] = 0x6e;
+        })
+    }
+
+    /// Appends `item` to `contents` at the next 4-byte aligned offset, which is
+    /// returned.
+    pub(crate) fn append_item(contents: &mut Vec<u8>, item: &[u8]) -> u32 {
+        contents.resize((contents.len() + 3) & !3, 0);
+        let offset = contents.len() as u32;
+        contents.extend_from_slice(item);
+        offset
+    }
+
+    /// Appends the ULEB128 encoding of `value` to `out`.
+    pub(crate) fn push_uleb(out: &mut Vec<u8>, mut value: u32) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// Encodes an `encoded_annotation` of the type `type_id`, with elements given as
+    /// pairs of the string id of their name and their encoded value.
+    pub(crate) fn encoded_annotation(type_id: u32, elements: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut annotation = Vec::new();
+        push_uleb(&mut annotation, type_id);
+        push_uleb(&mut annotation, elements.len() as u32);
+        for (name, value) in elements {
+            push_uleb(&mut annotation, *name);
+            annotation.extend_from_slice(value);
+        }
+        annotation
+    }
+
+    /// Encodes an `annotation_set_item`, or an `annotation_set_ref_list`, of the items
+    /// at `offsets`.
+    pub(crate) fn offset_list(offsets: &[u32]) -> Vec<u8> {
+        let mut list = (offsets.len() as u32).to_le_bytes().to_vec();
+        for offset in offsets {
+            list.extend_from_slice(&offset.to_le_bytes());
+        }
+        list
+    }
+
+    /// Encodes an `annotations_directory_item` with the given class annotations, field
+    /// annotations, as pairs of a field id and the offset of its `annotation_set_item`,
+    /// and parameter annotations, as pairs of a method id and the offset of its
+    /// `annotation_set_ref_list`.
+    pub(crate) fn annotations_directory(
+        class_annotations: u32,
+        fields: &[(u32, u32)],
+        parameters: &[(u32, u32)],
+    ) -> Vec<u8> {
+        let mut directory = class_annotations.to_le_bytes().to_vec();
+        directory.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(&(parameters.len() as u32).to_le_bytes());
+        for (id, offset) in fields.iter().chain(parameters) {
+            directory.extend_from_slice(&id.to_le_bytes());
+            directory.extend_from_slice(&offset.to_le_bytes());
+        }
+        directory
+    }
+
+    /// Points the `annotations_off` of the class def of `class_idx` to `offset`.
+    pub(crate) fn set_annotations_off(
+        contents: &mut [u8],
+        dex: &Dex<Mmap>,
+        class_idx: ClassId,
+        offset: u32,
+    ) {
+        let index = dex
+            .class_defs()
+            .position(|def| def.expect("bad class def").class_idx == class_idx)
+            .expect("class def not found");
+        let class_def_offset = dex.inner.class_defs_offset() as usize + index * 32;
+        contents[class_def_offset + 20..class_def_offset + 24]
+            .copy_from_slice(&offset.to_le_bytes());
+    }
+
+    #[test]
+    fn test_invalid_type_descriptor() {
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let string_id = dex
+                .strings
+                .get_id("<init>")
+                .expect("error finding string")
+                .expect("string not found");
+            let type_ids_off = dex.inner.type_ids_offset() as usize;
+            contents[type_ids_off..type_ids_off + 4].copy_from_slice(&string_id.to_le_bytes());
+        });
+        match dex.get_type(0) {
+            Err(super::Error::InvalidDescriptor(_)) => {}
+            other => panic!("expected invalid descriptor error, got: {:?}", other),
+        }
+        assert!(dex.get_type(1).is_ok());
+    }
+
+    #[test]
+    fn test_find_class_by_name() {
+        let dex =
+            super::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for class_def in dex.class_defs() {
+            let class_def = class_def.expect("can't load class");
+            let jtype = dex.get_type(class_def.class_idx()).expect("bad type");
+            let result = dex.find_class_by_name(&jtype.type_descriptor().to_string());
+            assert!(result.is_ok());
+            assert!(result.unwrap().is_some());
+        }
+
+        let name = "Lcom/devoteam/quickaction/QuickActionItem;";
+        for name in &[name, "com.devoteam.quickaction.QuickActionItem"] {
+            let class = dex
+                .find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found");
+            assert_eq!(
+                class.jtype().type_descriptor(),
+                "Lcom/devoteam/quickaction/QuickActionItem;"
+            );
+        }
+        let class = dex
+            .find_class_by_name("mobi.intuitit.android.widget.BoundRemoteViews$BindingAction")
+            .expect("error finding class");
+        assert!(class.is_some());
+        for name in &[
+            "Lcom/example/Missing;",
+            "com.example.Missing",
+            "I",
+            "Lcom/devoteam/quickaction/QuickActionItem",
+        ] {
+            assert!(dex
+                .find_class_by_name(name)
+                .expect("error finding class")
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_classes_filtered() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let public_classes = dex
+            .classes_filtered(|class_def| {
+                class_def.access_flags() & AccessFlags::PUBLIC.bits() != 0
+            })
+            .collect::<Result<Vec<_>>>()
+            .expect("error parsing classes");
+        assert!(!public_classes.is_empty());
+        assert!(public_classes.iter().all(|class| class.is_public()));
+        let expected = dex
+            .classes()
+            .filter(|class| class.as_ref().map(|c| c.is_public()).unwrap_or(true))
+            .count();
+        assert_eq!(public_classes.len(), expected);
+    }
+
+    #[test]
+    fn test_find_class() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let interface = dex
+            .find_class(|class_def| class_def.access_flags() & AccessFlags::INTERFACE.bits() != 0)
+            .expect("error finding class")
+            .expect("no interface found");
+        assert!(interface.is_interface());
+        let first_interface = dex
+            .classes()
+            .map(|class| class.expect("error parsing class"))
+            .find(|class| class.is_interface())
+            .expect("no interface found");
+        assert_eq!(interface.id(), first_interface.id());
+        assert!(dex
+            .find_class(|_| false)
+            .expect("error finding class")
+            .is_none());
+    }
+
+    #[test]
+    fn test_slice_at() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(dex.slice_at(0, 4).expect("cannot read magic"), b"dex\n");
+        let file_size = dex.header().file_size();
+        assert!(dex.slice_at(file_size, 0).is_ok());
+        assert!(matches!(
+            dex.slice_at(file_size - 1, 2).err(),
+            Some(crate::Error::OutOfBounds { offset, file_size: size })
+                if offset == u64::from(file_size) + 1 && size == file_size as usize
+        ));
+        assert!(dex.slice_at(uint::MAX, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_parameter_names() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "setText")
+            .expect("method not found");
+        let names = method.parameter_names();
+        assert_eq!(names.len(), method.params().len());
+        assert_eq!(
+            names[0].as_ref().map(|n| n.to_string()),
+            Some("text".to_string())
+        );
+    }
+
+    #[test]
+    fn test_methods_in_dex_order() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            let ids = class
+                .methods_in_dex_order()
+                .map(|m| m.id())
+                .collect::<Vec<_>>();
+            assert!(class.methods().map(|m| m.id()).eq(ids.iter().copied()));
+            let (direct, virtual_) = ids.split_at(class.direct_methods().len());
+            assert!(direct.windows(2).all(|w| w[0] < w[1]));
+            assert!(virtual_.windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    fn test_redact_strings() {
+        use sha1::{Digest, Sha1};
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let is_url = |s: &str| s.starts_with("http://");
+        let contents = dex
+            .redact_strings(|s| is_url(s))
+            .expect("error redacting strings");
+        assert_eq!(contents.len(), dex.source.as_ref().len());
+        assert_eq!(&contents[12..32], Sha1::digest(&contents[32..]).as_slice());
+
+        let mut file = NamedTempFile::new().expect("cannot create temporary file");
+        file.write_all(&contents)
+            .expect("cannot write redacted dex");
+        let redacted = DexReader::from_file(file.path()).expect("cannot open redacted dex");
+        let mut count = 0;
+        for (original, string) in dex.strings().zip(redacted.strings()) {
+            let (original, string) = (original.expect("bad string"), string.expect("bad string"));
+            if is_url(&original) {
+                count += 1;
+                assert_eq!(string.len(), original.len());
+                assert!(string.chars().all(|c| c == '*'));
+            } else {
+                assert_eq!(original, string);
+            }
+        }
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_from_file_mmap() {
+        fn read<T: AsRef<[u8]>>(source: T) -> Result<Dex<T>> {
+            DexReader::new().read(source)
+        }
+        let dex = Dex::from_file_mmap("resources/classes.dex").expect("cannot map dex file");
+        let contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        assert_eq!(dex.source.as_ref(), &contents[..]);
+        // the mapped file can be read as any other source.
+        let map = unsafe {
+            Mmap::map(&std::fs::File::open("resources/classes.dex").expect("cannot open file"))
+                .expect("cannot map file")
+        };
+        assert_eq!(
+            read(map).expect("cannot read dex").header().file_size(),
+            contents.len() as uint
+        );
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        assert_eq!(
+            class.jtype().type_descriptor(),
+            "Lcom/devoteam/quickaction/QuickActionItem;"
+        );
+        assert_eq!(
+            dex.classes().count(),
+            dex.header().class_defs_size() as usize
+        );
+    }
+
+    #[test]
+    fn test_methods_with_finally() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let methods = dex
+            .methods_with_finally()
+            .expect("error finding methods with finally");
+        assert!(!methods.is_empty());
+        let expected: usize = dex
+            .classes()
+            .map(|class| {
+                let class = class.expect("error parsing class");
+                class.methods().filter(|m| m.has_finally()).count()
+            })
+            .sum();
+        assert_eq!(methods.len(), expected);
+        assert!(methods.iter().all(|m| m.code().is_some()));
+    }
+
+    #[test]
+    fn test_opcode_set() {
+        use crate::disass::opcodes::{INVOKEVIRTUAL, RETURNVOID};
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "setText")
+            .expect("method not found");
+        let opcodes = method.opcode_set(&dex).expect("error decoding code");
+        assert!(opcodes.contains(&(INVOKEVIRTUAL as Opcode)));
+        assert!(opcodes.contains(&(RETURNVOID as Opcode)));
+        let instructions = method.code().expect("no code").instructions().count();
+        assert!(opcodes.len() <= instructions);
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            for method in class.methods().filter(|m| m.code().is_none()) {
+                assert!(method
+                    .opcode_set(&dex)
+                    .expect("error decoding code")
+                    .is_empty());
+            }
+        }
+
+        let (_file, dex) = truncated_code_dex();
+        let truncated = dex
+            .classes()
+            .map(|class| class.expect("error parsing class"))
+            .any(|class| {
+                class
+                    .methods()
+                    .any(|method| method.opcode_set(&dex).is_err())
+            });
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_cache_capacity() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let first = dex.get_string(100).expect("bad string");
+        let second = dex.get_string(100).expect("bad string");
+        assert!(std::ptr::eq(first.as_ptr(), second.as_ptr()));
+        let stats = dex.string_cache_stats();
+        assert_eq!((stats.hits(), stats.misses()), (1, 1));
+
+        let dex = DexReader::new()
+            .cache_capacity(0)
+            .open("resources/classes.dex")
+            .expect("cannot open dex file");
+        let first = dex.get_string(100).expect("bad string");
+        let second = dex.get_string(100).expect("bad string");
+        assert_eq!(first, second);
+        assert_eq!(dex.string_cache_stats().misses(), 2);
+        let _ = dex.classes().count();
+        assert_eq!(dex.string_cache_stats().hits(), 0);
+
+        // the least recently used string is evicted.
+        let dex = DexReader::new()
+            .cache_capacity(2)
+            .open("resources/classes.dex")
+            .expect("cannot open dex file");
+        for id in &[0, 1, 0, 2, 0, 1] {
+            dex.get_string(*id).expect("bad string");
+        }
+        let stats = dex.string_cache_stats();
+        assert_eq!((stats.hits(), stats.misses()), (2, 4));
+    }
+
+    #[test]
+    fn test_intern() {
+        let dex = DexReader::new()
+            .intern()
+            .open("resources/classes.dex")
+            .expect("cannot open dex file");
+        let first = dex.types().collect::<Result<Vec<_>>>().expect("bad type");
+        let _ = dex
+            .strings()
+            .collect::<Result<Vec<_>>>()
+            .expect("bad string");
+        let second = dex.types().collect::<Result<Vec<_>>>().expect("bad type");
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert!(std::ptr::eq(
+                a.type_descriptor().as_ptr(),
+                b.type_descriptor().as_ptr()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_abstract_methods_have_no_code() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut abstract_methods = 0;
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            for method in class.methods().filter(|m| m.is_abstract() || m.is_native()) {
+                abstract_methods += 1;
+                assert!(method.code().is_none());
+            }
+            for method in class.methods().filter(|m| m.code().is_none()) {
+                assert!(method.is_abstract() || method.is_native());
+            }
+        }
+        assert!(abstract_methods > 0);
+    }
+
+    #[test]
+    fn test_class_names() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let names = dex
+            .class_names()
+            .collect::<Result<Vec<_>>>()
+            .expect("error reading class names");
+        assert_eq!(names.len(), dex.header().class_defs_size() as usize);
+        for (name, class) in names.iter().zip(dex.classes()) {
+            assert_eq!(class.expect("error parsing class").jtype(), name);
+        }
+    }
+
+    #[test]
+    fn test_likely_encrypted_strings() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert!(dex
+            .likely_encrypted_strings()
+            .expect("error reading strings")
+            .is_empty());
+
+        let encrypted = [
+            "q8Zt3KfP1xLw9sVbN2mRjYhE0cUoAa7=",
+            "3f2a9c81b7e4d60512ffa9c3b8e7d1a04c6b59e2",
+        ];
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let strings = [
+                "Lorg/adw/launcher/Launcher;",
+                "Landroid/content/BroadcastReceiver;",
+            ];
+            for (string, replacement) in strings.iter().zip(encrypted.iter()) {
+                let string_id = dex
+                    .strings
+                    .get_id(string)
+                    .expect("error finding string")
+                    .expect("string not found");
+                let offset = dex.string_data_offset(string_id).expect("bad string") as usize;
+                // the utf16 size is a single byte for these strings.
+                let data = &mut contents[offset + 1..offset + 1 + string.len()];
+                data.copy_from_slice(&replacement.as_bytes()[..string.len()]);
+            }
+        });
+        let mut strings = dex
+            .likely_encrypted_strings()
+            .expect("error reading strings")
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        strings.sort();
+        assert_eq!(strings, vec![&encrypted[1][..35], &encrypted[0][..27]]);
+    }
+
+    #[test]
+    fn test_permission_strings() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert!(dex
+            .permission_strings()
+            .expect("error reading strings")
+            .is_empty());
+
+        let permission = "android.permission.READ_PHONE_STATE";
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let string = "Landroid/content/BroadcastReceiver;";
+            let string_id = dex
+                .strings
+                .get_id(string)
+                .expect("error finding string")
+                .expect("string not found");
+            let offset = dex.string_data_offset(string_id).expect("bad string") as usize;
+            contents[offset + 1..offset + 1 + string.len()].copy_from_slice(permission.as_bytes());
+        });
+        let strings = dex.permission_strings().expect("error reading strings");
+        assert_eq!(
+            strings.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            vec![permission]
+        );
+    }
+
+    #[test]
+    fn test_constant_value() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let (mut constants, mut non_constants) = (0, 0);
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            for field in class.fields() {
+                match field.constant_value() {
+                    Some(value) => {
+                        constants += 1;
+                        assert!(field.is_static() && field.is_final());
+                        assert_eq!(Some(value), field.initial_value());
+                    }
+                    None if field.initial_value().is_some() => {
+                        non_constants += 1;
+                        assert!(!field.is_final());
+                    }
+                    None => {}
+                }
+            }
+        }
+        assert!(constants > 0);
+        assert!(non_constants > 0);
+    }
+
+    #[test]
+    fn test_annotation_constant_value() {
+        use crate::encoded_value::EncodedValue;
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let (class, field_id) = dex
+            .classes()
+            .map(|class| class.expect("error parsing class"))
+            .find_map(|class| {
+                let field = class.static_fields().iter().find(|field| {
+                    field.is_final()
+                        && field.initial_value().is_none()
+                        && field.annotations().is_empty()
+                })?;
+                Some((class.id(), field.id()))
+            })
+            .expect("no static final field without a value");
+        let (_file, patched) = appended_dex(|contents| {
+            // rename a type of the same length to the annotation.
+            let string_id = |string| {
+                dex.strings
+                    .get_id(string)
+                    .expect("error finding string")
+                    .expect("string not found")
+            };
+            let renamed = "Landroid/graphics/BlurMaskFilter;";
+            let descriptor = b"Ldalvik/annotation/ConstantValue;";
+            let offset = dex
+                .string_data_offset(string_id(renamed))
+                .expect("error reading string offset") as usize
+                + 1;
+            contents[offset..offset + descriptor.len()].copy_from_slice(descriptor);
+            let jtype = dex
+                .get_type_from_descriptor(renamed)
+                .expect("error finding type")
+                .expect("type not found");
+            let value = string_id("value");
+            // value = 42, as an int of one byte.
+            let mut item = vec![0x00];
+            item.extend(encoded_annotation(jtype.id(), &[(value, &[0x04, 42])]));
+            let item_offset = append_item(contents, &item);
+            let set_offset = append_item(contents, &offset_list(&[item_offset]));
+            let directory = annotations_directory(0, &[(field_id as u32, set_offset)], &[]);
+            let directory_offset = append_item(contents, &directory);
+            set_annotations_off(contents, &dex, class, directory_offset);
+        });
+        let class = patched
+            .class_from_id(class)
+            .expect("error parsing class")
+            .expect("class not found");
+        let field = class
+            .fields()
+            .find(|field| field.id() == field_id)
+            .expect("field not found");
+        assert_eq!(field.initial_value(), None);
+        assert_eq!(field.constant_value(), Some(&EncodedValue::Int(42)));
+    }
+
+    #[test]
+    fn test_duplicate_class_defs() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert!(dex
+            .duplicate_class_defs()
+            .expect("error finding duplicates")
+            .is_empty());
+
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let offset = dex.inner.class_defs_offset() as usize;
+            contents.copy_within(offset..offset + 32, offset + 2 * 32);
+            // Make the duplicate distinguishable by its access flags.
+            contents[offset + 2 * 32 + 4] ^= AccessFlags::FINAL.bits() as u8;
+        });
+        let first = dex
+            .class_defs()
+            .next()
+            .expect("no class defs")
+            .expect("bad class def");
+        assert_eq!(
+            dex.duplicate_class_defs()
+                .expect("error finding duplicates"),
+            vec![first.class_idx]
+        );
+        let name = dex
+            .get_type(first.class_idx)
+            .expect("bad type")
+            .type_descriptor()
+            .to_string();
+        let class = dex
+            .find_class_by_name(&name)
+            .expect("error finding class")
+            .expect("class not found");
+        assert_eq!(class.id(), first.class_idx);
+        assert_eq!(class.access_flags().bits(), first.access_flags);
+    }
+
+    #[test]
+    fn test_super_class() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;")
+            .expect("error finding class")
+            .expect("class not found");
+        let super_class = class.super_class().expect("class has no super class");
+        assert_eq!(
+            dex.get_type(super_class).expect("bad type"),
+            "Lmobi/intuitit/android/widget/SimpleRemoteViews$ReflectionAction;"
+        );
+
+        // Only `java.lang.Object` has no super class.
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let offset = dex.inner.class_defs_offset() as usize + 8;
+            contents[offset..offset + 4].copy_from_slice(&crate::NO_INDEX.to_le_bytes());
+        });
+        let root = dex
+            .classes()
+            .next()
+            .expect("no classes")
+            .expect("error parsing class");
+        assert_eq!(root.super_class(), None);
+        let subclass = dex
+            .classes()
+            .nth(1)
+            .expect("no classes")
+            .expect("error parsing class");
+        assert!(subclass.super_class().is_some());
+    }
+
+    #[test]
+    fn test_top_level_class() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let find = |name| {
+            dex.find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found")
+        };
+        let launcher = find("Lorg/adw/launcher/Launcher;");
+        assert!(launcher
+            .enclosing_class(&dex)
+            .expect("error finding enclosing class")
+            .is_none());
+        assert_eq!(
+            launcher.top_level_class(&dex).expect("error finding class"),
+            "Lorg/adw/launcher/Launcher;"
+        );
+        // Anonymous class inside an anonymous class.
+        let anonymous = find("Lorg/adw/launcher/Search$1$1;");
+        assert_eq!(
+            anonymous
+                .enclosing_class(&dex)
+                .expect("error finding enclosing class")
+                .expect("no enclosing class"),
+            "Lorg/adw/launcher/Search$1;"
+        );
+        assert_eq!(
+            anonymous
+                .top_level_class(&dex)
+                .expect("error finding class"),
+            "Lorg/adw/launcher/Search;"
+        );
+    }
+
+    #[test]
+    fn test_is_override() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;")
+            .expect("error finding class")
+            .expect("class not found");
+        let is_override = |name| {
+            class
+                .methods()
+                .find(|m| m.name() == name)
+                .expect("method not found")
+                .is_override(&dex)
+                .expect("error checking override")
+        };
+        assert!(is_override("apply"));
+        assert!(is_override("getTag"));
+        assert!(!is_override("getDefault"));
+        assert!(!is_override("<init>"));
+        // The super class of the launcher is not defined in the dex.
+        let launcher = dex
+            .find_class_by_name("Lorg/adw/launcher/Launcher;")
+            .expect("error finding class")
+            .expect("class not found");
+        let on_create = launcher
+            .methods()
+            .find(|m| m.name() == "onCreate")
+            .expect("method not found");
+        assert!(!on_create
+            .is_override(&dex)
+            .expect("error checking override"));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_extract_network_indicators() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let indicators = dex
+            .extract_network_indicators()
+            .expect("error extracting indicators");
+        assert!(indicators
+            .urls()
+            .contains("http://schemas.android.com/apk/res/android"));
+        assert!(indicators.domains().contains("code.google.com"));
+        assert!(indicators.domains().contains("xmlpull.org"));
+        assert!(!indicators
+            .domains()
+            .iter()
+            .any(|domain| domain.starts_with("org.adw")));
+    }
+
+    #[test]
+    fn test_return_shorty() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        for method in class.methods() {
+            assert_eq!(method.returns_void(), method.return_type() == "V");
+            let shorty = method.return_shorty();
+            if method.return_type().is_reference() {
+                assert_eq!(shorty, 'L');
+            } else {
+                assert_eq!(shorty.to_string(), method.return_type().to_string());
+            }
+        }
+        let set_text = class
+            .methods()
+            .find(|m| m.name() == "setText")
+            .expect("method not found");
+        assert!(set_text.returns_void());
+        for proto in dex.proto_ids() {
+            let proto = proto.expect("bad proto id");
+            let return_type = dex.get_type(proto.return_type()).expect("bad type");
+            let shorty = proto.return_shorty(&dex).expect("bad shorty");
+            assert_eq!(shorty == 'V', return_type == "V");
+        }
+    }
+
+    #[test]
+    fn test_find_method_by_signature() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let method = dex
+            .find_method_by_signature(
+                "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V",
+            )
+            .expect("error finding method")
+            .expect("method not found");
+        assert_eq!(method.name(), "setText");
+        assert_eq!(method.class(), "Lcom/devoteam/quickaction/QuickActionItem;");
+        for signature in &[
+            "Lcom/devoteam/quickaction/QuickActionItem;->setText(I)V",
+            "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)I",
+            "Lcom/devoteam/quickaction/QuickActionItem;->missing()V",
+            "Lcom/example/Missing;->setText(Ljava/lang/String;)V",
+        ] {
+            assert!(dex
+                .find_method_by_signature(signature)
+                .expect("error finding method")
+                .is_none());
+        }
+        for signature in &[
+            "setText(Ljava/lang/String;)V",
+            "Lcom/devoteam/quickaction/QuickActionItem;->(I)V",
+            "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String)V",
+            "Lcom/devoteam/quickaction/QuickActionItem;->setText()",
+        ] {
+            match dex.find_method_by_signature(signature) {
+                Err(super::Error::InvalidDescriptor(_)) => {}
+                other => panic!("expected invalid descriptor error, got: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_methods_with_code() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let methods = dex
+            .methods_with_code()
+            .collect::<Result<Vec<_>>>()
+            .expect("error parsing methods");
+        assert!(methods
+            .iter()
+            .all(|m| m.code().is_some() && !m.is_abstract() && !m.is_native()));
+        for method in &methods {
+            let expected = dex
+                .get_code_item(method.code_offset())
+                .expect("error parsing code item")
+                .expect("no code item");
+            assert_eq!(
+                expected.insns(),
+                method.code().expect("missing code item").insns()
+            );
+        }
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            assert!(class
+                .methods()
+                .filter(|m| m.code().is_none())
+                .all(|m| m.code_offset() == 0));
+        }
+        let expected: Vec<_> = dex
+            .classes()
+            .flat_map(|class| {
+                let class = class.expect("error parsing class");
+                class
+                    .methods()
+                    .filter(|m| m.code().is_some())
+                    .map(|m| m.id())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert!(methods.iter().map(|m| m.id()).eq(expected));
+    }
+
+    #[test]
+    fn test_get_type_list() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert!(dex
+            .get_type_list(0)
+            .expect("error reading type list")
+            .is_empty());
+        let proto = dex
+            .proto_ids()
+            .map(|proto| proto.expect("bad proto id"))
+            .find(|proto| proto.params_off() != 0)
+            .expect("no proto with parameters");
+        assert!(!dex
+            .get_type_list(proto.params_off())
+            .expect("error reading type list")
+            .is_empty());
+        match dex.get_type_list(proto.params_off() + 2) {
+            Err(super::Error::MalFormed(_)) => {}
+            other => panic!("expected malformed error, got: {:?}", other),
+        }
+
+        // Prototypes without parameters have a params_off of 0.
+        let method = dex
+            .methods_with_code()
+            .map(|method| method.expect("error parsing method"))
+            .find(|method| method.shorty().len() == 1)
+            .expect("method not found");
+        assert!(method.params().is_empty());
+    }
+
+    #[test]
+    fn test_interface_ids() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut found = false;
+        for class_def in dex.class_defs() {
+            let class_def = class_def.expect("bad class def");
+            let type_ids = dex
+                .get_type_id_list(class_def.interfaces_off())
+                .expect("error reading interfaces");
+            let class = dex
+                .class_from_id(class_def.class_idx())
+                .expect("error parsing class")
+                .expect("class not found");
+            assert_eq!(
+                class.interface_ids(&dex).expect("error reading interfaces"),
+                &type_ids[..]
+            );
+            found |= !type_ids.is_empty();
+        }
+        assert!(found);
+
+        // the class is not defined under this id, as for a class of another dex.
+        let mut class = dex
+            .classes()
+            .next()
+            .expect("no classes")
+            .expect("error parsing class");
+        class.id += 1;
+        assert!(matches!(
+            class.interface_ids(&dex),
+            Err(crate::Error::InvalidId(_))
+        ));
+    }
+
+    #[test]
+    fn test_member_count_distribution() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let distribution = dex
+            .member_count_distribution()
+            .expect("error computing distribution");
+        let mut methods = BTreeMap::new();
+        let mut fields = BTreeMap::new();
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            *methods.entry(class.methods().count()).or_insert(0) += 1;
+            *fields.entry(class.fields().count()).or_insert(0) += 1;
+        }
+        assert_eq!(distribution.methods_per_class(), &methods);
+        assert_eq!(distribution.fields_per_class(), &fields);
+        assert_eq!(
+            distribution.max_methods(),
+            *methods.keys().last().expect("no classes")
+        );
+        assert_eq!(
+            distribution.methods_per_class().values().sum::<usize>(),
+            dex.header().class_defs_size() as usize
+        );
+    }
+
+    #[test]
+    fn test_export_methods_csv() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut csv = Vec::new();
+        dex.export_methods_csv(&mut csv)
+            .expect("error exporting methods");
+        let csv = String::from_utf8(csv).expect("invalid utf-8");
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("class,name,signature,access_flags,instructions")
+        );
+        let method = dex
+            .find_method_by_signature(
+                "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V",
+            )
+            .expect("error finding method")
+            .expect("method not found");
+        let row = format!(
+            "Lcom/devoteam/quickaction/QuickActionItem;,setText,(Ljava/lang/String;)V,{:#x},{}",
+            method.access_flags().bits(),
+            method.code().expect("no code").instructions().count()
+        );
+        assert!(lines.any(|line| line == row));
+        let methods: usize = dex
+            .classes()
+            .map(|class| class.expect("error parsing class").methods().count())
+            .sum();
+        assert_eq!(csv.lines().count(), methods + 1);
+
+        assert_eq!(super::csv_field("<init>"), "<init>");
+        assert_eq!(super::csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+
+        let (_file, dex) = truncated_code_dex();
+        assert!(dex.export_methods_csv(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_dynamic_loading_sites() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        // The launcher doesn't load code dynamically.
+        assert!(dex
+            .dynamic_loading_sites()
+            .expect("error finding dynamic loading sites")
+            .is_empty());
+        let sites = dex
+            .methods_invoking(&["Ljava/lang/Class;->getMethod", "Ljava/lang/System;->gc"])
+            .expect("error finding methods");
+        assert!(!sites.is_empty());
+        let graph = dex.call_graph().expect("cannot build call graph");
+        let expected = graph
+            .calls()
+            .iter()
+            .filter(|(_, callees)| {
+                callees.iter().any(|callee| {
+                    graph.names()[callee] == "Ljava/lang/Class;->getMethod"
+                        || graph.names()[callee] == "Ljava/lang/System;->gc"
+                })
+            })
+            .map(|(caller, _)| *caller)
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            sites.iter().map(|m| m.id()).collect::<HashSet<_>>(),
+            expected
+        );
+        assert!(dex
+            .methods_invoking(&["Lcom/example/Missing;->run", "invalid"])
+            .expect("error finding methods")
+            .is_empty());
+
+        let (_file, dex) = truncated_code_dex();
+        assert!(dex.methods_invoking(&["Ljava/lang/System;->gc"]).is_err());
+    }
+
+    #[test]
+    fn test_class_from_id() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let resolved = dex
+            .class_from_id(class.id())
+            .expect("error resolving class")
+            .expect("class not found");
+        assert_eq!(resolved.jtype(), class.jtype());
+
+        let external = dex
+            .get_type_from_descriptor("Ljava/lang/Object;")
+            .expect("error finding type")
+            .expect("type not found");
+        assert!(dex
+            .class_from_id(external.id())
+            .expect("error resolving class")
+            .is_none());
+
+        let primitive = dex
+            .types()
+            .map(|jtype| jtype.expect("bad type"))
+            .find(|jtype| jtype.is_primitive())
+            .expect("no primitive type");
+        match dex.class_from_id(primitive.id()) {
+            Err(super::Error::InvalidId(_)) => {}
+            other => panic!("expected invalid id error, got: {:?}", other),
+        }
+        assert!(dex.class_from_id(dex.header().type_ids_size()).is_err());
+    }
+
+    #[test]
+    fn test_cfg_isomorphic() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let other = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let method = dex
+            .find_method_by_signature(
+                "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V",
+            )
+            .expect("error finding method")
+            .expect("method not found");
+        let same = other
+            .find_method_by_signature(
+                "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V",
+            )
+            .expect("error finding method")
+            .expect("method not found");
+        assert!(method.cfg_isomorphic(&same).expect("cannot build cfg"));
+        let method_blocks = method.code().expect("no code").cfg().expect("no cfg");
+        let different = dex
+            .methods_with_code()
+            .map(|m| m.expect("error parsing method"))
+            .find(|m| {
+                let cfg = m.code().expect("no code").cfg().expect("no cfg");
+                cfg.blocks().len() != method_blocks.blocks().len()
+            })
+            .expect("no method with a different cfg");
+        assert!(!method.cfg_isomorphic(&different).expect("cannot build cfg"));
+        let abstract_method = dex
+            .classes()
+            .flat_map(|class| class.expect("error parsing class").virtual_methods)
+            .find(|m| m.code().is_none())
+            .expect("no method without code");
+        assert!(!method
+            .cfg_isomorphic(&abstract_method)
+            .expect("cannot build cfg"));
+        assert!(abstract_method
+            .cfg_isomorphic(&abstract_method)
+            .expect("cannot build cfg"));
+    }
+
+    #[test]
+    fn test_code_items() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let code_items = dex
+            .code_items()
+            .collect::<Result<Vec<_>>>()
+            .expect("error parsing code items");
+        assert_eq!(
+            code_items.len() as uint,
+            dex.map_list()
+                .get_len(super::ItemType::CodeItem)
+                .expect("no code items")
+        );
+        let offsets = code_items
+            .iter()
+            .map(|(offset, _)| *offset)
+            .collect::<HashSet<_>>();
+        for class_def in dex.class_defs() {
+            let class_def = class_def.expect("bad class def");
+            let class_data = match dex
+                .get_class_data(class_def.class_data_off)
+                .expect("bad class data")
+            {
+                Some(class_data) => class_data,
+                None => continue,
+            };
+            for methods in class_data
+                .direct_methods()
+                .into_iter()
+                .chain(class_data.virtual_methods())
+            {
+                for method in methods.as_slice().iter().filter(|m| *m.code_offset() != 0) {
+                    assert!(offsets.contains(method.code_offset()));
+                }
+            }
+        }
+        let (offset, code_item) = &code_items[0];
+        let expected = dex
+            .get_code_item(*offset)
+            .expect("bad code item")
+            .expect("no code item");
+        assert_eq!(code_item.insns(), expected.insns());
+    }
+
+    #[test]
+    fn test_disassemble_with_lines() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let method = dex
+            .find_method_by_signature(
+                "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V",
+            )
+            .expect("error finding method")
+            .expect("method not found");
+        let code = method.code().expect("no code");
+        let debug_info = code.debug_info_item().expect("no debug info");
+        assert!(!debug_info.positions().is_empty());
+        let listing = method
+            .disassemble_with_lines(&dex)
+            .expect("error disassembling method");
+        assert_eq!(listing.len(), code.instructions().count());
+        assert!(listing[0].1.starts_with("0000: "));
+        assert!(listing.iter().all(|(line, _)| line.is_some()));
+        let first = debug_info.positions()[0];
+        assert_eq!(listing[0].0, Some(first.line()));
+        let last = debug_info.positions().last().expect("no positions");
+        assert_eq!(listing.last().expect("empty listing").0, Some(last.line()));
+        assert!(listing
+            .last()
+            .expect("empty listing")
+            .1
+            .ends_with(": return-void"));
+        // the operands are resolved as in smali.
+        assert_eq!(
+            listing[1].1,
+            "0003: invoke-virtual {v1, v0}, \
+             Lcom/devoteam/quickaction/QuickActionItem;->findViewById(I)Landroid/view/View;"
+        );
+
+        let (_file, dex) = truncated_code_dex();
+        let truncated = dex
+            .classes()
+            .map(|class| class.expect("error parsing class"))
+            .any(|class| {
+                class
+                    .methods()
+                    .any(|method| method.disassemble_with_lines(&dex).is_err())
+            });
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_find_classes_with_annotation_element() {
+        use crate::encoded_value::EncodedValue;
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let name = EncodedValue::String("BindingAction".to_string().into());
+        let classes = dex
+            .find_classes_with_annotation_element("Ldalvik/annotation/InnerClass;", "name", &name)
+            .expect("error finding classes");
+        assert_eq!(
+            classes
+                .iter()
+                .map(|class| class.jtype().to_string())
+                .collect::<Vec<_>>(),
+            vec!["Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;"]
+        );
+        for (annotation, key) in &[
+            ("Ldalvik/annotation/InnerClass;", "accessFlags"),
+            ("Ldalvik/annotation/Signature;", "name"),
+        ] {
+            assert!(dex
+                .find_classes_with_annotation_element(annotation, key, &name)
+                .expect("error finding classes")
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn test_likely_compiler() {
+        use super::CompilerHint;
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(
+            dex.likely_compiler().expect("error guessing compiler"),
+            CompilerHint::Dx
+        );
+
+        // Replace the last string with a marker of the same length.
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let string_id = dex.header().string_ids_size() - 1;
+            let offset = &mut (dex.string_data_offset(string_id).expect("bad offset") as usize);
+            let len = scroll::Uleb128::read(contents, offset).expect("bad length") as usize;
+            assert!(len >= 6);
+            let marker = format!("~~R8{{{}}}", "x".repeat(len - 6));
+            contents[*offset..*offset + len].copy_from_slice(marker.as_bytes());
+        });
+        assert_eq!(
+            dex.likely_compiler().expect("error guessing compiler"),
+            CompilerHint::R8
+        );
+    }
+
+    #[test]
+    fn test_reachable_instructions() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let method = dex
+            .find_method_by_signature(
+                "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V",
+            )
+            .expect("error finding method")
+            .expect("method not found");
+        let reachable = method
+            .reachable_instructions()
+            .expect("error computing reachable instructions");
+        assert_eq!(
+            reachable.len(),
+            method.code().expect("no code").disassemble().count()
+        );
+        assert_eq!(reachable.iter().next(), Some(&0));
+
+        // dx doesn't emit dead code: only payloads and the nops aligning them are
+        // unreachable.
+        for method in dex.methods_with_code().take(200) {
+            let method = method.expect("error parsing method");
+            let code = method.code().expect("missing code item");
+            let reachable = method
+                .reachable_instructions()
+                .expect("error computing reachable instructions");
+            let mut address = 0;
+            for inst in code.disassemble() {
+                if inst.op() != 0 {
+                    assert!(reachable.contains(&address));
+                } else if inst.length() > 2 {
+                    assert!(!reachable.contains(&address));
+                }
+                address += inst.length() / 2;
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        let mut stream = contents.clone();
+        stream.extend_from_slice(b"trailing data");
+        let dex = DexReader::new()
+            .from_reader(&stream[..])
+            .expect("cannot read dex");
+        assert_eq!(dex.source.as_ref(), &contents[..]);
+        assert!(dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .is_some());
+
+        let truncated = &contents[..contents.len() - 1];
+        match DexReader::new().from_reader(truncated) {
+            Err(crate::Error::MalFormed(message)) => {
+                assert!(message.starts_with("Truncated dex file"), "{}", message)
+            }
+            other => panic!("expected a truncation error, found: {:?}", other.err()),
+        }
+        assert!(DexReader::new().from_reader(&contents[..16]).is_err());
+        assert!(DexReader::new()
+            .from_reader(&b"not a dex file"[..])
+            .is_err());
+    }
+
+    #[test]
+    fn test_method_source_file() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "setText")
+            .expect("method not found");
+        let source_file = method
+            .source_file(&dex)
+            .expect("error getting source file")
+            .expect("no source file");
+        assert_eq!(source_file, "QuickActionItem.java");
+        assert_eq!(Some(&source_file), class.source_file());
+    }
+
+    #[test]
+    fn test_member_classes() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/BoundRemoteViews;")
+            .expect("error finding class")
+            .expect("class not found");
+        let member_classes = class
+            .member_classes()
+            .expect("error getting member classes");
+        assert!(member_classes
+            .iter()
+            .any(|jtype| jtype == "Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;"));
+        for member in member_classes {
+            let member = dex
+                .find_class_by_type(member.id())
+                .expect("error finding class")
+                .expect("class not found");
+            assert_eq!(
+                member
+                    .enclosing_class(&dex)
+                    .expect("error getting enclosing class"),
+                Some(class.jtype().clone())
+            );
+        }
+        let not_nesting = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        assert!(not_nesting
+            .member_classes()
+            .expect("error getting member classes")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_raw_member_order() {
+        use crate::class::MemberKind;
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let members = class.raw_member_order();
+        assert_eq!(
+            members.len(),
+            class.fields().count() + class.methods().count()
+        );
+        let ids = class
+            .fields()
+            .map(|f| f.id())
+            .chain(class.methods().map(|m| m.id()))
+            .collect::<Vec<_>>();
+        assert_eq!(members.iter().map(|m| m.id()).collect::<Vec<_>>(), ids);
+        for (kind, count) in [
+            (MemberKind::StaticField, class.static_fields().len()),
+            (MemberKind::InstanceField, class.instance_fields().len()),
+            (MemberKind::DirectMethod, class.direct_methods().len()),
+            (MemberKind::VirtualMethod, class.virtual_methods().len()),
+        ] {
+            let list = members
+                .iter()
+                .filter(|m| m.kind() == kind)
+                .collect::<Vec<_>>();
+            assert_eq!(list.len(), count);
+            let mut prev = 0;
+            for member in list {
+                assert_eq!(prev + member.idx_diff(), member.id());
+                prev = member.id();
+            }
+        }
+    }
+
+    #[test]
+    fn test_checksum_and_signature() {
+        let contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        let dex = DexReader::new()
+            .read(contents.clone())
+            .expect("cannot read dex");
+        assert_eq!(
+            dex.checksum().to_le_bytes(),
+            [contents[8], contents[9], contents[10], contents[11]]
+        );
+        assert_eq!(
+            dex.checksum(),
+            adler32::adler32(&contents[12..]).expect("cannot compute checksum")
+        );
+        assert_eq!(&dex.signature()[..], &contents[12..32]);
+        assert!(dex.verify_checksum().expect("error computing checksum"));
+        assert!(dex.verify_signature().expect("error computing signature"));
+
+        // `patched_dex` only updates the checksum.
+        let (_file, dex) = patched_dex(|contents| contents[0x70] ^= 0xff);
+        assert!(dex.verify_checksum().expect("error computing checksum"));
+        assert!(!dex.verify_signature().expect("error computing signature"));
+        let mut corrupted = contents;
+        corrupted[0x70] ^= 0xff;
+        assert!(DexReader::new().read(corrupted).is_err());
+    }
+
+    #[test]
+    fn test_runtime_visible_annotations() {
+        use crate::annotation::{AnnotationTarget, Visibility};
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let annotations = dex
+            .runtime_visible_annotations()
+            .expect("error collecting annotations");
+        let mut expected = 0;
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            let is_runtime = |item: &&crate::annotation::AnnotationItem| {
+                item.visibility() == Visibility::Runtime
+            };
+            expected += class.annotations().iter().filter(is_runtime).count();
+            expected += class
+                .fields()
+                .map(|f| f.annotations().iter().filter(is_runtime).count())
+                .sum::<usize>();
+            for method in class.methods() {
+                expected += method.annotations().iter().filter(is_runtime).count();
+                expected += method
+                    .param_annotations()
+                    .iter()
+                    .map(|set| set.iter().filter(is_runtime).count())
+                    .sum::<usize>();
+            }
+        }
+        assert_eq!(annotations.len(), expected);
+        assert!(!annotations.is_empty());
+        // System annotations like `Signature` and `EnclosingClass` are not included.
+        assert!(annotations
+            .iter()
+            .all(|(_, annotation)| !annotation.jtype().to_string().starts_with("Ldalvik/")));
+        assert!(annotations.iter().all(|(target, _)| match target {
+            AnnotationTarget::Class(id) => dex.get_type(*id).is_ok(),
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn test_max_registers_live() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut peak = 0;
+        for method in dex.methods_with_code() {
+            let method = method.expect("error parsing method");
+            let code = method.code().expect("missing code item");
+            let max_live = method
+                .max_registers_live()
+                .expect("error computing liveness");
+            assert!(max_live <= code.registers_size());
+            // The parameters are live on entry unless they are never read.
+            let live_on_entry = code
+                .liveness()
+                .expect("error computing liveness")
+                .live_in(0)
+                .expect("no instruction at 0")
+                .len();
+            assert!(live_on_entry <= code.ins_size() as usize);
+            peak = peak.max(max_live);
+        }
+        assert!(peak > 0);
+    }
+
+    #[test]
+    fn test_string_builder_chains() {
+        use super::{string_builder_chains_in, BuilderCall};
+        use std::collections::HashMap;
+
+        let calls = [
+            (1, BuilderCall::Init),
+            (2, BuilderCall::Append),
+            (3, BuilderCall::ToString),
+        ]
+        .iter()
+        .cloned()
+        .collect::<HashMap<_, _>>();
+        let insns = [
+            0x0022, 0x0005, // 0: new-instance v0, type@5
+            0x1070, 0x0001, 0x0000, // 2: invoke-direct {v0}, meth@1
+            0x0113, 0x0061, // 5: const/16 v1, 'a'
+            0x206e, 0x0002, 0x0010, // 7: invoke-virtual {v0, v1}, meth@2
+            0x000c, // 10: move-result-object v0
+            0x0113, 0x0062, // 11: const/16 v1, 'b'
+            0x206e, 0x0002, 0x0010, // 13: invoke-virtual {v0, v1}, meth@2
+            0x000c, // 16: move-result-object v0
+            0x106e, 0x0003, 0x0000, // 17: invoke-virtual {v0}, meth@3
+            0x000c, // 20: move-result-object v0
+            0x0011, // 21: return-object v0
+        ];
+        let chain = string_builder_chains_in(&insns, 5, &calls).expect("error finding chains");
+        let expected = InstructionIterator::new(&insns[..20])
+            .map(|instruction| instruction.map(|(_, instruction)| instruction))
+            .collect::<Result<Vec<_>>>()
+            .expect("error decoding chain");
+        assert_eq!(chain, expected);
+        assert!(string_builder_chains_in(&insns, 6, &calls)
+            .expect("error finding chains")
+            .is_empty());
+        // Appending a parameter is a regular concatenation.
+        let mut concat = insns;
+        concat[15] = 0x0020;
+        assert!(string_builder_chains_in(&concat, 5, &calls)
+            .expect("error finding chains")
+            .is_empty());
+        assert!(string_builder_chains_in(&insns[..1], 5, &calls).is_err());
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for (_, chain) in dex.string_builder_chains().expect("error finding chains") {
+            match chain.first() {
+                Some(Instruction::NewInstance { .. }) => {}
+                first => panic!("chain starting with {:?}", first),
+            }
+            match chain.last() {
+                Some(Instruction::Invoke { .. }) => {}
+                last => panic!("chain ending with {:?}", last),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exception_footprint() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let names =
+            |types: &[crate::jtype::Type]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/internal/utils/XmlUtils;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "readThisIntArrayXml")
+            .expect("method not found");
+        let footprint = method.exception_footprint().expect("bad footprint");
+        assert_eq!(
+            names(footprint.declared()),
+            vec![
+                "Lorg/xmlpull/v1/XmlPullParserException;",
+                "Ljava/io/IOException;"
+            ]
+        );
+        assert_eq!(
+            names(footprint.caught()),
+            vec![
+                "Ljava/lang/NullPointerException;",
+                "Ljava/lang/NumberFormatException;"
+            ]
+        );
+        assert!(!footprint.catches_all());
+
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/ListViewImageManager;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "getImageFromId")
+            .expect("method not found");
+        let footprint = method.exception_footprint().expect("bad footprint");
+        assert!(footprint.declared().is_empty());
+        assert_eq!(names(footprint.caught()), vec!["Ljava/io/IOException;"]);
+        assert!(footprint.catches_all());
+
+        let code = method.code().expect("no code");
+        let insns_len = code.insns().len() as u64;
+        for try_block in code.tries().iter() {
+            let end = u64::from(try_block.start_addr()) + u64::from(try_block.insn_count());
+            assert!(end <= insns_len);
+            for handler in try_block.catch_handlers() {
+                assert!(handler.addr() < insns_len);
+            }
+        }
+        assert!(code
+            .tries()
+            .iter()
+            .any(|try_block| try_block.catch_all_addr().is_some()));
+    }
+
+    #[test]
+    fn test_modifier_string() {
+        let flags = AccessFlags::PUBLIC | AccessFlags::FINAL;
+        assert_eq!(flags.to_modifier_string(), "public final");
+        let flags = AccessFlags::STATIC | AccessFlags::PRIVATE;
+        assert_eq!(flags.to_modifier_string(), "private static");
+        let flags = AccessFlags::PUBLIC | AccessFlags::INTERFACE | AccessFlags::ABSTRACT;
+        assert_eq!(flags.to_modifier_string(), "public abstract interface");
+        assert_eq!(AccessFlags::empty().to_modifier_string(), "");
+
+        let annotation = flags | AccessFlags::ANNOTATION;
+        assert_eq!(annotation.to_modifier_string(), "public abstract interface");
+        assert_eq!(
+            annotation.to_modifier_string_verbose(),
+            "public abstract @interface"
+        );
+        let enumeration = AccessFlags::PUBLIC | AccessFlags::FINAL | AccessFlags::ENUM;
+        assert_eq!(enumeration.to_modifier_string(), "public final");
+        assert_eq!(
+            enumeration.to_modifier_string_verbose(),
+            "public final enum"
+        );
+        let synthetic = AccessFlags::FINAL | AccessFlags::SYNTHETIC;
+        assert_eq!(synthetic.to_modifier_string(), "final");
+        assert_eq!(synthetic.to_modifier_string_verbose(), "final synthetic");
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        assert_eq!(class.access_flags().to_modifier_string(), "public");
+    }
+
+    #[test]
+    fn test_classes_topological() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let (classes, cuts) = dex
+            .classes_topological_with_cuts()
+            .expect("error sorting classes");
+        assert!(cuts.is_empty());
+        assert_eq!(classes.len(), dex.classes().count());
+        let position = classes
+            .iter()
+            .enumerate()
+            .map(|(i, class)| (class.id(), i))
+            .collect::<std::collections::HashMap<_, _>>();
+        for (i, class) in classes.iter().enumerate() {
+            for parent in class
+                .super_class()
+                .into_iter()
+                .chain(class.interfaces().iter().map(|jtype| jtype.id()))
+            {
+                if let Some(&j) = position.get(&parent) {
+                    assert!(j < i, "{} comes before its parent", class.jtype());
+                }
+            }
+        }
+
+        // Make `ReflectionAction` extend its subclass `BindingAction`.
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let find = |name| {
+                dex.find_class_by_name(name)
+                    .expect("error finding class")
+                    .expect("class not found")
+                    .id()
+            };
+            let subclass = find("Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;");
+            let super_class =
+                find("Lmobi/intuitit/android/widget/SimpleRemoteViews$ReflectionAction;");
+            let position = dex
+                .class_defs()
+                .position(|def| def.expect("bad class def").class_idx == super_class)
+                .expect("class def not found");
+            let offset = dex.inner.class_defs_offset() as usize + position * 32 + 8;
+            contents[offset..offset + 4].copy_from_slice(&subclass.to_le_bytes());
+        });
+        let (classes, cuts) = dex
+            .classes_topological_with_cuts()
+            .expect("error sorting classes");
+        assert_eq!(classes.len(), dex.classes().count());
+        assert_eq!(cuts.len(), 1);
+        let names = cuts
+            .iter()
+            .map(|&(class, parent)| {
+                let name = |id| dex.get_type(id).expect("bad type").to_string();
+                (name(class), name(parent))
+            })
+            .collect::<Vec<_>>();
+        let cycle = [
+            "Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;",
+            "Lmobi/intuitit/android/widget/SimpleRemoteViews$ReflectionAction;",
+        ];
+        assert!(
+            names == vec![(cycle[0].to_string(), cycle[1].to_string())]
+                || names == vec![(cycle[1].to_string(), cycle[0].to_string())]
+        );
+        assert_eq!(
+            dex.classes_topological()
+                .expect("error sorting classes")
+                .len(),
+            classes.len()
+        );
+    }
+
+    #[test]
+    fn test_type_descriptor() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for type_id in 0..dex.inner.type_ids_len() {
+            let jtype = dex.get_type(type_id).expect("bad type");
+            let descriptor = dex.type_descriptor(type_id).expect("bad type");
+            assert_eq!(&descriptor, jtype.type_descriptor());
+        }
+        assert!(dex.type_descriptor(dex.inner.type_ids_len()).is_err());
+    }
+
+    #[test]
+    fn test_lenient_warnings() {
+        let dex = DexReader::new()
+            .lenient()
+            .open("resources/classes.dex")
+            .expect("cannot open dex file");
+        assert!(dex.warnings().is_empty());
+
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let offset = dex.inner.class_defs_offset() as usize + 4;
+            contents[offset + 3] |= 0x80;
+            contents.extend_from_slice(&[0; 4]);
+        });
+        let class_def = dex
+            .class_defs()
+            .next()
+            .expect("no classes")
+            .expect("bad class def");
+        assert!(dex.warnings().is_empty());
+        match dex.classes().next().expect("no classes") {
+            Err(crate::Error::InvalidAccessFlags {
+                class_idx,
+                owner,
+                raw,
+            }) => {
+                assert_eq!(class_idx, class_def.class_idx);
+                assert_eq!(owner, crate::FlagsOwner::Class);
+                assert_eq!(raw, u64::from(class_def.access_flags));
+            }
+            other => panic!(
+                "expected an access flags error, got {:?}",
+                other.map(|class| class.id())
+            ),
+        }
+        let err = crate::Error::InvalidAccessFlags {
+            class_idx: 1,
+            owner: crate::FlagsOwner::Method(2),
+            raw: 0x8000,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Invalid access flags 0x8000 of method 2 in class 1"
+        );
+        let dex = DexReader::new()
+            .lenient()
+            .read(dex.source.as_ref().to_vec())
+            .expect("cannot read dex file");
+        let file_size = dex.header().file_size();
+        assert_eq!(
+            dex.warnings(),
+            &[
+                ParseWarning::FileSizeMismatch {
+                    declared: file_size,
+                    actual: file_size as usize + 4,
+                },
+                ParseWarning::UnknownAccessFlags {
+                    class_id: class_def.class_idx,
+                    access_flags: class_def.access_flags,
+                },
+            ][..]
+        );
+        let class = dex
+            .classes()
+            .next()
+            .expect("no classes")
+            .expect("error parsing class");
+        assert_eq!(
+            class.access_flags().bits(),
+            class_def.access_flags & !0x8000_0000
+        );
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let redacted = dex
+            .redact_strings(|s| s == "Landroid/content/BroadcastReceiver;")
+            .expect("error redacting strings");
+        let dex = DexReader::new()
+            .lenient()
+            .read(redacted)
+            .expect("cannot read dex file");
+        assert!(matches!(
+            dex.warnings(),
+            [ParseWarning::UnsortedStrings { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_lenient_member_recoveries() {
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let source = dex.source.as_ref();
+            let read = |offset: &mut usize| {
+                scroll::Uleb128::read(source, offset).expect("cannot read class data")
+            };
+            // the access flags of the first field and of the first constructor with code
+            // of a class, along with the code of the constructor.
+            let (field, method, code_off) = dex
+                .class_defs()
+                .map(|class_def| class_def.expect("bad class def"))
+                .filter(|class_def| class_def.class_data_off != 0)
+                .find_map(|class_def| {
+                    let offset = &mut (class_def.class_data_off as usize);
+                    let sizes: Vec<_> = (0..4).map(|_| read(offset)).collect();
+                    let mut field = None;
+                    for _ in 0..sizes[0] + sizes[1] {
+                        read(offset);
+                        field = field.or(Some(*offset));
+                        read(offset);
+                    }
+                    for _ in 0..sizes[2] {
+                        read(offset);
+                        let flags_offset = *offset;
+                        let flags = read(offset);
+                        let code_off = read(offset) as usize;
+                        if flags & 0x10000 != 0 && code_off != 0 {
+                            return field.map(|field| (field, flags_offset, code_off));
+                        }
+                    }
+                    None
+                })
+                .expect("no class with a field and a constructor");
+            // 0x20 is not a field flag, and 0x200 not a method flag: the constructor
+            // flag makes the uleb128 of the method flags at least 3 bytes long.
+            contents[field] |= 0x20;
+            contents[method + 1] |= 0x04;
+            contents[code_off + 8..code_off + 12].copy_from_slice(&1u32.to_le_bytes());
+        });
+        assert!(dex.classes().any(|class| matches!(
+            class,
+            Err(crate::Error::InvalidAccessFlags {
+                owner: crate::FlagsOwner::Field(_),
+                ..
+            })
+        )));
+
+        let dex = DexReader::new()
+            .lenient()
+            .read(dex.source.as_ref().to_vec())
+            .expect("cannot read dex file");
+        let (field_id, method_id) = match dex.warnings() {
+            [ParseWarning::UnknownFieldAccessFlags {
+                field_id,
+                access_flags: field_flags,
+            }, ParseWarning::UnknownMethodAccessFlags {
+                method_id,
+                access_flags: method_flags,
+            }, ParseWarning::InvalidDebugInfo {
+                method_id: debug_info_method_id,
+                debug_info_off: 1,
+            }] if method_id == debug_info_method_id => {
+                assert_ne!(field_flags & 0x20, 0);
+                assert_ne!(method_flags & 0x200, 0);
+                (*field_id, *method_id)
+            }
+            other => panic!("unexpected warnings: {:?}", other),
+        };
+        let class = dex
+            .classes()
+            .map(|class| class.expect("error parsing class"))
+            .find(|class| class.methods().any(|method| method.id() == method_id))
+            .expect("constructor not found");
+        let field = class
+            .fields()
+            .find(|field| field.id() == field_id)
+            .expect("field not found");
+        assert_eq!(field.access_flags().bits() & 0x20, 0);
+        let method = class
+            .methods()
+            .find(|method| method.id() == method_id)
+            .expect("constructor not found");
+        assert!(method.is_constructor());
+        assert_eq!(method.access_flags().bits() & 0x200, 0);
+        let code = method.code().expect("missing code item");
+        assert!(code.debug_info_item().is_none());
+        assert!(!code.insns().is_empty());
+    }
+
+    #[test]
+    fn test_map_list() {
+        use super::ItemType;
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let map_list = dex.map_list();
+        let header = dex.header();
+        let expected = [
+            (ItemType::Header, 1, 0),
+            (
+                ItemType::StringIdItem,
+                header.string_ids_size(),
+                header.string_ids_off(),
+            ),
+            (
+                ItemType::TypeIdItem,
+                header.type_ids_size(),
+                header.type_ids_off(),
+            ),
+            (
+                ItemType::ProtoIdItem,
+                header.proto_ids_size(),
+                header.proto_ids_off(),
+            ),
+            (
+                ItemType::FieldIdItem,
+                header.field_ids_size(),
+                header.field_ids_off(),
+            ),
+            (
+                ItemType::MethodIdItem,
+                header.method_ids_size(),
+                header.method_ids_off(),
+            ),
+            (
+                ItemType::ClassDefItem,
+                header.class_defs_size(),
+                header.class_defs_off(),
+            ),
+            (ItemType::MapList, 1, header.map_off()),
+        ];
+        for (item_type, size, offset) in expected.iter() {
+            assert_eq!(map_list.get_len(*item_type), Some(*size));
+            assert_eq!(map_list.get_offset(*item_type), Some(*offset));
+        }
+        assert!(map_list
+            .items()
+            .windows(2)
+            .all(|items| items[0].offset() < items[1].offset()));
+    }
+
+    #[test]
+    fn test_to_java_declaration() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let declarations = |name: &str| {
+            let class = dex
+                .find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found");
+            class
+                .methods()
+                .map(|m| m.to_java_declaration().expect("bad declaration"))
+                .collect::<Vec<_>>()
+        };
+        let launcher_model = declarations("org.adw.launcher.LauncherModel");
+        assert!(launcher_model.contains(
+            &"private org.adw.launcher.LiveFolderInfo findOrMakeLiveFolder(\
+              java.util.HashMap<java.lang.Long, org.adw.launcher.FolderInfo>, long)"
+                .to_string()
+        ));
+        assert!(launcher_model.contains(&"static {}".to_string()));
+        assert!(declarations("org.adw.launcher.Launcher$19")
+            .contains(&"public void onNothingSelected(android.widget.AdapterView<?>)".to_string()));
+        assert!(declarations(
+            "org.metalev.multitouch.controller.MultiTouchController$MultiTouchObjectCanvas"
+        )
+        .contains(
+            &"public abstract T getDraggableObjectAtPoint(\
+              org.metalev.multitouch.controller.MultiTouchController$PointInfo)"
+                .to_string()
+        ));
+        let serializer = declarations("mobi.intuitit.android.internal.utils.FastXmlSerializer");
+        assert!(serializer.contains(
+            &"private void append(char[], int, int) throws java.io.IOException".to_string()
+        ));
+        assert!(serializer.contains(&"public FastXmlSerializer()".to_string()));
+        assert!(
+            declarations("org.adw.launcher.LiveFolder$FolderLoadingTask").contains(
+                &"protected android.database.Cursor doInBackground(\
+              org.adw.launcher.LiveFolderInfo...)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_reflective_field_access() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        // Only the static initializer of `MultiTouchController` reads a field by reflection.
+        let methods = dex
+            .reflective_field_access()
+            .expect("error finding reflective field access");
+        assert_eq!(
+            methods
+                .iter()
+                .map(|m| format!("{}->{}", m.class(), m.name()))
+                .collect::<Vec<_>>(),
+            vec!["Lorg/metalev/multitouch/controller/MultiTouchController;-><clinit>"]
+        );
+
+        let (_file, dex) = truncated_code_dex();
+        assert!(dex.reflective_field_access().is_err());
+    }
+
+    #[test]
+    fn test_method_prototypes() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut intent_handlers = Vec::new();
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            for method in class.methods() {
+                let method_item = dex.get_method_item(method.id()).expect("bad method id");
+                let prototype = dex
+                    .get_prototype(method_item.proto_idx().into())
+                    .expect("bad proto id");
+                assert_eq!(*method.prototype(), prototype);
+                assert_eq!(prototype.shorty().len(), prototype.parameters().len() + 1);
+                if prototype.return_type() == "V"
+                    && prototype.parameters().len() == 1
+                    && prototype.parameters()[0] == "Landroid/content/Intent;"
+                {
+                    intent_handlers.push(method.name().to_string());
+                }
+            }
+        }
+        assert!(intent_handlers.iter().any(|name| name == "onNewIntent"));
+    }
+
+    #[test]
+    fn test_classes_after_corrupt_class() {
+        let healthy = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let (_file, dex) = patched_dex(|contents| {
+            // Point the class_data_off of the first class def past the end of the file.
+            let offset = healthy.inner.class_defs_offset() as usize + 24;
+            contents[offset..offset + 4].copy_from_slice(&0xFFFF_FF00u32.to_le_bytes());
+        });
+        let classes = dex.classes().collect::<Vec<_>>();
+        assert_eq!(classes.len(), healthy.classes().count());
+        assert!(classes[0].is_err());
+        assert!(classes[1..].iter().all(|class| class.is_ok()));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        let dex = DexReader::from_bytes(&contents).expect("cannot read dex");
+        let mapped = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(dex.classes().count(), mapped.classes().count());
+        assert!(dex.classes().all(|class| class.is_ok()));
+        assert!(std::ptr::eq(
+            dex.source.as_ref().as_ptr(),
+            contents.as_ptr()
+        ));
+
+        let mut bad_magic = contents.clone();
+        bad_magic[0] = b'x';
+        assert!(DexReader::from_bytes(&bad_magic).is_err());
+        assert_eq!(dex.header().version(), 35);
+        let mut version = contents.clone();
+        version[4..7].copy_from_slice(b"039");
+        crate::utils::update_checksums(&mut version, scroll::LE).expect("cannot update checksums");
+        let dex = DexReader::from_bytes(&version).expect("cannot read dex");
+        assert_eq!(dex.header().version(), 39);
+        version[4..7].copy_from_slice(b"099");
+        crate::utils::update_checksums(&mut version, scroll::LE).expect("cannot update checksums");
+        assert!(matches!(
+            DexReader::from_bytes(&version),
+            Err(crate::Error::UnsupportedFormat(_))
+        ));
+        let mut compact = contents.clone();
+        compact[..8].copy_from_slice(b"cdex001\0");
+        assert!(matches!(
+            DexReader::from_bytes(&compact),
+            Err(crate::Error::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            DexReader::new().from_reader(&compact[..]),
+            Err(crate::Error::UnsupportedFormat(_))
+        ));
+        assert!(DexReader::from_bytes(&contents[..0x20]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_super() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let find = |name| {
+            dex.find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found")
+        };
+        let class = find("Lcom/devoteam/quickaction/QuickActionItem;");
+        assert!(class
+            .resolve_super(&dex)
+            .expect("error resolving super")
+            .is_none());
+        assert_eq!(
+            class.super_type().as_ref().expect("no super type"),
+            "Landroid/widget/LinearLayout;"
+        );
+
+        let class = find("Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;");
+        let super_class = class
+            .resolve_super(&dex)
+            .expect("error resolving super")
+            .expect("super class not defined");
+        assert_eq!(Some(super_class.id()), class.super_class());
+        assert_eq!(
+            super_class.jtype(),
+            class.super_type().as_ref().expect("no super type")
+        );
+    }
+
+    #[test]
+    fn test_subclasses_and_implementors() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let names = |classes: Vec<crate::class::Class>| {
+            classes
+                .iter()
+                .map(|class| class.jtype().to_string())
+                .collect::<Vec<_>>()
+        };
+        let subclasses = dex
+            .subclasses_of("mobi.intuitit.android.widget.SimpleRemoteViews$ReflectionAction")
+            .expect("error finding subclasses");
+        assert!(names(subclasses).contains(
+            &"Lmobi/intuitit/android/widget/BoundRemoteViews$BindingAction;".to_string()
+        ));
+        let subclasses = dex
+            .subclasses_of("Landroid/widget/LinearLayout;")
+            .expect("error finding subclasses");
+        assert!(
+            names(subclasses).contains(&"Lcom/devoteam/quickaction/QuickActionItem;".to_string())
+        );
+        for class in dex
+            .subclasses_of("java.lang.Object")
+            .expect("error finding subclasses")
+        {
+            assert_eq!(
+                class.super_type().as_ref().expect("no super type"),
+                "Ljava/lang/Object;"
+            );
+        }
+
+        let implementors = dex
+            .implementors_of("android.widget.Checkable")
+            .expect("error finding implementors");
+        assert!(
+            names(implementors).contains(&"Lcom/devoteam/quickaction/QuickActionItem;".to_string())
+        );
+        assert!(dex
+            .subclasses_of("Lcom/example/Missing;")
+            .expect("error finding subclasses")
+            .is_empty());
+        assert!(dex
+            .implementors_of("Lcom/example/Missing;")
+            .expect("error finding implementors")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_annotation_lookup() {
+        use crate::{annotation::Visibility, encoded_value::EncodedValue};
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let find = |name| {
+            dex.find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found")
+        };
+        let class = find("Lmobi/intuitit/android/widget/BoundRemoteViews;");
+        let member_classes = class
+            .annotation("Ldalvik/annotation/MemberClasses;")
+            .expect("annotation not found");
+        assert_eq!(member_classes.visibility(), Visibility::System);
+        match member_classes.element("value") {
+            Some(EncodedValue::Array(values)) => assert!(values.iter().all(|v| v.is_type())),
+            value => panic!("unexpected value: {:?}", value),
+        }
+        assert!(member_classes.element("missing").is_none());
+        assert!(class.annotation("Ljava/lang/Deprecated;").is_none());
+
+        let class = find("Lmobi/intuitit/android/internal/utils/FastXmlSerializer;");
+        let method = class
+            .methods()
+            .find(|method| method.annotation("Ldalvik/annotation/Throws;").is_some())
+            .expect("method not found");
+        let throws = method
+            .annotation("Ldalvik/annotation/Throws;")
+            .expect("annotation not found");
+        match throws.element("value") {
+            Some(EncodedValue::Array(values)) => assert!(matches!(
+                &values[..],
+                [EncodedValue::Type(jtype)] if jtype == "Ljava/io/IOException;"
+            )),
+            value => panic!("unexpected value: {:?}", value),
+        }
+
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            for field in class.fields() {
+                for item in field.annotations().iter() {
+                    let found = field
+                        .annotation(item.jtype().type_descriptor())
+                        .expect("annotation not found");
+                    assert_eq!(found.jtype(), item.jtype());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sections_past_the_end() {
+        let contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        // offsets of the sizes and offsets in the header, along with crafted values.
+        for (field, value) in &[
+            (0x38, 0xffff_fff0u32),
+            (0x3c, 0xffff_fff0),
+            (0x48, 0x0fff_ffff),
+            (0x54, 0xffff_fff0),
+            (0x58, 0x2000_0000),
+            (0x68, 0xffff_fff0),
+            (0x6c, 0xffff_fff0),
+        ] {
+            let mut contents = contents.clone();
+            let contents_len = contents.len();
+            contents[*field..*field + 4].copy_from_slice(&value.to_le_bytes());
+            crate::utils::update_checksums(&mut contents, scroll::LE)
+                .expect("cannot update checksums");
+            match DexReader::new().read(contents) {
+                Err(crate::Error::OutOfBounds { file_size, .. }) => {
+                    assert_eq!(file_size, contents_len)
+                }
+                other => panic!("expected an out of bounds offset, found: {:?}", other.err()),
+            }
+        }
+
+        // a section without elements doesn't allow any id.
+        let mut contents = contents.clone();
+        contents[0x58..0x5c].copy_from_slice(&0u32.to_le_bytes());
+        crate::utils::update_checksums(&mut contents, scroll::LE).expect("cannot update checksums");
+        let dex = DexReader::new().read(contents).expect("cannot read dex");
+        assert!(matches!(
+            dex.get_method_item(0),
+            Err(crate::Error::InvalidMethodIndex { index: 0, max: 0 })
+        ));
+        assert!(dex.get_method_handle_item(0).is_err());
+    }
+
+    #[test]
+    fn test_list_sizes_past_the_end() {
+        let contents = std::fs::read("resources/classes.dex").expect("cannot read dex file");
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class_def = dex
+            .class_defs()
+            .map(|class_def| class_def.expect("bad class def"))
+            .find(|class_def| class_def.interfaces_off != 0 && class_def.annotations_off != 0)
+            .expect("no class with interfaces and annotations");
+        let read = |offset: u32| {
+            let mut contents = contents.clone();
+            let offset = offset as usize;
+            contents[offset..offset + 4].copy_from_slice(&0x1000_0000u32.to_le_bytes());
+            crate::utils::update_checksums(&mut contents, scroll::LE)
+                .expect("cannot update checksums");
+            DexReader::new().read(contents)
+        };
+
+        assert!(matches!(
+            read(dex.header().map_off()).err(),
+            Some(crate::Error::OutOfBounds { .. })
+        ));
+        let patched = read(class_def.interfaces_off).expect("cannot read dex");
+        assert!(matches!(
+            patched.get_type_id_list(class_def.interfaces_off),
+            Err(crate::Error::OutOfBounds { .. })
+        ));
+        // the size of the field annotations.
+        let patched = read(class_def.annotations_off + 4).expect("cannot read dex");
+        assert!(matches!(
+            patched.get_annotations_directory_item(class_def.annotations_off),
+            Err(crate::Error::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_prototypes() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let prototypes = dex
+            .prototypes()
+            .collect::<Result<Vec<_>>>()
+            .expect("bad prototype");
+        assert_eq!(prototypes.len(), dex.proto_ids().count());
+        assert!(prototypes
+            .iter()
+            .any(|prototype| prototype.return_type() == "V"
+                && prototype.parameters().len() == 1
+                && prototype.parameters()[0] == "Landroid/content/Intent;"));
+
+        // the types referenced by the prototypes are a subset of the type_ids.
+        let types = dex.types().collect::<Result<Vec<_>>>().expect("bad type");
+        assert_eq!(types.len(), dex.inner.type_ids_len() as usize);
+        for prototype in &prototypes {
+            for jtype in prototype.parameters() {
+                assert_eq!(types[jtype.id() as usize], *jtype);
+            }
+        }
+
+        let proto_ids_offset = dex.inner.proto_ids_offset() as usize;
+        let (_file, dex) = patched_dex(|contents| {
+            // return_type_idx of the second proto_id_item
+            let offset = proto_ids_offset + 12 + 4;
+            contents[offset..offset + 4].copy_from_slice(&0xffffu32.to_le_bytes());
+        });
+        let prototypes = dex.prototypes().collect::<Vec<_>>();
+        assert_eq!(prototypes.len(), dex.proto_ids().count());
+        assert!(prototypes[0].is_ok());
+        assert!(prototypes[1].is_err());
+        assert!(prototypes[2..].iter().all(|prototype| prototype.is_ok()));
+    }
+
+    #[test]
+    fn test_hidden_api_flags() {
+        use crate::{hiddenapi::HiddenApiFlags, ItemType};
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            assert!(class
+                .fields()
+                .all(|field| field.hidden_api_flags().is_none()));
+            assert!(class
+                .methods()
+                .all(|method| method.hidden_api_flags().is_none()));
+        }
+
+        let name = "Lcom/devoteam/quickaction/QuickActionItem;";
+        let class = dex
+            .find_class_by_name(name)
+            .expect("error finding class")
+            .expect("class not found");
+        let index = dex
+            .class_defs()
+            .position(|def| def.expect("bad class def").class_idx == class.id())
+            .expect("class def not found");
+        let count = class.fields().count() + class.methods().count();
+        let flags = (0..count as u8).map(|i| i % 8).collect::<Vec<_>>();
+        let section = crate::hiddenapi::tests::section(
+            dex.inner.class_defs_len() as usize,
+            &[(index, &flags)],
+        );
+
+        // appends the section, followed by a map_list with an entry for it.
+        let (_file, dex) = appended_dex(|contents| {
+            contents[4..7].copy_from_slice(b"039");
+            let section_offset = append_item(contents, &section);
+            let map_offset = (contents.len() as u32 + 3) & !3;
+            let mut map_items = dex
+                .map_list()
+                .map_items
+                .iter()
+                .map(|item| {
+                    let offset = match item.item_type() {
+                        ItemType::MapList => map_offset,
+                        _ => item.offset(),
+                    };
+                    (item.item_type() as u16, item.size(), offset)
+                })
+                .collect::<Vec<_>>();
+            map_items.push((ItemType::HiddenapiClassDataItem as u16, 1, section_offset));
+            map_items.sort_by_key(|(_, _, offset)| *offset);
+            let mut map_list = (map_items.len() as u32).to_le_bytes().to_vec();
+            for (item_type, size, offset) in map_items {
+                map_list.extend_from_slice(&item_type.to_le_bytes());
+                map_list.extend_from_slice(&[0, 0]);
+                map_list.extend_from_slice(&size.to_le_bytes());
+                map_list.extend_from_slice(&offset.to_le_bytes());
+            }
+            assert_eq!(append_item(contents, &map_list), map_offset);
+            contents[0x34..0x38].copy_from_slice(&map_offset.to_le_bytes());
+        });
+        assert_eq!(dex.header().version(), 39);
+        let flagged = dex
+            .find_class_by_name(name)
+            .expect("error finding class")
+            .expect("class not found");
+        let members = flagged
+            .fields()
+            .map(|field| field.hidden_api_flags())
+            .chain(flagged.methods().map(|method| method.hidden_api_flags()))
+            .collect::<Vec<_>>();
+        assert_eq!(members.len(), count);
+        assert_eq!(members[0], Some(HiddenApiFlags::Whitelist));
+        assert_eq!(members[1], Some(HiddenApiFlags::Greylist));
+        assert_eq!(members[2], Some(HiddenApiFlags::Blacklist));
+        assert!(members.iter().all(Option::is_some));
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            if class.id() != flagged.id() {
+                assert!(class
+                    .methods()
+                    .all(|method| method.hidden_api_flags().is_none()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_class_display() {
+        use crate::class::AccessFlags;
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let find = |name| {
+            dex.find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found")
+        };
+        let mut class = find("Lcom/devoteam/quickaction/QuickActionItem;");
+        assert_eq!(
+            class.to_string(),
+            "public class com.devoteam.quickaction.QuickActionItem extends \
+             android.widget.LinearLayout implements android.widget.Checkable"
+        );
+        assert_eq!(
+            find("Lmobi/intuitit/android/internal/utils/XmlUtils;").to_string(),
+            "public class mobi.intuitit.android.internal.utils.XmlUtils"
+        );
+        assert_eq!(
+            find("Lmobi/intuitit/android/content/LauncherIntent$Action;").to_string(),
+            "public final class mobi.intuitit.android.content.LauncherIntent$Action"
+        );
+
+        class.access_flags = AccessFlags::PUBLIC | AccessFlags::INTERFACE | AccessFlags::ABSTRACT;
+        // the super class of an interface is not part of its declaration.
+        assert_eq!(
+            class.to_string(),
+            "public interface com.devoteam.quickaction.QuickActionItem extends \
+             android.widget.Checkable"
+        );
+        class.interfaces.clear();
+        assert_eq!(
+            class.to_string(),
+            "public interface com.devoteam.quickaction.QuickActionItem"
+        );
+    }
+
+    #[test]
+    fn test_string_xrefs() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/SimpleRemoteViews;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method_id = |name| {
+            class
+                .methods()
+                .find(|method| method.name() == name)
+                .expect("method not found")
+                .id()
+        };
+
+        assert!(dex.string_xrefs.get().is_none());
+        let xrefs = dex
+            .string_xrefs("setImageBitmap")
+            .expect("error finding xrefs");
+        assert_eq!(xrefs, vec![method_id("setImageViewBitmap")]);
+        // the index built by the first lookup serves the next ones.
+        let indexed = dex.string_xrefs.get().expect("strings not indexed");
+        assert!(indexed.values().all(|methods| !methods.is_empty()));
+        let xrefs = dex.string_xrefs("setText").expect("error finding xrefs");
+        assert!(xrefs.contains(&method_id("setTextViewText")));
+        assert!(dex
+            .string_xrefs("not a string of the dex")
+            .expect("error finding xrefs")
+            .is_empty());
+
+        let constants = dex
+            .method_string_constants(method_id("setChronometer"))
+            .expect("error finding string constants");
+        assert_eq!(constants, vec!["setBase", "setFormat", "setStarted"]);
+        let constants = dex
+            .method_string_constants(method_id("setImageViewBitmap"))
+            .expect("error finding string constants");
+        assert_eq!(constants, vec!["setImageBitmap"]);
+
+        // methods of the framework have no code in this dex.
+        let framework_method = dex
+            .method_ids()
+            .position(|item| {
+                let item = item.expect("bad method id");
+                dex.find_class_def_by_type(TypeId::from(item.class_idx()))
+                    .expect("error finding class")
+                    .is_none()
+            })
+            .expect("no framework method");
+        assert!(dex
+            .method_string_constants(framework_method as MethodId)
+            .expect("error finding string constants")
+            .is_empty());
+        assert!(dex.method_string_constants(MethodId::MAX).is_err());
+    }
+
+    #[test]
+    fn test_resolve_members() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let field = &class.instance_fields()[0];
+        let field_ref = dex
+            .resolve_field(field.id())
+            .expect("error resolving field");
+        assert_eq!(field_ref.id(), field.id());
+        assert_eq!(field_ref.class(), class.jtype());
+        assert_eq!(field_ref.name(), field.name());
+        assert_eq!(field_ref.jtype(), field.jtype());
+        assert_eq!(
+            field_ref.to_string(),
+            "Lcom/devoteam/quickaction/QuickActionItem;->mChecked:Z"
+        );
+
+        let method = class
+            .methods()
+            .find(|method| method.name() == "setText")
+            .expect("method not found");
+        let method_ref = dex
+            .resolve_method(method.id())
+            .expect("error resolving method");
+        assert_eq!(method_ref.class(), method.class());
+        assert_eq!(method_ref.name(), method.name());
+        assert_eq!(method_ref.prototype(), method.prototype());
+        assert_eq!(
+            method_ref.to_string(),
+            "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V"
+        );
+        let definition = dex
+            .find_method_by_signature(&method_ref.to_string())
+            .expect("error finding method")
+            .expect("method not found");
+        assert_eq!(definition.id(), method.id());
+
+        // members of the framework are referenced but not defined.
+        let is_defined = |class: &Type| {
+            dex.find_class_by_name(class.type_descriptor())
+                .expect("error finding class")
+                .is_some()
+        };
+        let framework_method = (0..dex.method_ids().count() as MethodId)
+            .map(|id| dex.resolve_method(id).expect("error resolving method"))
+            .find(|method_ref| !is_defined(method_ref.class()))
+            .expect("no framework method");
+        assert!(dex
+            .find_method_by_signature(&framework_method.to_string())
+            .expect("error finding method")
+            .is_none());
+        let framework_field = (0..dex.field_ids().count() as FieldId)
+            .map(|id| dex.resolve_field(id).expect("error resolving field"))
+            .find(|field_ref| !is_defined(field_ref.class()));
+        assert!(framework_field.is_some());
+
+        assert!(dex.resolve_field(FieldId::MAX).is_err());
+        assert!(dex.resolve_method(MethodId::MAX).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let stats = dex.stats().expect("error computing stats");
+        let header = dex.header();
+        assert_eq!(stats.version(), 35);
+        assert_eq!(stats.classes(), header.class_defs_size() as usize);
+        assert_eq!(
+            stats.referenced_methods(),
+            header.method_ids_size() as usize
+        );
+        assert_eq!(stats.referenced_fields(), header.field_ids_size() as usize);
+        assert_eq!(stats.strings(), header.string_ids_size() as usize);
+        assert_eq!(stats.types(), header.type_ids_size() as usize);
+
+        let (mut methods, mut fields, mut bytecode_size) = (0, 0, 0);
+        for class in dex.classes() {
+            let class = class.expect("cannot load class");
+            methods += class.methods().count();
+            fields += class.fields().count();
+            bytecode_size += class
+                .methods()
+                .filter_map(|method| method.code())
+                .map(|code| code.insns().len() * 2)
+                .sum::<usize>();
+        }
+        assert_eq!(stats.defined_methods(), methods);
+        assert_eq!(stats.defined_fields(), fields);
+        assert_eq!(stats.bytecode_size(), bytecode_size);
+        assert!(stats.defined_methods() < stats.referenced_methods());
+        assert!(bytecode_size > 0);
+    }
+
+    #[test]
+    fn test_annotation_defaults() {
+        use crate::encoded_value::EncodedValue;
+
+        let name = "Lmobi/intuitit/android/widget/SimpleRemoteViews;";
+        let patch = |value: &[u8]| {
+            appended_dex(|contents| {
+                let dex =
+                    DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+                let string_id = |string| {
+                    dex.strings
+                        .get_id(string)
+                        .expect("error finding string")
+                        .expect("string not found")
+                };
+                // rename a type of the same length to the system annotation.
+                let offset = dex
+                    .string_data_offset(string_id("Ljava/lang/IndexOutOfBoundsException;"))
+                    .expect("error reading string offset") as usize
+                    + 1;
+                let descriptor = b"Ldalvik/annotation/AnnotationDefault;";
+                contents[offset..offset + descriptor.len()].copy_from_slice(descriptor);
+                let default_type = dex
+                    .get_type_from_descriptor("Ljava/lang/IndexOutOfBoundsException;")
+                    .expect("error finding type")
+                    .expect("type not found")
+                    .id();
+                let class_type = dex
+                    .get_type_from_descriptor(name)
+                    .expect("error finding type")
+                    .expect("type not found");
+
+                // annotation_item, annotation_set_item and annotations_directory_item
+                // the elements are given as pairs of the index of their name and
+                // their one byte encoded value.
+                let elements = value
+                    .chunks(2)
+                    .map(|element| {
+                        (
+                            string_id(["name", "accessFlags"][element[0] as usize]),
+                            &element[1..],
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let mut default = vec![0x1d];
+                default.extend(encoded_annotation(class_type.id(), &elements));
+                let mut item = vec![0x02];
+                item.extend(encoded_annotation(
+                    default_type,
+                    &[(string_id("value"), &default)],
+                ));
+                let item_offset = append_item(contents, &item);
+                let set_offset = append_item(contents, &offset_list(&[item_offset]));
+                let directory_offset =
+                    append_item(contents, &annotations_directory(set_offset, &[], &[]));
+                set_annotations_off(contents, &dex, class_type.id(), directory_offset);
+            })
+        };
+        let find_class = |dex: &Dex<Mmap>| {
+            dex.find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found")
+        };
+
+        // name = null, accessFlags = true
+        let (_file, dex) = patch(&[0, 0x1e, 1, 0x3f]);
+        let class = find_class(&dex);
+        let defaults = class
+            .annotation_defaults()
+            .expect("error reading defaults")
+            .expect("no defaults");
+        let defaults = defaults
+            .iter()
+            .map(|element| (element.name().to_string(), element.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            defaults,
+            vec![
+                ("name".to_string(), &EncodedValue::Null),
+                ("accessFlags".to_string(), &EncodedValue::Boolean(true)),
+            ]
+        );
+
+        let (_file, dex) = patch(&[]);
+        let class = find_class(&dex);
+        let defaults = class.annotation_defaults().expect("error reading defaults");
+        assert_eq!(defaults.map(|defaults| defaults.len()), Some(0));
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert!(find_class(&dex)
+            .annotation_defaults()
+            .expect("error reading defaults")
+            .is_none());
+    }
+
+    #[test]
+    fn test_enum_constants() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let find = |name| {
+            dex.find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found")
+        };
+        let class = find("Lorg/adw/launcher/ResizeViewHandler$ModifyMode;");
+        let names = |fields: Vec<&crate::field::Field>| {
+            fields
+                .iter()
+                .map(|field| field.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        // the fields are sorted by name in the dex.
+        assert_eq!(
+            names(class.static_fields().iter().collect()),
+            vec!["$VALUES", "Grow", "Move", "None"]
+        );
+        assert_eq!(names(class.enum_constants()), vec!["None", "Move", "Grow"]);
+
+        let class = find("Lcom/devoteam/quickaction/QuickActionItem;");
+        assert!(class.enum_constants().is_empty());
+    }
+
+    #[test]
+    fn test_reference_limit() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let methods = dex.method_ref_count();
+        assert_eq!(methods, dex.header().method_ids_size() as usize);
+        assert_eq!(
+            dex.field_ref_count(),
+            dex.header().field_ids_size() as usize
+        );
+        assert!(methods > dex.field_ref_count());
+
+        let fraction = methods as f32 / super::MAX_REFERENCES as f32;
+        assert!(!dex.is_near_reference_limit(0.95));
+        assert!(!dex.is_near_reference_limit(fraction * 1.01));
+        assert!(dex.is_near_reference_limit(fraction * 0.99));
+        assert!(dex.is_near_reference_limit(0.0));
+    }
+
+    #[test]
+    fn test_class_def_interfaces() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut implementing = 0;
+        for (class_def, class) in dex.class_defs().zip(dex.classes()) {
+            let (class_def, class) = (
+                class_def.expect("bad class def"),
+                class.expect("cannot load class"),
+            );
+            assert!(class.interface_ids.get().is_none());
+            let ids = class_def
+                .interface_ids(&dex)
+                .expect("cannot read interfaces");
+            assert_eq!(
+                ids,
+                class.interface_ids(&dex).expect("cannot read interfaces")
+            );
+            assert!(class
+                .interfaces()
+                .iter()
+                .map(|jtype| jtype.id())
+                .eq(ids.iter().copied()));
+            if !ids.is_empty() {
+                implementing += 1;
+            }
+        }
+        assert!(implementing > 0);
+    }
+
+    #[test]
+    fn test_ancestors_of() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let ancestors = |dex: &Dex<Mmap>, name: &str| {
+            dex.ancestors_of(name).map(|ancestors| {
+                ancestors
+                    .iter()
+                    .map(|jtype| jtype.to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            ancestors(&dex, "com.devoteam.quickaction.QuickActionItem").expect("no ancestors"),
+            vec!["Landroid/widget/LinearLayout;"]
+        );
+        assert_eq!(
+            ancestors(&dex, "Lmobi/intuitit/android/widget/SimpleRemoteViews;")
+                .expect("no ancestors"),
+            vec!["Ljava/lang/Object;"]
+        );
+        assert!(ancestors(&dex, "Ljava/lang/Object;")
+            .expect("no ancestors")
+            .is_empty());
+
+        // a subclass of a class defined in the dex.
+        let class = dex
+            .classes()
+            .map(|class| class.expect("cannot load class"))
+            .find(|class| {
+                class
+                    .resolve_super(&dex)
+                    .expect("error resolving super class")
+                    .is_some()
+            })
+            .expect("no subclass");
+        let super_class = class
+            .resolve_super(&dex)
+            .expect("error resolving super class")
+            .expect("super class not found");
+        let chain = ancestors(&dex, &class.jtype().to_string()).expect("no ancestors");
+        assert_eq!(chain[0], super_class.jtype().to_string());
+        assert_eq!(
+            &chain[1..],
+            &ancestors(&dex, &super_class.jtype().to_string()).expect("no ancestors")[..]
+        );
+
+        // make the class its own super class.
+        let name = "Lmobi/intuitit/android/widget/SimpleRemoteViews;";
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let class_idx = dex
+                .find_class_type_id(name)
+                .expect("error finding type")
+                .expect("type not found");
+            let position = dex
+                .class_defs()
+                .position(|def| def.expect("bad class def").class_idx == class_idx)
+                .expect("class def not found");
+            let offset = dex.inner.class_defs_offset() as usize + position * 32 + 8;
+            contents[offset..offset + 4].copy_from_slice(&class_idx.to_le_bytes());
+        });
+        match dex.ancestors_of(name) {
+            Err(crate::Error::CyclicHierarchy(class)) => assert_eq!(class, name),
+            other => panic!("expected a cyclic hierarchy, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_find_methods() {
+        use crate::method::{AccessFlags, MethodPattern};
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let find = |pattern: MethodPattern| {
+            dex.find_methods(&pattern)
+                .expect("error finding methods")
+                .iter()
+                .map(|method| method.to_string())
+                .collect::<Vec<_>>()
+        };
+        let set_text =
+            "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V".to_string();
+        let pattern = || MethodPattern::new().name("setText").return_type("V");
+        assert!(find(pattern()).contains(&set_text));
+        for params in &[&["*"][..], &[".."], &["Ljava/lang/String;", ".."]] {
+            assert!(find(pattern().params(params)).contains(&set_text));
+        }
+        for params in &[&[][..], &["*", "*"], &["I", ".."], &["*", "*", ".."]] {
+            assert!(!find(pattern().params(params)).contains(&set_text));
+        }
+        assert!(!find(pattern().access_flags(AccessFlags::STATIC)).contains(&set_text));
+
+        let methods = find(
+            MethodPattern::new()
+                .name("set*Bitmap")
+                .params(&["I", ".."])
+                .access_flags(AccessFlags::PUBLIC),
+        );
+        assert!(methods.contains(
+            &"Lmobi/intuitit/android/widget/SimpleRemoteViews;->\
+              setImageViewBitmap(ILandroid/graphics/Bitmap;)V"
+                .to_string()
+        ));
+        for method in &methods {
+            let name = method.split("->").nth(1).expect("no name");
+            assert!(name.starts_with("set") && name.contains("Bitmap(I"));
+        }
+        assert!(find(MethodPattern::new().name("decrypt").return_type("[B")).is_empty());
+        assert_eq!(
+            find(MethodPattern::new()).len(),
+            dex.classes()
+                .map(|class| class.expect("cannot load class").methods().count())
+                .sum::<usize>()
+        );
+
+        let invalid = MethodPattern::new().params(&["..", "I"]);
+        assert!(matches!(
+            dex.find_methods(&invalid),
+            Err(crate::Error::InvalidDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn test_static_values() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut with_values = None;
+        for (class_def, class) in dex.class_defs().zip(dex.classes()) {
+            let (class_def, class) = (
+                class_def.expect("bad class def"),
+                class.expect("cannot load class"),
+            );
+            let values = dex
+                .get_static_values(class_def.static_values_off)
+                .expect("cannot read static values");
+            let values = values.values();
+            for (index, field) in class.static_fields().iter().enumerate() {
+                assert_eq!(class.static_value_for(field), values.get(index));
+            }
+            // instance fields have no static value.
+            for field in class.instance_fields() {
+                assert!(class.static_value_for(field).is_none());
+            }
+            if values.len() > 1 && with_values.is_none() {
+                with_values = Some((class_def, values.len()));
+            }
+        }
+
+        // use the static values of a class for a class with fewer static fields.
+        let (source, len) = with_values.expect("no class with static values");
+        let (_file, dex) = patched_dex(|contents| {
+            let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+            let position = dex
+                .classes()
+                .position(|class| {
+                    let class = class.expect("cannot load class");
+                    class.static_fields().len() < len && !class.instance_fields().is_empty()
+                })
+                .expect("no class with fewer static fields");
+            let offset = dex.inner.class_defs_offset() as usize + position * 32 + 28;
+            contents[offset..offset + 4].copy_from_slice(&source.static_values_off.to_le_bytes());
+        });
+        let errors = dex
+            .classes()
+            .filter_map(|class| class.err())
+            .collect::<Vec<_>>();
+        match &errors[..] {
+            [crate::Error::MalFormed(message)] => assert!(message.contains("static values for")),
+            errors => panic!("expected a malformed class, got: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn test_parameter_annotations() {
+        let name = "Lmobi/intuitit/android/widget/SimpleRemoteViews;";
+        // `sets` tells for each listed parameter whether it is annotated.
+        let patch = |sets: &[bool]| {
+            appended_dex(|contents| {
+                let dex =
+                    DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+                let class = dex
+                    .find_class_by_name(name)
+                    .expect("error finding class")
+                    .expect("class not found");
+                let method = class
+                    .methods()
+                    .find(|method| method.name() == "setBitmap")
+                    .expect("method not found");
+
+                // annotation_item of the class type without elements, visible at
+                // runtime, and its annotation_set_item.
+                let mut item = vec![0x01];
+                item.extend(encoded_annotation(class.id(), &[]));
+                let item_offset = append_item(contents, &item);
+                let set_offset = append_item(contents, &offset_list(&[item_offset]));
+                // annotation_set_ref_list, a parameter without annotations has offset 0.
+                let offsets = sets
+                    .iter()
+                    .map(|annotated| if *annotated { set_offset } else { 0 })
+                    .collect::<Vec<_>>();
+                let list_offset = append_item(contents, &offset_list(&offsets));
+                // annotations_directory_item with only the parameter annotations.
+                let directory = annotations_directory(0, &[], &[(method.id() as u32, list_offset)]);
+                let directory_offset = append_item(contents, &directory);
+                set_annotations_off(contents, &dex, class.id(), directory_offset);
+            })
+        };
+        let annotations = |dex: &Dex<Mmap>| {
+            let class = dex
+                .find_class_by_name(name)
+                .expect("error finding class")
+                .expect("class not found");
+            let method = class
+                .methods()
+                .find(|method| method.name() == "setBitmap")
+                .expect("method not found");
+            assert_eq!(method.params().len(), 3);
+            method
+                .parameter_annotations()
+                .iter()
+                .map(|set| {
+                    set.iter()
+                        .map(|item| item.jtype().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let annotated = vec![name.to_string()];
+
+        let (_file, dex) = patch(&[true, false, true]);
+        assert_eq!(
+            annotations(&dex),
+            vec![annotated.clone(), vec![], annotated.clone()]
+        );
+        // the listed parameters are the last ones.
+        let (_file, dex) = patch(&[true]);
+        assert_eq!(annotations(&dex), vec![vec![], vec![], annotated]);
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(annotations(&dex), vec![Vec::<String>::new(); 3]);
+    }
+
+    #[test]
+    fn test_class_at() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(dex.num_classes(), dex.header().class_defs_size() as usize);
+        for (index, class) in dex.classes().enumerate() {
+            let class = class.expect("error parsing class");
+            let at = dex.class_at(index).expect("error parsing class");
+            assert_eq!(at.id(), class.id());
+            assert_eq!(at.jtype(), class.jtype());
+            assert_eq!(at.methods().count(), class.methods().count());
+            assert_eq!(at.fields().count(), class.fields().count());
+        }
+        match dex.class_at(dex.num_classes()) {
+            Err(crate::Error::InvalidClassDefIndex { index, max }) => {
+                assert_eq!(index, dex.num_classes());
+                assert_eq!(max, dex.header().class_defs_size());
+            }
+            other => panic!(
+                "expected an invalid class index error, got {:?}",
+                other.map(|c| c.id())
+            ),
+        }
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let header = dex.header();
+        let max = header.type_ids_size();
+        match dex.get_type(max) {
+            Err(crate::Error::InvalidTypeIndex { index, max: size }) => {
+                assert_eq!((index, size), (max, header.type_ids_size()))
+            }
+            other => panic!("expected an invalid type index, got {:?}", other),
+        }
+        let max = header.string_ids_size();
+        let err = dex.get_string(max).expect_err("string id is valid");
+        assert!(matches!(err, crate::Error::InvalidStringIndex { index, .. } if index == max));
+        assert_eq!(
+            err.to_string(),
+            format!("Invalid string id: {} (of {})", max, max)
+        );
+        let max = header.method_ids_size();
+        assert!(matches!(
+            dex.get_method_item(u64::from(max)),
+            Err(crate::Error::InvalidMethodIndex { max: size, .. }) if size == max
+        ));
+        assert!(matches!(
+            dex.get_field_item(u64::from(header.field_ids_size())),
+            Err(crate::Error::InvalidFieldIndex { .. })
+        ));
+        assert!(matches!(
+            dex.get_proto_item(u64::from(header.proto_ids_size())),
+            Err(crate::Error::InvalidProtoIndex { .. })
+        ));
+        // the test file has no method handles.
+        assert!(matches!(
+            dex.get_method_handle_item(0),
+            Err(crate::Error::InvalidMethodHandleIndex { index: 0, max: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_string_table() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let table = dex.string_table().expect("error reading strings");
+        assert_eq!(table.len(), dex.header().string_ids_size() as usize);
+        assert!(table.windows(2).all(|pair| {
+            pair[0].encode_utf16().cmp(pair[1].encode_utf16()) == std::cmp::Ordering::Less
+        }));
+        for string in table.iter().step_by(7) {
+            assert!(dex.contains_string(string).expect("error searching string"));
+        }
+        let class_name = "Lmobi/intuitit/android/widget/SimpleRemoteViews;";
+        assert!(table.iter().any(|string| string == class_name));
+        // neither a prefix nor an extension of a string in the table.
+        assert!(!dex
+            .contains_string(&class_name[..class_name.len() - 1])
+            .expect("error searching string"));
+        assert!(!dex
+            .contains_string(&format!("{}x", class_name))
+            .expect("error searching string"));
+    }
+
+    #[test]
+    fn test_with_annotation() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let mut classes = Vec::new();
+        let mut methods = Vec::new();
+        for class in dex.classes() {
+            let class = class.expect("error parsing class");
+            if class
+                .annotation("Ldalvik/annotation/MemberClasses;")
+                .is_some()
+            {
+                classes.push(class.id());
+            }
+            for method in class.methods() {
+                if method.annotation("Ldalvik/annotation/Throws;").is_some() {
+                    methods.push(method.id());
+                }
+            }
+        }
+        assert!(!classes.is_empty());
+        assert!(!methods.is_empty());
+
+        let found = dex
+            .classes_with_annotation("dalvik.annotation.MemberClasses")
+            .expect("error finding classes");
+        assert_eq!(
+            found.iter().map(|class| class.id()).collect::<Vec<_>>(),
+            classes
+        );
+        let found = dex
+            .methods_with_annotation("Ldalvik/annotation/Throws;")
+            .expect("error finding methods");
+        let mut found = found.iter().map(|method| method.id()).collect::<Vec<_>>();
+        found.sort_unstable();
+        methods.sort_unstable();
+        assert_eq!(found, methods);
+        assert!(dex
+            .methods_with_annotation("android.webkit.JavascriptInterface")
+            .expect("error finding methods")
+            .is_empty());
+        assert!(dex
+            .classes_with_annotation("Ldalvik/annotation/Throws;")
+            .expect("error finding classes")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_classes_with_clinit() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let classes = dex
+            .classes_with_clinit()
+            .expect("error finding classes with clinit");
+        assert!(!classes.is_empty());
+        for class in &classes {
+            let clinit = class.static_initializer().expect("no static initializer");
+            assert!(clinit.is_static() && clinit.is_constructor());
+        }
+        let total = dex.header().class_defs_size() as usize;
+        assert!(classes.len() < total);
+    }
+
+    #[test]
+    fn test_annotation_types() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let types = dex
+            .annotation_types()
+            .expect("error collecting annotation types");
+        assert!(!types.is_empty());
+        assert!(types.iter().all(|t| t.is_class()));
+        assert!(types
+            .iter()
+            .any(|t| t.type_descriptor() == "Ldalvik/annotation/Signature;"));
     }
 
     #[test]
@@ -1,9 +1,8 @@
-use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::fmt;
+use core::convert::TryInto;
+use core::fmt;
 
 #[allow(dead_code)]
-mod opcodes;
+pub(crate) mod opcodes;
 
 use crate::disass::opcodes::*;
 
@@ -1125,6 +1124,11 @@ struct InstType {
 
 const INSTTYPES: [InstType; 256] = include!("insn.in");
 
+/// Mnemonic of the opcode `op`, spelled with `_` where the Dalvik mnemonics use `-`.
+pub(crate) fn mnemonic(op: usize) -> &'static str {
+    INSTTYPES[op].mnemonic
+}
+
 pub struct Inst<'a> {
     bytes: &'a [u8],
 }
@@ -1170,12 +1174,31 @@ impl Inst<'_> {
                 return len;
             }
 
-            panic!("Unexpected NOP type {:x}", self.bytes[1]);
+            // the high byte of a nop which isn't a payload is ignored
+            return 2;
         }
 
         return table!(self).length();
     }
 
+    /// Length in bytes of the instruction, or `None` if its header is truncated, i.e.
+    /// shorter than the code unit of the opcode or than the 8 bytes read for the
+    /// size of a payload.
+    fn checked_length(&self) -> Option<usize> {
+        let is_payload = matches!(self.bytes.get(..2), Some([0, 1..=3]));
+        if self.bytes.len() < 2 || (is_payload && self.bytes.len() < 8) {
+            return None;
+        }
+        Some(self.length())
+    }
+
+    /// Number of 16-bit code units occupied by the instruction, including the data of
+    /// payload pseudo-instructions. This is `length` in code units rather than bytes;
+    /// a `u32` is needed since payloads can be larger than 65535 code units.
+    pub fn size_in_code_units(&self) -> u32 {
+        (self.length() / 2) as u32
+    }
+
     pub fn get_a(&self) -> u64 {
         table!(self).a(self.bytes)
     }
@@ -1292,6 +1315,9 @@ impl fmt::Display for Inst<'_> {
     }
 }
 
+/// Iterator over the instructions of a code. The iteration stops at the first
+/// instruction which doesn't fit in the code, so the yielded instructions can be
+/// read without going past it.
 pub struct InstIterator<'a> {
     pub bytes: &'a [u8],
     index: usize,
@@ -1303,23 +1329,36 @@ impl InstIterator<'_> {
         InstIterator {
             bytes: bytes,
             index: 0,
-            length: length,
+            length: length.min(bytes.len()),
         }
     }
+
+    /// Iterator over the instructions encoded in the code units `insns`.
+    pub(crate) fn from_insns(insns: &[u16]) -> InstIterator<'_> {
+        let (_, data, _) = unsafe { insns.align_to::<u8>() };
+        InstIterator::new(data, insns.len() * 2)
+    }
 }
 
 impl<'a> Iterator for InstIterator<'a> {
     type Item = Inst<'a>;
 
     fn next(&mut self) -> Option<Inst<'a>> {
-        if self.index < self.length {
-            let i = Inst {
-                bytes: &self.bytes[self.index..],
-            };
-            self.index += i.length();
-            Some(i)
-        } else {
-            None
+        if self.index >= self.length {
+            return None;
+        }
+        let bytes = &self.bytes[self.index..self.length];
+        match (Inst { bytes }).checked_length() {
+            Some(length) if length <= bytes.len() => {
+                self.index += length;
+                Some(Inst {
+                    bytes: &bytes[..length],
+                })
+            }
+            _ => {
+                self.index = self.length;
+                None
+            }
         }
     }
 }
@@ -1334,4 +1373,31 @@ mod test_inst {
         let i = Inst { bytes: &buf };
         assert_eq!(i.op(), buf[0] as usize);
     }
+
+    #[test]
+    fn test_size_in_code_units() {
+        let insns = [
+            0x1012, // const/4 v0, #+1
+            0x106e, 0x0000, 0x0000, // invoke-virtual {v0}, meth@0
+            // packed-switch-payload with two targets
+            0x0100, 0x0002, 0x0000, 0x0000, 0x0001, 0x0000, 0x0002, 0x0000,
+        ];
+        let sizes = InstIterator::from_insns(&insns)
+            .map(|i| i.size_in_code_units())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![1, 3, 8]);
+        assert_eq!(sizes.iter().sum::<u32>() as usize, insns.len());
+    }
+
+    #[test]
+    fn test_truncated_instructions() {
+        // invoke-virtual without its operands.
+        let insns = [0x000e, 0x006e];
+        assert_eq!(InstIterator::from_insns(&insns).count(), 1);
+        // fill-array-data-payload header and payloads larger than the code.
+        assert_eq!(InstIterator::from_insns(&[0x0300]).count(), 0);
+        let insns = [0x0100, 0xffff, 0x0000, 0x0000];
+        assert_eq!(InstIterator::from_insns(&insns).count(), 0);
+        assert_eq!(InstIterator::new(&[0x0e, 0x00], 16).count(), 1);
+    }
 }
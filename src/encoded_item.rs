@@ -1,5 +1,6 @@
+use alloc::vec::Vec;
+use core::ops::Deref;
 use scroll::{ctx, Pread, Sleb128, Uleb128};
-use std::ops::Deref;
 
 use getset::Getters;
 
@@ -73,8 +74,17 @@ where
         let mut prev = 0;
         let offset = &mut 0;
         let mut inner = Vec::with_capacity(len);
-        for _ in 0..len {
+        for index in 0..len {
             let encoded_item: T = source.gread_with(offset, prev)?;
+            // every index after the first is encoded as a non-zero difference
+            // from the previous one, so the list must be strictly ascending.
+            if index != 0 && encoded_item.id() <= prev {
+                return Err(Error::MalFormed(format!(
+                    "Encoded item id {} is not greater than previous id {}",
+                    encoded_item.id(),
+                    prev
+                )));
+            }
             prev = encoded_item.id();
             inner.push(encoded_item);
         }
@@ -190,3 +200,43 @@ impl<'a> ctx::TryFromCtx<'a, ()> for EncodedTypeAddrPair {
         ))
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use scroll::Pread;
+
+    use super::EncodedCatchHandler;
+    use crate::{code::ExceptionType, DexReader};
+
+    fn handlers(bytes: &[u8]) -> Vec<(Option<String>, u64)> {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let handler: EncodedCatchHandler = bytes.pread_with(0, &dex).expect("bad handler");
+        handler
+            .handlers()
+            .into_iter()
+            .map(|handler| match handler.exception {
+                ExceptionType::Ty(jtype) => (Some(jtype.to_string()), handler.addr),
+                ExceptionType::BaseException => (None, handler.addr),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_catch_all_handler() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let names = [1, 2].map(|id| Some(dex.get_type(id).expect("bad type").to_string()));
+        // -2: two typed handlers followed by a catch-all.
+        assert_eq!(
+            handlers(&[0x7e, 1, 0x10, 2, 0x20, 0x30]),
+            vec![
+                (names[0].clone(), 0x10),
+                (names[1].clone(), 0x20),
+                (None, 0x30)
+            ]
+        );
+        // 0: only a catch-all.
+        assert_eq!(handlers(&[0, 0x30]), vec![(None, 0x30)]);
+        // 1: a single typed handler.
+        assert_eq!(handlers(&[1, 1, 0x10]), vec![(names[0].clone(), 0x10)]);
+    }
+}
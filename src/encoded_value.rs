@@ -1,4 +1,6 @@
 //! Contains structures defining values in a `Dex`.
+use alloc::vec::Vec;
+
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{self, ctx, Pread, Uleb128, LE};
@@ -19,7 +21,7 @@ use crate::{
 
 /// Used to represent values of fields, annotations etc.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#encoding)
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EncodedValue {
     Byte(byte),
     Short(short),
@@ -270,6 +272,13 @@ enum ValueType {
 
 macro_rules! try_extended_gread {
     ($source:expr,$offset:expr,$value_arg:expr,$size:expr,$sign_extended:literal) => {{
+        if $value_arg >= $size {
+            return Err(Error::MalFormed(format!(
+                "Invalid size {} for a value of {} bytes",
+                $value_arg + 1,
+                $size
+            )));
+        }
         if *$offset + $value_arg >= $source.len() {
             return Err(Error::Scroll(scroll::Error::TooBig {
                     size: *$offset + $value_arg,
@@ -311,6 +320,40 @@ macro_rules! try_extended_gread {
 
 }
 
+/// Reads the `value_arg + 1` bytes of a float or a double. Unlike integral values, they
+/// are zero-extended to the right, i.e. they are the most significant bytes of the
+/// little endian value of `size` bytes.
+fn read_right_zero_extended(
+    source: &[ubyte],
+    offset: &mut usize,
+    value_arg: usize,
+    size: usize,
+) -> Result<[ubyte; 8]> {
+    if value_arg >= size {
+        return Err(Error::MalFormed(format!(
+            "Invalid size {} for a value of {} bytes",
+            value_arg + 1,
+            size
+        )));
+    }
+    let value: &[ubyte] = source.gread_with(offset, value_arg + 1)?;
+    let mut bytes = [0x0; 8];
+    bytes[size - value.len()..size].copy_from_slice(value);
+    Ok(bytes)
+}
+
+/// Returns an error if the `value_arg` of a value of type `value_type`, which doesn't
+/// give the size of the value, is greater than `max`.
+fn check_value_arg(value_type: &ValueType, value_arg: usize, max: usize) -> Result<()> {
+    if value_arg > max {
+        return Err(Error::MalFormed(format!(
+            "Invalid value_arg {} for a value of type {:?}",
+            value_arg, value_type
+        )));
+    }
+    Ok(())
+}
+
 impl<'a, S> ctx::TryFromCtx<'a, &super::Dex<S>> for EncodedValue
 where
     S: AsRef<[u8]>,
@@ -329,83 +372,71 @@ where
         debug!(target: "encoded-value", "encoded value type: {:?}, value_arg: {}", value_type, value_arg);
         let value = match value_type {
             ValueType::Byte => {
-                debug_assert_eq!(value_arg, 0);
                 EncodedValue::Byte(try_extended_gread!(source, offset, value_arg, 1))
             }
             ValueType::Short => {
-                debug_assert!(value_arg < 2);
                 EncodedValue::Short(try_extended_gread!(source, offset, value_arg, 2, SIGN))
             }
             ValueType::Char => {
-                debug_assert!(value_arg < 2);
                 EncodedValue::Char(try_extended_gread!(source, offset, value_arg, 2))
             }
             ValueType::Int => {
-                debug_assert!(value_arg < 4);
                 EncodedValue::Int(try_extended_gread!(source, offset, value_arg, 4, SIGN))
             }
             ValueType::Long => {
-                debug_assert!(value_arg < 8);
                 EncodedValue::Long(try_extended_gread!(source, offset, value_arg, 8, SIGN))
             }
             ValueType::Float => {
-                debug_assert!(value_arg < 4);
-                EncodedValue::Float(try_extended_gread!(source, offset, value_arg, 4))
+                let bytes = read_right_zero_extended(source, offset, value_arg, 4)?;
+                EncodedValue::Float(bytes.pread_with(0, LE)?)
             }
             ValueType::Double => {
-                debug_assert!(value_arg < 8);
-                EncodedValue::Double(try_extended_gread!(source, offset, value_arg, 8))
+                let bytes = read_right_zero_extended(source, offset, value_arg, 8)?;
+                EncodedValue::Double(bytes.pread_with(0, LE)?)
             }
             ValueType::MethodType => {
-                debug_assert!(value_arg < 4);
                 let proto_id: uint = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::MethodType(dex.get_proto_item(ProtoId::from(proto_id))?)
             }
             ValueType::MethodHandle => {
-                debug_assert!(value_arg < 4);
                 let index: MethodHandleId = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::MethodHandle(dex.get_method_handle_item(index)?)
             }
             ValueType::String => {
-                debug_assert!(value_arg < 4);
                 let string_id: StringId = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::String(dex.get_string(string_id)?)
             }
             ValueType::Type => {
-                debug_assert!(value_arg < 4);
                 let type_id: TypeId = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::Type(dex.get_type(type_id)?)
             }
             ValueType::Field => {
-                debug_assert!(value_arg < 4);
                 let index: uint = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::Field(dex.get_field_item(FieldId::from(index))?)
             }
             ValueType::Method => {
-                debug_assert!(value_arg < 4);
                 let index: uint = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::Method(dex.get_method_item(MethodId::from(index))?)
             }
             ValueType::Enum => {
-                debug_assert!(value_arg < 4);
                 let index: uint = try_extended_gread!(source, offset, value_arg, 4);
                 EncodedValue::Enum(dex.get_field_item(FieldId::from(index))?)
             }
             ValueType::Array => {
-                debug_assert!(value_arg == 0);
+                check_value_arg(&value_type, value_arg, 0)?;
                 let encoded_array: EncodedArray = source.gread_with(offset, dex)?;
                 EncodedValue::Array(encoded_array.into_inner())
             }
             ValueType::Annotation => {
-                debug_assert!(value_arg == 0);
+                check_value_arg(&value_type, value_arg, 0)?;
                 EncodedValue::Annotation(source.gread_with(offset, dex)?)
             }
             ValueType::Null => {
-                debug_assert!(value_arg == 0);
+                check_value_arg(&value_type, value_arg, 0)?;
                 EncodedValue::Null
             }
             ValueType::Boolean => {
-                debug_assert!(value_arg < 2);
+                check_value_arg(&value_type, value_arg, 1)?;
                 EncodedValue::Boolean(value_arg == 1)
             }
         };
@@ -420,6 +451,11 @@ pub struct EncodedArray {
 }
 
 impl EncodedArray {
+    /// Values of the array.
+    pub fn values(&self) -> &[EncodedValue] {
+        &self.values
+    }
+
     pub(crate) fn into_inner(self) -> Vec<EncodedValue> {
         self.values
     }
@@ -444,3 +480,88 @@ where
         Ok((Self { values }, *offset))
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use scroll::Pread;
+
+    use super::{EncodedArray, EncodedValue};
+    use crate::DexReader;
+
+    #[test]
+    fn test_float_and_double_values() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let value = |bytes: &[u8]| bytes.pread_with::<EncodedValue>(0, &dex);
+        // 1.0f is 0x3f800000, encoded in its 2 most significant bytes.
+        assert_eq!(value(&[0x30, 0x80, 0x3f]).expect("bad float"), 1.0f32);
+        assert_eq!(
+            value(&[0x70, 0x00, 0x00, 0x20, 0xc1]).expect("bad float"),
+            -10.0f32
+        );
+        // 2.0 is 0x4000000000000000, encoded in its most significant byte.
+        assert_eq!(value(&[0x11, 0x40]).expect("bad double"), 2.0f64);
+        assert_eq!(value(&[0x31, 0xe0, 0x3f]).expect("bad double"), 0.5f64);
+        assert!(value(&[0x90, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_value_arg() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let value = |bytes: &[u8]| bytes.pread_with::<EncodedValue>(0, &dex);
+        // a VALUE_BYTE of 2 bytes, a VALUE_STRING of 5 bytes, a VALUE_NULL and a
+        // VALUE_BOOLEAN with a value_arg other than 0 and 1.
+        assert!(value(&[0x20, 0x00, 0x00]).is_err());
+        assert!(value(&[0x97, 0x00, 0x00, 0x00, 0x00, 0x00]).is_err());
+        assert!(value(&[0x3e]).is_err());
+        assert!(value(&[0x5f]).is_err());
+        assert_eq!(
+            value(&[0x3f]).expect("bad boolean"),
+            EncodedValue::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn test_nested_values() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        // VALUE_ARRAY of an int, a nested VALUE_ARRAY of a string and null.
+        let bytes = [0x1c, 0x03, 0x24, 0xff, 0x7f, 0x1c, 0x01, 0x17, 0x00, 0x1e];
+        let array = bytes
+            .pread_with::<EncodedValue>(0, &dex)
+            .expect("bad array");
+        let first_string = dex.get_string(0).expect("bad string");
+        match array {
+            EncodedValue::Array(values) => {
+                assert_eq!(values.len(), 3);
+                assert_eq!(values[0], 0x7fff);
+                assert_eq!(
+                    values[1],
+                    EncodedValue::Array(vec![EncodedValue::String(first_string)])
+                );
+                assert!(values[2].is_null());
+            }
+            value => panic!("not an array: {:?}", value),
+        }
+
+        // VALUE_ANNOTATION of type 0 with an element named by string 0 set to true.
+        let bytes = [0x1d, 0x00, 0x01, 0x00, 0x3f];
+        match bytes
+            .pread_with::<EncodedValue>(0, &dex)
+            .expect("bad annotation")
+        {
+            EncodedValue::Annotation(annotation) => {
+                assert_eq!(*annotation.jtype(), dex.get_type(0).expect("bad type"));
+                assert_eq!(annotation.elements().len(), 1);
+                assert_eq!(*annotation.elements()[0].value(), true);
+            }
+            value => panic!("not an annotation: {:?}", value),
+        }
+
+        let array = [0x02, 0x04, 0x2a, 0x1f]
+            .pread_with::<EncodedArray>(0, &dex)
+            .expect("bad array");
+        assert_eq!(
+            array.values(),
+            &[EncodedValue::Int(42), EncodedValue::Boolean(false)]
+        );
+    }
+}
@@ -1,42 +1,153 @@
-use std::{
+use alloc::string::String;
+use core::{
     error,
     fmt::{self, Display},
-    io,
 };
+#[cfg(feature = "std")]
+use std::io;
 
 use scroll;
 
+use crate::{
+    class::ClassId,
+    field::FieldId,
+    jtype::TypeId,
+    method::{MethodHandleId, MethodId, ProtoId},
+    string::StringId,
+    uint, ulong,
+};
+
+/// Item whose access flags are invalid, see `Error::InvalidAccessFlags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagsOwner {
+    /// The class itself.
+    Class,
+    /// A field of the class.
+    Field(FieldId),
+    /// A method of the class.
+    Method(MethodId),
+}
+
 #[derive(Debug)]
 pub enum Error {
     MalFormed(String),
+    #[cfg(feature = "std")]
     IO(io::Error),
     InvalidId(String),
     Scroll(scroll::Error),
     BadOffset(usize, String),
+    InvalidDescriptor(String),
+    UnsupportedFormat(String),
+    /// A class is its own ancestor, given the type descriptor of the class.
+    CyclicHierarchy(String),
+    /// The access flags of a class, or of one of its members, have bits which are
+    /// not defined for it. `class_idx` is the class or the class declaring the member,
+    /// and `owner` tells whether the flags are those of the class or of a member.
+    InvalidAccessFlags {
+        class_idx: ClassId,
+        owner: FlagsOwner,
+        raw: ulong,
+    },
+    /// An index out of the type_ids section, which has `max` items.
+    InvalidTypeIndex {
+        index: TypeId,
+        max: uint,
+    },
+    /// An index out of the string_ids section, which has `max` items.
+    InvalidStringIndex {
+        index: StringId,
+        max: uint,
+    },
+    /// An index out of the field_ids section, which has `max` items.
+    InvalidFieldIndex {
+        index: FieldId,
+        max: uint,
+    },
+    /// An index out of the method_ids section, which has `max` items.
+    InvalidMethodIndex {
+        index: MethodId,
+        max: uint,
+    },
+    /// An index out of the proto_ids section, which has `max` items.
+    InvalidProtoIndex {
+        index: ProtoId,
+        max: uint,
+    },
+    /// An index out of the class_defs section, which has `max` items.
+    InvalidClassDefIndex {
+        index: usize,
+        max: uint,
+    },
+    /// An index out of the method_handles section, which has `max` items, `0` if the
+    /// file has no such section.
+    InvalidMethodHandleIndex {
+        index: MethodHandleId,
+        max: uint,
+    },
+    /// An item, list or section ends at `offset`, past the end of the file of
+    /// `file_size` bytes, i.e. its offset or size is invalid.
+    OutOfBounds {
+        offset: ulong,
+        file_size: usize,
+    },
+    /// An APK without a `classes.dex` entry.
+    MissingDex,
 }
 
 impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
+            #[cfg(feature = "std")]
             Error::IO(_) => "IO error",
             Error::MalFormed(_) => "Entity is malformed in some way",
             Error::Scroll(_) => "Scroll error",
             Error::InvalidId(_) => "Invalid index",
             Error::BadOffset(_, _) => "Invalid offset",
+            Error::InvalidDescriptor(_) => "Invalid type descriptor",
+            Error::UnsupportedFormat(_) => "Unsupported file format",
+            Error::CyclicHierarchy(_) => "Cyclic class hierarchy",
+            Error::InvalidAccessFlags { .. } => "Invalid access flags",
+            Error::InvalidTypeIndex { .. }
+            | Error::InvalidStringIndex { .. }
+            | Error::InvalidFieldIndex { .. }
+            | Error::InvalidMethodIndex { .. }
+            | Error::InvalidProtoIndex { .. }
+            | Error::InvalidClassDefIndex { .. }
+            | Error::InvalidMethodHandleIndex { .. } => "Invalid index",
+            Error::OutOfBounds { .. } => "Invalid offset",
+            Error::MissingDex => "No dex file in the APK",
         }
     }
 
     fn cause(&self) -> Option<&error::Error> {
         match *self {
+            #[cfg(feature = "std")]
             Error::IO(ref io) => io.source(),
+            #[cfg(feature = "std")]
             Error::Scroll(ref err) => err.source(),
+            #[cfg(not(feature = "std"))]
+            Error::Scroll(_) => None,
             Error::MalFormed(_) => None,
             Error::InvalidId(_) => None,
             Error::BadOffset(_, _) => None,
+            Error::InvalidDescriptor(_) => None,
+            Error::UnsupportedFormat(_) => None,
+            Error::CyclicHierarchy(_) => None,
+            Error::InvalidAccessFlags { .. } => None,
+            Error::InvalidTypeIndex { .. }
+            | Error::InvalidStringIndex { .. }
+            | Error::InvalidFieldIndex { .. }
+            | Error::InvalidMethodIndex { .. }
+            | Error::InvalidProtoIndex { .. }
+            | Error::InvalidClassDefIndex { .. }
+            | Error::InvalidMethodHandleIndex { .. } => None,
+            Error::OutOfBounds { .. } => None,
+            Error::MissingDex => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         Error::IO(err)
@@ -49,14 +160,78 @@ impl From<scroll::Error> for Error {
     }
 }
 
+#[cfg(feature = "apk")]
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Error {
+        match err {
+            zip::result::ZipError::Io(err) => Error::IO(err),
+            err => Error::MalFormed(format!("Invalid APK: {}", err)),
+        }
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            #[cfg(feature = "std")]
             Error::IO(ref err) => write!(fmt, "{}", err),
             Error::Scroll(ref err) => write!(fmt, "{}", err),
             Error::MalFormed(ref msg) => write!(fmt, "Malformed entity: {}", msg),
             Error::InvalidId(ref msg) => write!(fmt, "{}", msg),
             Error::BadOffset(offset, ref msg) => write!(fmt, "{}: {}", msg, offset),
+            Error::InvalidDescriptor(ref msg) => write!(fmt, "Invalid descriptor: {}", msg),
+            Error::UnsupportedFormat(ref msg) => write!(fmt, "Unsupported format: {}", msg),
+            Error::CyclicHierarchy(ref class) => {
+                write!(fmt, "Cyclic class hierarchy: {} is its own ancestor", class)
+            }
+            Error::InvalidAccessFlags {
+                class_idx,
+                owner,
+                raw,
+            } => match owner {
+                FlagsOwner::Class => write!(
+                    fmt,
+                    "Invalid access flags {:#x} in class {}",
+                    raw, class_idx
+                ),
+                FlagsOwner::Field(field_id) => write!(
+                    fmt,
+                    "Invalid access flags {:#x} of field {} in class {}",
+                    raw, field_id, class_idx
+                ),
+                FlagsOwner::Method(method_id) => write!(
+                    fmt,
+                    "Invalid access flags {:#x} of method {} in class {}",
+                    raw, method_id, class_idx
+                ),
+            },
+            Error::InvalidTypeIndex { index, max } => {
+                write!(fmt, "Invalid type id: {} (of {})", index, max)
+            }
+            Error::InvalidStringIndex { index, max } => {
+                write!(fmt, "Invalid string id: {} (of {})", index, max)
+            }
+            Error::InvalidFieldIndex { index, max } => {
+                write!(fmt, "Invalid field id: {} (of {})", index, max)
+            }
+            Error::InvalidMethodIndex { index, max } => {
+                write!(fmt, "Invalid method id: {} (of {})", index, max)
+            }
+            Error::InvalidProtoIndex { index, max } => {
+                write!(fmt, "Invalid proto id: {} (of {})", index, max)
+            }
+            Error::InvalidClassDefIndex { index, max } => {
+                write!(fmt, "Invalid class index: {} (of {})", index, max)
+            }
+            Error::InvalidMethodHandleIndex { index, max } => {
+                write!(fmt, "Invalid method handle id: {} (of {})", index, max)
+            }
+            Error::OutOfBounds { offset, file_size } => write!(
+                fmt,
+                "Offset {} out of bounds, file size: {}",
+                offset, file_size
+            ),
+            Error::MissingDex => write!(fmt, "No classes.dex in the APK"),
         }
     }
 }
@@ -1,12 +1,17 @@
 //! Dex `Field` and supporting structures
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
 use scroll::{ctx, Pread, Uleb128};
 
 use crate::{
-    annotation::AnnotationSetItem,
+    annotation::{AnnotationItem, AnnotationSetItem},
     class::ClassId,
+    dex::ParseWarning,
     encoded_item::{EncodedItem, EncodedItemArray},
     encoded_value::EncodedValue,
-    error::Error,
+    error::{Error, FlagsOwner},
+    hiddenapi::HiddenApiFlags,
     jtype::{Type, TypeId},
     string::{DexString, StringId},
     ulong, ushort, utils,
@@ -31,6 +36,9 @@ bitflags! {
 /// Represents the field of a class
 #[derive(Debug, Getters, CopyGetters)]
 pub struct Field {
+    /// Index into the `FieldId`s list for this field.
+    #[get_copy = "pub"]
+    id: FieldId,
     /// Name of the field.
     #[get = "pub"]
     name: DexString,
@@ -49,7 +57,11 @@ pub struct Field {
     initial_value: Option<EncodedValue>,
     /// Annotations of the field.
     #[get = "pub"]
-    annotations: AnnotationSetItem,
+    pub(crate) annotations: AnnotationSetItem,
+    /// Hidden API restriction of the field, `None` if the dex file has no
+    /// `hiddenapi_class_data_item` or no flags for the class of the field.
+    #[get_copy = "pub"]
+    pub(crate) hidden_api_flags: Option<HiddenApiFlags>,
 }
 
 impl Field {
@@ -60,6 +72,19 @@ impl Field {
         self.initial_value.as_ref()
     }
 
+    /// Compile-time constant value of the field. Only `static final` fields have one:
+    /// their initial value in the dex or, without one, the `value` of their
+    /// `dalvik.annotation.ConstantValue` annotation.
+    pub fn constant_value(&self) -> Option<&EncodedValue> {
+        if !(self.is_static() && self.is_final()) {
+            return None;
+        }
+        self.initial_value().or_else(|| {
+            self.annotation("Ldalvik/annotation/ConstantValue;")
+                .and_then(|item| item.element("value"))
+        })
+    }
+
     gen_is_flag_set!(is_public, PUBLIC);
     gen_is_flag_set!(is_private, PRIVATE);
     gen_is_flag_set!(is_protected, PROTECTED);
@@ -70,6 +95,12 @@ impl Field {
     gen_is_flag_set!(is_synthetic, SYNTHETIC);
     gen_is_flag_set!(is_enum, ENUM);
 
+    /// Returns the annotation of the field with the given type descriptor, e.g.
+    /// `Ljava/lang/Deprecated;`, regardless of its visibility.
+    pub fn annotation(&self, type_descriptor: &str) -> Option<&AnnotationItem> {
+        self.annotations.find_annotation(type_descriptor)
+    }
+
     /// Returns the value of `dalvik.annotation.Signature`.
     pub fn signature(&self) -> super::Result<Option<String>> {
         utils::get_signature(self.annotations())
@@ -80,32 +111,86 @@ impl Field {
         encoded_field: &EncodedField,
         initial_value: Option<EncodedValue>,
         annotations: AnnotationSetItem,
+        warnings: &mut Vec<ParseWarning>,
     ) -> super::Result<Self> {
         debug!(target: "field", "encoded field: {:?}", encoded_field);
         let field_item = dex.get_field_item(encoded_field.field_id)?;
         debug!(target: "field", "field id item: {:?}", field_item);
         Ok(Self {
+            id: encoded_field.field_id,
             name: dex.get_string(field_item.name_idx)?,
             jtype: dex.get_type(TypeId::from(field_item.type_idx))?,
             class: ClassId::from(field_item.class_idx),
-            access_flags: AccessFlags::from_bits(encoded_field.access_flags).ok_or_else(|| {
-                Error::InvalidId(format!(
-                    "Invalid access flags when loading field {}",
-                    field_item.name_idx
-                ))
-            })?,
+            access_flags: match AccessFlags::from_bits(encoded_field.access_flags) {
+                Some(access_flags) => access_flags,
+                None if dex.lenient => {
+                    warnings.push(ParseWarning::UnknownFieldAccessFlags {
+                        field_id: encoded_field.field_id,
+                        access_flags: encoded_field.access_flags,
+                    });
+                    AccessFlags::from_bits_truncate(encoded_field.access_flags)
+                }
+                None => {
+                    return Err(Error::InvalidAccessFlags {
+                        class_idx: ClassId::from(field_item.class_idx),
+                        owner: FlagsOwner::Field(encoded_field.field_id),
+                        raw: encoded_field.access_flags,
+                    })
+                }
+            },
             initial_value,
             annotations,
+            hidden_api_flags: None,
+        })
+    }
+}
+
+/// Reference to a field by its `FieldIdItem`, e.g. from an instruction. Unlike a
+/// `Field`, the field doesn't need to be defined in the dex, so there are no access
+/// flags or initial value. Displays as `Lcom/example/Foo;->count:I`.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct FieldRef {
+    /// Index into the `FieldId`s list for this field.
+    #[get_copy = "pub"]
+    id: FieldId,
+    /// Class declaring the field.
+    #[get = "pub"]
+    class: Type,
+    /// Name of the field.
+    #[get = "pub"]
+    name: DexString,
+    /// Type of the field.
+    #[get = "pub"]
+    jtype: Type,
+}
+
+impl FieldRef {
+    pub(crate) fn try_from_dex<S: AsRef<[u8]>>(
+        dex: &super::Dex<S>,
+        field_id: FieldId,
+    ) -> super::Result<Self> {
+        let field_item = dex.get_field_item(field_id)?;
+        Ok(Self {
+            id: field_id,
+            class: dex.get_type(TypeId::from(field_item.class_idx))?,
+            name: dex.get_string(field_item.name_idx)?,
+            jtype: dex.get_type(TypeId::from(field_item.type_idx))?,
         })
     }
 }
 
+impl fmt::Display for FieldRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}->{}:{}", self.class, self.name, self.jtype)
+    }
+}
+
 /// List of `EncodedField`s
 pub type EncodedFieldArray = EncodedItemArray<EncodedField>;
 
 /// Defines a `Field`
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#field-id-item)
-#[derive(Pread, Debug, Getters, PartialEq)]
+#[derive(Pread, Debug, Clone, Getters, PartialEq)]
 #[get = "pub"]
 pub struct FieldIdItem {
     /// Index into `TypeId`s list which contains the defining class's `Type`.
@@ -0,0 +1,125 @@
+//! Hidden API restrictions of the members of platform dex files, read from the
+//! `hiddenapi_class_data_item` section found in dex files of version `039` and later.
+//! [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#hiddenapi-class-data-item)
+use alloc::vec::Vec;
+
+use scroll::{Endian, Pread, Uleb128};
+
+use crate::{error::Error, uint, ulong, Result};
+
+/// Access restriction of a field or method for apps, see `Field::hidden_api_flags`
+/// and `Method::hidden_api_flags`. The `GreylistMax*` members are accessible to the
+/// apps targeting at most the given Android version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HiddenApiFlags {
+    /// Public SDK API, accessible by all apps.
+    Whitelist,
+    /// Non-SDK API accessible by apps.
+    Greylist,
+    /// Non-SDK API which can't be accessed by apps.
+    Blacklist,
+    /// Non-SDK API accessible by apps targeting at most Android 8.x (Oreo).
+    GreylistMaxO,
+    /// Non-SDK API accessible by apps targeting at most Android 9 (Pie).
+    GreylistMaxP,
+    /// Non-SDK API accessible by apps targeting at most Android 10 (Q).
+    GreylistMaxQ,
+    /// Non-SDK API accessible by apps targeting at most Android 11 (R).
+    GreylistMaxR,
+    /// Non-SDK API accessible by apps targeting at most Android 12 (S).
+    GreylistMaxS,
+}
+
+/// Restrictions, in the order of their values in the low 3 bits of the flags of a
+/// member. The higher bits tell the domain of the API, e.g. core platform or test
+/// API, and are ignored.
+const RESTRICTIONS: [HiddenApiFlags; 8] = [
+    HiddenApiFlags::Whitelist,
+    HiddenApiFlags::Greylist,
+    HiddenApiFlags::Blacklist,
+    HiddenApiFlags::GreylistMaxO,
+    HiddenApiFlags::GreylistMaxP,
+    HiddenApiFlags::GreylistMaxQ,
+    HiddenApiFlags::GreylistMaxR,
+    HiddenApiFlags::GreylistMaxS,
+];
+
+/// Reads the flags of the first `count` members of the class defined by the
+/// `class_def_index`th class_def_item, from the `hiddenapi_class_data_item` at
+/// `section_offset`. The members are in the order of the `ClassDataItem`: static
+/// fields, instance fields, direct methods and virtual methods. Returns `None` if
+/// the class has no flags.
+pub(crate) fn class_flags(
+    source: &[u8],
+    section_offset: usize,
+    class_def_index: usize,
+    count: usize,
+    endian: Endian,
+) -> Result<Option<Vec<HiddenApiFlags>>> {
+    let section_size: uint = source.pread_with(section_offset, endian)?;
+    let section_end = section_offset as ulong + ulong::from(section_size);
+    let section = source
+        .get(section_offset..section_end as usize)
+        .ok_or(Error::OutOfBounds {
+            offset: section_end,
+            file_size: source.len(),
+        })?;
+    let flags_offset: uint = section.pread_with(4 + class_def_index * 4, endian)?;
+    if flags_offset == 0 {
+        return Ok(None);
+    }
+    let offset = &mut (flags_offset as usize);
+    let mut flags = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value = Uleb128::read(section, offset)?;
+        flags.push(RESTRICTIONS[(value & 0x7) as usize]);
+    }
+    Ok(Some(flags))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{class_flags, HiddenApiFlags};
+
+    /// Builds a `hiddenapi_class_data_item` for `num_classes` classes, with the flags
+    /// given for some of them.
+    pub(crate) fn section(num_classes: usize, flags: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut offsets = vec![0u32; num_classes];
+        let mut data = Vec::new();
+        let header_size = 4 + 4 * num_classes;
+        for (class_def_index, class_flags) in flags {
+            offsets[*class_def_index] = (header_size + data.len()) as u32;
+            data.extend_from_slice(class_flags);
+        }
+        let mut section = ((header_size + data.len()) as u32).to_le_bytes().to_vec();
+        for offset in offsets {
+            section.extend_from_slice(&offset.to_le_bytes());
+        }
+        section.extend_from_slice(&data);
+        section
+    }
+
+    #[test]
+    fn test_class_flags() {
+        // the domain bits of the last flag are ignored, the third class has no flags.
+        let flags: &[u8] = &[0, 2, 7, 0x80 | 0x19, 0];
+        let mut source = vec![0xff; 6];
+        source.extend(section(3, &[(1, flags), (0, &[1])]));
+        let read = |index, count| class_flags(&source, 6, index, count, scroll::LE);
+        assert_eq!(
+            read(1, 4).expect("error reading flags"),
+            Some(vec![
+                HiddenApiFlags::Whitelist,
+                HiddenApiFlags::Blacklist,
+                HiddenApiFlags::GreylistMaxS,
+                HiddenApiFlags::Greylist,
+            ])
+        );
+        assert_eq!(
+            read(0, 1).expect("error reading flags"),
+            Some(vec![HiddenApiFlags::Greylist])
+        );
+        assert_eq!(read(2, 2).expect("error reading flags"), None);
+        assert!(read(0, 8).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! Compact index of the classes, members and references of a `Dex`, which can be
+//! saved to disk and loaded back without parsing the dex file again.
+//!
+//! The binary format is versioned and stable: the last byte of `INDEX_MAGIC` is the
+//! version of the format, which is incremented on any change to the layout, and an
+//! index is only read back by a version of the crate writing the same format. An
+//! index of version 1 is made of:
+//!
+//! - `INDEX_MAGIC`, `dexidx` followed by the version byte `1`.
+//! - The method signatures, then the field signatures, each a list of strings.
+//! - The classes: for each class, its descriptor, its access flags, `0` or `1`
+//!   followed by the super class descriptor, the list of interface descriptors and
+//!   the lists of ids of its methods and fields.
+//! - The calls, then the field accesses: for each method with code, its id followed by
+//!   the list of ids of the methods called or the fields accessed.
+//!
+//! Integers are ULEB128 encoded. Strings are UTF-8 prefixed by their length in bytes
+//! and lists by their number of elements. Ids are indices into the method and field
+//! signatures.
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use getset::{CopyGetters, Getters};
+use scroll::Uleb128;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    callgraph::CallGraph,
+    error::Error,
+    field::FieldId,
+    instruction::Instruction,
+    method::{MethodId, ProtoId},
+    ubyte, uint, ulong, Result,
+};
+
+/// Magic and version of the binary format of a `DexIndex`, see the module
+/// documentation.
+pub const INDEX_MAGIC: &[ubyte] = b"dexidx\x01";
+
+/// Signatures of the classes, methods and fields of a dex file along with the
+/// methods and fields referenced by each method which has code.
+#[derive(Debug, Default, PartialEq, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[get = "pub"]
+pub struct DexIndex {
+    /// Signature of each `MethodId`, e.g. `Lcom/example/Foo;->bar(I)V`.
+    methods: Vec<String>,
+    /// Signature of each `FieldId`, e.g. `Lcom/example/Foo;->count:I`.
+    fields: Vec<String>,
+    /// Classes defined in the dex file, in the order of the class_defs section.
+    classes: Vec<ClassIndex>,
+    /// Methods invoked by each method which has code.
+    calls: BTreeMap<MethodId, BTreeSet<MethodId>>,
+    /// Fields read or written by each method which has code.
+    field_accesses: BTreeMap<MethodId, BTreeSet<FieldId>>,
+}
+
+/// A class of a `DexIndex`.
+#[derive(Debug, PartialEq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClassIndex {
+    /// Descriptor of the class.
+    #[get = "pub"]
+    descriptor: String,
+    /// Access flags of the class, as `class::AccessFlags` bits.
+    #[get_copy = "pub"]
+    access_flags: uint,
+    /// Descriptor of the super class, if there is one.
+    #[get = "pub"]
+    super_class: Option<String>,
+    /// Descriptors of the interfaces implemented by the class.
+    #[get = "pub"]
+    interfaces: Vec<String>,
+    /// Methods defined in the class, as indices into `DexIndex::methods`.
+    #[get = "pub"]
+    methods: Vec<MethodId>,
+    /// Fields defined in the class, as indices into `DexIndex::fields`.
+    #[get = "pub"]
+    fields: Vec<FieldId>,
+}
+
+impl DexIndex {
+    pub(crate) fn try_from_dex<S: AsRef<[u8]>>(dex: &super::Dex<S>) -> Result<Self> {
+        let mut index = Self::default();
+        for method_item in dex.method_ids() {
+            let method_item = method_item?;
+            let proto_item = dex.get_proto_item(ProtoId::from(method_item.proto_idx()))?;
+            let params = dex
+                .get_type_list(proto_item.params_off())?
+                .iter()
+                .map(|param| param.to_string())
+                .collect::<String>();
+            index.methods.push(format!(
+                "{}->{}({}){}",
+                dex.get_type(method_item.class_idx().into())?,
+                dex.get_string(method_item.name_idx())?,
+                params,
+                dex.get_type(proto_item.return_type())?
+            ));
+        }
+        for field_item in dex.field_ids() {
+            let field_item = field_item?;
+            index.fields.push(format!(
+                "{}->{}:{}",
+                dex.get_type((*field_item.class_idx()).into())?,
+                dex.get_string(*field_item.name_idx())?,
+                dex.get_type((*field_item.type_idx()).into())?
+            ));
+        }
+        for class in dex.classes() {
+            let class = class?;
+            index.classes.push(ClassIndex {
+                descriptor: class.jtype().to_string(),
+                access_flags: class.access_flags().bits(),
+                super_class: class.super_type().as_ref().map(ToString::to_string),
+                interfaces: class.interfaces().iter().map(|i| i.to_string()).collect(),
+                methods: class.methods().map(|method| method.id()).collect(),
+                fields: class.fields().map(|field| field.id()).collect(),
+            });
+            for method in class.methods() {
+                let code = match method.code() {
+                    Some(code) => code,
+                    None => continue,
+                };
+                let (mut calls, mut field_accesses) = (BTreeSet::new(), BTreeSet::new());
+                let (max_method, max_field) =
+                    (index.methods.len() as ulong, index.fields.len() as ulong);
+                for instruction in code.instructions() {
+                    match instruction?.1 {
+                        Instruction::Invoke { method_idx, .. }
+                        | Instruction::InvokePolymorphic { method_idx, .. } => {
+                            if method_idx >= max_method {
+                                return Err(Error::InvalidMethodIndex {
+                                    index: method_idx,
+                                    max: max_method as uint,
+                                });
+                            }
+                            calls.insert(method_idx);
+                        }
+                        Instruction::InstanceGet { field_idx, .. }
+                        | Instruction::InstancePut { field_idx, .. }
+                        | Instruction::StaticGet { field_idx, .. }
+                        | Instruction::StaticPut { field_idx, .. } => {
+                            if field_idx >= max_field {
+                                return Err(Error::InvalidFieldIndex {
+                                    index: field_idx,
+                                    max: max_field as uint,
+                                });
+                            }
+                            field_accesses.insert(field_idx);
+                        }
+                        _ => {}
+                    }
+                }
+                index.calls.insert(method.id(), calls);
+                index.field_accesses.insert(method.id(), field_accesses);
+            }
+        }
+        Ok(index)
+    }
+
+    /// Returns the call graph of the methods of the index, see `Dex::call_graph`. The
+    /// graph of an imported index is built without the dex file.
+    pub fn call_graph(&self) -> CallGraph {
+        CallGraph::from_index(self)
+    }
+
+    /// Writes the index to `writer` in the binary format.
+    #[cfg(feature = "std")]
+    pub fn export<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the index in the binary format, see `export`.
+    pub fn to_bytes(&self) -> Vec<ubyte> {
+        let mut out = Vec::from(INDEX_MAGIC);
+        write_strings(&mut out, &self.methods);
+        write_strings(&mut out, &self.fields);
+        write_uleb(&mut out, self.classes.len() as ulong);
+        for class in &self.classes {
+            write_string(&mut out, &class.descriptor);
+            write_uleb(&mut out, ulong::from(class.access_flags));
+            match &class.super_class {
+                Some(super_class) => {
+                    write_uleb(&mut out, 1);
+                    write_string(&mut out, super_class);
+                }
+                None => write_uleb(&mut out, 0),
+            }
+            write_strings(&mut out, &class.interfaces);
+            write_ids(&mut out, &class.methods);
+            write_ids(&mut out, &class.fields);
+        }
+        for references in &[&self.calls, &self.field_accesses] {
+            write_uleb(&mut out, references.len() as ulong);
+            for (method_id, ids) in references.iter() {
+                write_uleb(&mut out, *method_id);
+                write_ids(&mut out, ids);
+            }
+        }
+        out
+    }
+
+    /// Reads an index written by `export`.
+    #[cfg(feature = "std")]
+    pub fn import<R: Read>(mut reader: R) -> Result<Self> {
+        let mut source = Vec::new();
+        reader.read_to_end(&mut source)?;
+        Self::from_bytes(&source)
+    }
+
+    /// Reads an index returned by `to_bytes`.
+    pub fn from_bytes(source: &[ubyte]) -> Result<Self> {
+        if !source.starts_with(INDEX_MAGIC) {
+            return Err(Error::MalFormed("Invalid dex index magic".to_string()));
+        }
+        let mut parser = IndexParser {
+            source,
+            offset: INDEX_MAGIC.len(),
+        };
+        let methods = parser.strings()?;
+        let fields = parser.strings()?;
+        let (max_method, max_field) = (methods.len() as ulong, fields.len() as ulong);
+        let mut classes = Vec::new();
+        for _ in 0..parser.uleb()? {
+            let descriptor = parser.string()?;
+            let access_flags = parser.uleb()? as uint;
+            let super_class = match parser.uleb()? {
+                0 => None,
+                _ => Some(parser.string()?),
+            };
+            classes.push(ClassIndex {
+                descriptor,
+                access_flags,
+                super_class,
+                interfaces: parser.strings()?,
+                methods: parser.ids(max_method)?.collect(),
+                fields: parser.ids(max_field)?.collect(),
+            });
+        }
+        let mut references = |max_id| -> Result<BTreeMap<_, BTreeSet<_>>> {
+            let mut references = BTreeMap::new();
+            for _ in 0..parser.uleb()? {
+                let method_id = parser.id(max_method)?;
+                references.insert(method_id, parser.ids(max_id)?.collect());
+            }
+            Ok(references)
+        };
+        let calls = references(max_method)?;
+        let field_accesses = references(max_field)?;
+        if parser.offset != source.len() {
+            return Err(Error::MalFormed(format!(
+                "Trailing data in dex index at offset: {}",
+                parser.offset
+            )));
+        }
+        Ok(Self {
+            methods,
+            fields,
+            classes,
+            calls,
+            field_accesses,
+        })
+    }
+}
+
+fn write_uleb(out: &mut Vec<ubyte>, mut value: ulong) {
+    loop {
+        let byte = (value & 0x7f) as ubyte;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_string(out: &mut Vec<ubyte>, string: &str) {
+    write_uleb(out, string.len() as ulong);
+    out.extend_from_slice(string.as_bytes());
+}
+
+fn write_strings(out: &mut Vec<ubyte>, strings: &[String]) {
+    write_uleb(out, strings.len() as ulong);
+    for string in strings {
+        write_string(out, string);
+    }
+}
+
+fn write_ids<'a, I: IntoIterator<Item = &'a ulong>>(out: &mut Vec<ubyte>, ids: I)
+where
+    I::IntoIter: ExactSizeIterator,
+{
+    let ids = ids.into_iter();
+    write_uleb(out, ids.len() as ulong);
+    for id in ids {
+        write_uleb(out, *id);
+    }
+}
+
+struct IndexParser<'a> {
+    source: &'a [ubyte],
+    offset: usize,
+}
+
+impl IndexParser<'_> {
+    fn uleb(&mut self) -> Result<ulong> {
+        Ok(Uleb128::read(self.source, &mut self.offset)?)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.uleb()? as usize;
+        let bytes = self
+            .source
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or_else(|| Error::BadOffset(self.offset, "string past end of index".to_string()))?;
+        self.offset += len;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| Error::MalFormed(format!("Malformed string in dex index: {}", e)))
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>> {
+        (0..self.uleb()?).map(|_| self.string()).collect()
+    }
+
+    /// Reads an index into a table of `max_id` entries.
+    fn id(&mut self, max_id: ulong) -> Result<ulong> {
+        let id = self.uleb()?;
+        if id >= max_id {
+            return Err(Error::InvalidId(format!("Invalid id in dex index: {}", id)));
+        }
+        Ok(id)
+    }
+
+    fn ids(&mut self, max_id: ulong) -> Result<impl Iterator<Item = ulong>> {
+        let ids = (0..self.uleb()?)
+            .map(|_| self.id(max_id))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ids.into_iter())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{DexIndex, INDEX_MAGIC};
+    use crate::{Dex, DexReader};
+
+    #[test]
+    fn test_export_import() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let index = dex.index().expect("error indexing dex");
+        assert_eq!(index.methods().len(), dex.method_ids().count());
+        assert_eq!(index.fields().len(), dex.field_ids().count());
+        assert_eq!(index.classes().len(), dex.classes().count());
+
+        let class = index
+            .classes()
+            .iter()
+            .find(|c| c.descriptor() == "Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("class not found");
+        assert_eq!(
+            class.super_class().as_deref(),
+            Some("Landroid/widget/LinearLayout;")
+        );
+        let set_text = *class
+            .methods()
+            .iter()
+            .find(|m| {
+                index.methods()[**m as usize]
+                    == "Lcom/devoteam/quickaction/QuickActionItem;->setText(Ljava/lang/String;)V"
+            })
+            .expect("method not found");
+        let calls = &index.calls()[&set_text];
+        assert!(calls.iter().any(
+            |m| index.methods()[*m as usize].starts_with("Landroid/widget/TextView;->setText(")
+        ));
+        let graph = dex.call_graph().expect("cannot build call graph");
+        for (method_id, callees) in index.calls() {
+            assert_eq!(graph.callees(*method_id), Some(callees));
+        }
+        assert_eq!(
+            graph.calls().len(),
+            index
+                .classes()
+                .iter()
+                .map(|c| c.methods().len())
+                .sum::<usize>()
+        );
+        assert!(index.field_accesses().keys().eq(index.calls().keys()));
+        assert!(index
+            .field_accesses()
+            .values()
+            .any(|fields| !fields.is_empty()));
+
+        let mut bytes = Vec::new();
+        dex.export_index(&mut bytes).expect("error exporting index");
+        assert!(bytes.starts_with(INDEX_MAGIC));
+        let imported = Dex::import_index(&bytes[..]).expect("error importing index");
+        assert_eq!(imported, index);
+        assert_eq!(imported.call_graph().to_dot(), graph.to_dot());
+
+        assert!(DexIndex::import(&bytes[..bytes.len() - 1]).is_err());
+        bytes.push(0);
+        assert!(DexIndex::import(&bytes[..]).is_err());
+        assert!(DexIndex::import(&b"dexidx\x02"[..]).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Network indicators found in the strings of a `Dex`.
+use std::{
+    collections::BTreeSet,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use getset::Getters;
+use regex::Regex;
+
+/// Default pattern for URLs.
+pub const URL_PATTERN: &str = r#"(?i)\b(?:https?|ftp|wss?)://[^\s"'<>`]+"#;
+/// Default pattern for domain names. Only a set of common top level domains is
+/// matched so that Java package and class names are not reported.
+pub const DOMAIN_PATTERN: &str = r"(?i)\b(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+(?:com|net|org|info|biz|io|co|me|xyz|top|online|site|club|ru|su|cn|de|uk|fr|br|in|jp|kr|ir|tk|pw|cc|ws)\b";
+/// Default pattern for IPv4 addresses.
+pub const IPV4_PATTERN: &str = r"\b(?:\d{1,3}\.){3}\d{1,3}\b";
+/// Default pattern for IPv6 addresses.
+pub const IPV6_PATTERN: &str = r"(?i)[0-9a-f.]*:[0-9a-f:.]*:[0-9a-f.]*";
+
+/// Patterns used to find network indicators. Every pattern can be replaced, the
+/// defaults are the `*_PATTERN` constants. Matches of the IP address patterns are
+/// only reported if they parse as an address.
+#[derive(Debug, Clone)]
+pub struct IndicatorPatterns {
+    url: Regex,
+    domain: Regex,
+    ipv4: Regex,
+    ipv6: Regex,
+}
+
+impl IndicatorPatterns {
+    /// Replaces the pattern used for URLs.
+    pub fn url(mut self, pattern: Regex) -> Self {
+        self.url = pattern;
+        self
+    }
+
+    /// Replaces the pattern used for domain names.
+    pub fn domain(mut self, pattern: Regex) -> Self {
+        self.domain = pattern;
+        self
+    }
+
+    /// Replaces the pattern used for IPv4 addresses.
+    pub fn ipv4(mut self, pattern: Regex) -> Self {
+        self.ipv4 = pattern;
+        self
+    }
+
+    /// Replaces the pattern used for IPv6 addresses.
+    pub fn ipv6(mut self, pattern: Regex) -> Self {
+        self.ipv6 = pattern;
+        self
+    }
+}
+
+impl Default for IndicatorPatterns {
+    fn default() -> Self {
+        Self {
+            url: Regex::new(URL_PATTERN).expect("invalid url pattern"),
+            domain: Regex::new(DOMAIN_PATTERN).expect("invalid domain pattern"),
+            ipv4: Regex::new(IPV4_PATTERN).expect("invalid ipv4 pattern"),
+            ipv6: Regex::new(IPV6_PATTERN).expect("invalid ipv6 pattern"),
+        }
+    }
+}
+
+/// URLs, domain names and IP addresses found in strings, without duplicates.
+#[derive(Debug, Default, Getters)]
+#[get = "pub"]
+pub struct NetworkIndicators {
+    /// URLs.
+    urls: BTreeSet<String>,
+    /// Domain names, including the hosts of the URLs.
+    domains: BTreeSet<String>,
+    /// IPv4 addresses.
+    ipv4_addresses: BTreeSet<Ipv4Addr>,
+    /// IPv6 addresses.
+    ipv6_addresses: BTreeSet<Ipv6Addr>,
+}
+
+impl NetworkIndicators {
+    /// Returns `true` if no indicator was found.
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+            && self.domains.is_empty()
+            && self.ipv4_addresses.is_empty()
+            && self.ipv6_addresses.is_empty()
+    }
+
+    /// Adds the indicators found in `string`.
+    pub(crate) fn scan(&mut self, string: &str, patterns: &IndicatorPatterns) {
+        for url in patterns.url.find_iter(string) {
+            self.urls.insert(url.as_str().to_string());
+        }
+        for domain in patterns.domain.find_iter(string) {
+            self.domains.insert(domain.as_str().to_lowercase());
+        }
+        for address in patterns.ipv4.find_iter(string) {
+            if let Ok(address) = address.as_str().parse() {
+                self.ipv4_addresses.insert(address);
+            }
+        }
+        for address in patterns.ipv6.find_iter(string) {
+            match address.as_str().trim_matches('.').parse::<Ipv6Addr>() {
+                Ok(address) if !address.is_unspecified() => {
+                    self.ipv6_addresses.insert(address);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndicatorPatterns, NetworkIndicators};
+
+    #[test]
+    fn test_scan() {
+        let patterns = IndicatorPatterns::default();
+        let mut indicators = NetworkIndicators::default();
+        for string in &[
+            "POST https://c2.example.com/gate.php?id=1 failed",
+            "connecting to 10.0.2.2:8080",
+            "version 1.2.3.400, not an address",
+            "fallback [2001:db8::ff00:42:8329]",
+            "::1",
+            "org.adw.launcher.Launcher",
+            "Ljava/lang/String;",
+            "12:30:45",
+        ] {
+            indicators.scan(string, &patterns);
+        }
+        assert_eq!(
+            indicators.urls().iter().collect::<Vec<_>>(),
+            vec!["https://c2.example.com/gate.php?id=1"]
+        );
+        assert_eq!(
+            indicators.domains().iter().collect::<Vec<_>>(),
+            vec!["c2.example.com"]
+        );
+        assert_eq!(
+            indicators.ipv4_addresses().iter().collect::<Vec<_>>(),
+            vec![&"10.0.2.2".parse::<std::net::Ipv4Addr>().unwrap()]
+        );
+        assert_eq!(
+            indicators.ipv6_addresses().iter().collect::<Vec<_>>(),
+            vec![
+                &"::1".parse::<std::net::Ipv6Addr>().unwrap(),
+                &"2001:db8::ff00:42:8329".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        let patterns =
+            IndicatorPatterns::default().domain(regex::Regex::new(r"\b[a-z]+\.onion\b").unwrap());
+        let mut indicators = NetworkIndicators::default();
+        indicators.scan("http://abcdef.onion/ or example.com", &patterns);
+        assert_eq!(
+            indicators.domains().iter().collect::<Vec<_>>(),
+            vec!["abcdef.onion"]
+        );
+        assert!(!indicators.is_empty());
+    }
+}
@@ -0,0 +1,1394 @@
+//! Typed decoding of the Dalvik instructions of a `Method`'s code.
+//!
+//! Unlike the disassembler, which exposes the raw operands of an instruction, the
+//! decoder reads the operands according to the
+//! [instruction format](https://source.android.com/devices/tech/dalvik/instruction-formats)
+//! of each opcode and reports unknown opcodes as errors. The indices of the operands
+//! can be resolved in the dex with `Instruction::resolve`.
+use alloc::vec::Vec;
+
+use crate::{
+    error::Error,
+    field::{FieldId, FieldRef},
+    int,
+    jtype::{Type, TypeId},
+    long,
+    method::{MethodHandleId, MethodHandleItem, MethodId, MethodRef, ProtoId, Prototype},
+    string::{DexString, StringId},
+    uint, ushort, Dex, Result,
+};
+
+/// Kind of value moved, returned or accessed by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// A 32-bit value, `int` or `float`.
+    Single,
+    /// A 64-bit value held in a register pair, `long` or `double`.
+    Wide,
+    /// An object reference.
+    Object,
+    Boolean,
+    Byte,
+    Char,
+    Short,
+}
+
+/// Kinds of the array, instance field and static field accessors, in opcode order.
+const ACCESS_KINDS: [ValueKind; 7] = [
+    ValueKind::Single,
+    ValueKind::Wide,
+    ValueKind::Object,
+    ValueKind::Boolean,
+    ValueKind::Byte,
+    ValueKind::Char,
+    ValueKind::Short,
+];
+
+/// Comparison of `cmp-kind` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpKind {
+    /// `cmpl-float`, `-1` if either operand is NaN.
+    LessFloat,
+    /// `cmpg-float`, `1` if either operand is NaN.
+    GreaterFloat,
+    /// `cmpl-double`, `-1` if either operand is NaN.
+    LessDouble,
+    /// `cmpg-double`, `1` if either operand is NaN.
+    GreaterDouble,
+    /// `cmp-long`.
+    Long,
+}
+
+/// Condition of the `if-test` and `if-testz` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfCond {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+const IF_CONDS: [IfCond; 6] = [
+    IfCond::Eq,
+    IfCond::Ne,
+    IfCond::Lt,
+    IfCond::Ge,
+    IfCond::Gt,
+    IfCond::Le,
+];
+
+/// Kind of method invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvokeKind {
+    Virtual,
+    Super,
+    Direct,
+    Static,
+    Interface,
+}
+
+const INVOKE_KINDS: [InvokeKind; 5] = [
+    InvokeKind::Virtual,
+    InvokeKind::Super,
+    InvokeKind::Direct,
+    InvokeKind::Static,
+    InvokeKind::Interface,
+];
+
+/// Operation of the `unop` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    NegInt,
+    NotInt,
+    NegLong,
+    NotLong,
+    NegFloat,
+    NegDouble,
+    IntToLong,
+    IntToFloat,
+    IntToDouble,
+    LongToInt,
+    LongToFloat,
+    LongToDouble,
+    FloatToInt,
+    FloatToLong,
+    FloatToDouble,
+    DoubleToInt,
+    DoubleToLong,
+    DoubleToFloat,
+    IntToByte,
+    IntToChar,
+    IntToShort,
+}
+
+const UNARY_OPS: [UnaryOp; 21] = [
+    UnaryOp::NegInt,
+    UnaryOp::NotInt,
+    UnaryOp::NegLong,
+    UnaryOp::NotLong,
+    UnaryOp::NegFloat,
+    UnaryOp::NegDouble,
+    UnaryOp::IntToLong,
+    UnaryOp::IntToFloat,
+    UnaryOp::IntToDouble,
+    UnaryOp::LongToInt,
+    UnaryOp::LongToFloat,
+    UnaryOp::LongToDouble,
+    UnaryOp::FloatToInt,
+    UnaryOp::FloatToLong,
+    UnaryOp::FloatToDouble,
+    UnaryOp::DoubleToInt,
+    UnaryOp::DoubleToLong,
+    UnaryOp::DoubleToFloat,
+    UnaryOp::IntToByte,
+    UnaryOp::IntToChar,
+    UnaryOp::IntToShort,
+];
+
+/// Operation of the `binop`, `binop/2addr` and `binop/lit` instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    Ushr,
+    /// Reverse subtraction, `literal - src`. Only used with literals.
+    Rsub,
+}
+
+const INT_OPS: [BinaryOp; 11] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Div,
+    BinaryOp::Rem,
+    BinaryOp::And,
+    BinaryOp::Or,
+    BinaryOp::Xor,
+    BinaryOp::Shl,
+    BinaryOp::Shr,
+    BinaryOp::Ushr,
+];
+
+/// Type of the operands of a `binop` instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericType {
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+/// A decoded Dalvik instruction. Registers are given by their number, branch offsets
+/// are relative to the address of the instruction, in 16-bit code units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Nop,
+    Move {
+        kind: ValueKind,
+        dst: ushort,
+        src: ushort,
+    },
+    MoveResult {
+        kind: ValueKind,
+        dst: ushort,
+    },
+    MoveException {
+        dst: ushort,
+    },
+    ReturnVoid,
+    Return {
+        kind: ValueKind,
+        src: ushort,
+    },
+    /// `const/4`, `const/16`, `const` and `const/high16`, with the sign-extended or
+    /// shifted value.
+    Const {
+        dst: ushort,
+        value: int,
+    },
+    /// `const-wide` instructions, with the sign-extended or shifted value.
+    ConstWide {
+        dst: ushort,
+        value: long,
+    },
+    /// `const-string` and `const-string/jumbo`.
+    ConstString {
+        reg: ushort,
+        string_idx: StringId,
+    },
+    ConstClass {
+        reg: ushort,
+        type_idx: TypeId,
+    },
+    MonitorEnter {
+        reg: ushort,
+    },
+    MonitorExit {
+        reg: ushort,
+    },
+    CheckCast {
+        reg: ushort,
+        type_idx: TypeId,
+    },
+    InstanceOf {
+        dst: ushort,
+        src: ushort,
+        type_idx: TypeId,
+    },
+    ArrayLength {
+        dst: ushort,
+        array: ushort,
+    },
+    NewInstance {
+        dst: ushort,
+        type_idx: TypeId,
+    },
+    NewArray {
+        dst: ushort,
+        size: ushort,
+        type_idx: TypeId,
+    },
+    /// `filled-new-array` and `filled-new-array/range`.
+    FilledNewArray {
+        type_idx: TypeId,
+        args: Vec<ushort>,
+    },
+    FillArrayData {
+        array: ushort,
+        payload_offset: int,
+    },
+    Throw {
+        reg: ushort,
+    },
+    /// `goto`, `goto/16` and `goto/32`.
+    Goto {
+        offset: int,
+    },
+    PackedSwitch {
+        reg: ushort,
+        payload_offset: int,
+    },
+    SparseSwitch {
+        reg: ushort,
+        payload_offset: int,
+    },
+    Cmp {
+        kind: CmpKind,
+        dst: ushort,
+        a: ushort,
+        b: ushort,
+    },
+    If {
+        cond: IfCond,
+        a: ushort,
+        b: ushort,
+        offset: int,
+    },
+    IfZero {
+        cond: IfCond,
+        reg: ushort,
+        offset: int,
+    },
+    ArrayGet {
+        kind: ValueKind,
+        dst: ushort,
+        array: ushort,
+        index: ushort,
+    },
+    ArrayPut {
+        kind: ValueKind,
+        src: ushort,
+        array: ushort,
+        index: ushort,
+    },
+    InstanceGet {
+        kind: ValueKind,
+        dst: ushort,
+        object: ushort,
+        field_idx: FieldId,
+    },
+    InstancePut {
+        kind: ValueKind,
+        src: ushort,
+        object: ushort,
+        field_idx: FieldId,
+    },
+    StaticGet {
+        kind: ValueKind,
+        dst: ushort,
+        field_idx: FieldId,
+    },
+    StaticPut {
+        kind: ValueKind,
+        src: ushort,
+        field_idx: FieldId,
+    },
+    /// `invoke-kind` and `invoke-kind/range`.
+    Invoke {
+        kind: InvokeKind,
+        method_idx: MethodId,
+        args: Vec<ushort>,
+    },
+    /// `invoke-polymorphic` and `invoke-polymorphic/range`.
+    InvokePolymorphic {
+        method_idx: MethodId,
+        proto_idx: ProtoId,
+        args: Vec<ushort>,
+    },
+    /// `invoke-custom` and `invoke-custom/range`.
+    InvokeCustom {
+        call_site_idx: uint,
+        args: Vec<ushort>,
+    },
+    Unary {
+        op: UnaryOp,
+        dst: ushort,
+        src: ushort,
+    },
+    /// `binop` and `binop/2addr`. For the latter, `dst` is also the first operand.
+    Binary {
+        op: BinaryOp,
+        ty: NumericType,
+        dst: ushort,
+        a: ushort,
+        b: ushort,
+    },
+    /// `binop/lit16` and `binop/lit8`, always on ints.
+    BinaryLit {
+        op: BinaryOp,
+        dst: ushort,
+        src: ushort,
+        literal: int,
+    },
+    ConstMethodHandle {
+        reg: ushort,
+        method_handle_idx: MethodHandleId,
+    },
+    ConstMethodType {
+        reg: ushort,
+        proto_idx: ProtoId,
+    },
+    PackedSwitchPayload {
+        first_key: int,
+        targets: Vec<int>,
+    },
+    SparseSwitchPayload {
+        keys: Vec<int>,
+        targets: Vec<int>,
+    },
+    FillArrayDataPayload {
+        element_width: ushort,
+        size: uint,
+        data: Vec<u8>,
+    },
+}
+
+impl Instruction {
+    /// Resolves the index operand of the instruction, e.g. the string of a
+    /// `const-string` or the method of an `invoke-virtual`, in `dex`. The call sites
+    /// of `invoke-custom` are not resolved.
+    pub fn resolve<S: AsRef<[u8]>>(&self, dex: &Dex<S>) -> Result<ResolvedInstruction<'_>> {
+        let reference = match self {
+            Instruction::ConstString { string_idx, .. } => {
+                Some(Reference::String(dex.get_string(*string_idx)?))
+            }
+            Instruction::ConstClass { type_idx, .. }
+            | Instruction::CheckCast { type_idx, .. }
+            | Instruction::InstanceOf { type_idx, .. }
+            | Instruction::NewInstance { type_idx, .. }
+            | Instruction::NewArray { type_idx, .. }
+            | Instruction::FilledNewArray { type_idx, .. } => {
+                Some(Reference::Type(dex.get_type(*type_idx)?))
+            }
+            Instruction::InstanceGet { field_idx, .. }
+            | Instruction::InstancePut { field_idx, .. }
+            | Instruction::StaticGet { field_idx, .. }
+            | Instruction::StaticPut { field_idx, .. } => {
+                Some(Reference::Field(dex.resolve_field(*field_idx)?))
+            }
+            Instruction::Invoke { method_idx, .. } => {
+                Some(Reference::Method(dex.resolve_method(*method_idx)?))
+            }
+            Instruction::InvokePolymorphic {
+                method_idx,
+                proto_idx,
+                ..
+            } => Some(Reference::PolymorphicMethod(
+                dex.resolve_method(*method_idx)?,
+                dex.get_prototype(*proto_idx)?,
+            )),
+            Instruction::ConstMethodHandle {
+                method_handle_idx, ..
+            } => Some(Reference::MethodHandle(
+                dex.get_method_handle_item(*method_handle_idx)?,
+            )),
+            Instruction::ConstMethodType { proto_idx, .. } => {
+                Some(Reference::Prototype(dex.get_prototype(*proto_idx)?))
+            }
+            _ => None,
+        };
+        Ok(ResolvedInstruction {
+            instruction: self,
+            reference,
+        })
+    }
+}
+
+/// An item of the dex referenced by the index operand of an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reference {
+    String(DexString),
+    Type(Type),
+    Field(FieldRef),
+    Method(MethodRef),
+    /// Method of an `invoke-polymorphic`, along with the prototype of the call.
+    PolymorphicMethod(MethodRef, Prototype),
+    MethodHandle(MethodHandleItem),
+    Prototype(Prototype),
+}
+
+/// An instruction along with the item of the dex its index operand refers to, see
+/// `Instruction::resolve`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedInstruction<'a> {
+    instruction: &'a Instruction,
+    reference: Option<Reference>,
+}
+
+impl<'a> ResolvedInstruction<'a> {
+    /// The decoded instruction.
+    pub fn instruction(&self) -> &'a Instruction {
+        self.instruction
+    }
+
+    /// The resolved item, `None` if the instruction has no index operand.
+    pub fn reference(&self) -> Option<&Reference> {
+        self.reference.as_ref()
+    }
+}
+
+/// Reads the code units of the instruction at `addr`.
+struct Units<'a> {
+    insns: &'a [ushort],
+    addr: usize,
+}
+
+impl Units<'_> {
+    fn get(&self, index: usize) -> Result<ushort> {
+        self.insns
+            .get(self.addr + index)
+            .cloned()
+            .ok_or_else(|| Error::MalFormed(format!("Instruction at {} out of bounds", self.addr)))
+    }
+
+    fn int(&self, index: usize) -> Result<int> {
+        Ok((uint::from(self.get(index)?) | uint::from(self.get(index + 1)?) << 16) as int)
+    }
+
+    /// Registers of the 35c and 45cc formats.
+    fn args(&self) -> Result<Vec<ushort>> {
+        let first = self.get(0)?;
+        let count = (first >> 12) as usize;
+        let regs = self.get(2)?;
+        let regs = [
+            regs & 0xf,
+            (regs >> 4) & 0xf,
+            (regs >> 8) & 0xf,
+            regs >> 12,
+            (first >> 8) & 0xf,
+        ];
+        if count > regs.len() {
+            return Err(Error::MalFormed(format!(
+                "Invalid argument count {} of instruction at {}",
+                count, self.addr
+            )));
+        }
+        Ok(regs[..count].to_vec())
+    }
+
+    /// Registers of the 3rc and 4rcc formats.
+    fn range(&self) -> Result<Vec<ushort>> {
+        let count = uint::from(self.get(0)? >> 8);
+        let first = uint::from(self.get(2)?);
+        (first..first + count)
+            .map(|reg| {
+                if reg > uint::from(ushort::MAX) {
+                    return Err(Error::MalFormed(format!(
+                        "Register range of instruction at {} out of bounds",
+                        self.addr
+                    )));
+                }
+                Ok(reg as ushort)
+            })
+            .collect()
+    }
+
+    fn payload_ints(&self, start: usize, len: usize) -> Result<Vec<int>> {
+        (0..len).map(|i| self.int(start + i * 2)).collect()
+    }
+}
+
+/// Decodes the instruction at `addr`, returning it along with its size in code units.
+pub(crate) fn decode(insns: &[ushort], addr: usize) -> Result<(Instruction, usize)> {
+    use Instruction::*;
+
+    let units = Units { insns, addr };
+    let first = units.get(0)?;
+    let op = first & 0xff;
+    let aa = first >> 8;
+    let (a, b) = ((first >> 8) & 0xf, first >> 12);
+    let bb_cc = || -> Result<(ushort, ushort)> {
+        let unit = units.get(1)?;
+        Ok((unit & 0xff, unit >> 8))
+    };
+    let offset16 = || -> Result<int> { Ok(int::from(units.get(1)? as i16)) };
+    let index = |at: usize| -> Result<ushort> { units.get(at) };
+    let decoded = match op {
+        0x00 => match first {
+            0x0000 => (Nop, 1),
+            0x0100 => {
+                let size = usize::from(units.get(1)?);
+                let targets = units.payload_ints(4, size)?;
+                (
+                    PackedSwitchPayload {
+                        first_key: units.int(2)?,
+                        targets,
+                    },
+                    4 + size * 2,
+                )
+            }
+            0x0200 => {
+                let size = usize::from(units.get(1)?);
+                let keys = units.payload_ints(2, size)?;
+                let targets = units.payload_ints(2 + size * 2, size)?;
+                (SparseSwitchPayload { keys, targets }, 2 + size * 4)
+            }
+            0x0300 => {
+                let element_width = units.get(1)?;
+                let size = units.int(2)? as uint;
+                let len = usize::from(element_width) * size as usize;
+                let data_units = len.div_ceil(2);
+                let data = (0..data_units)
+                    .map(|i| units.get(4 + i).map(|unit| unit.to_le_bytes()))
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .flatten()
+                    .take(len)
+                    .collect();
+                (
+                    FillArrayDataPayload {
+                        element_width,
+                        size,
+                        data,
+                    },
+                    4 + data_units,
+                )
+            }
+            ident => {
+                return Err(Error::MalFormed(format!(
+                    "Unknown payload ident {:#x} at {}",
+                    ident, addr
+                )))
+            }
+        },
+        0x01 | 0x04 | 0x07 => {
+            let kind =
+                [ValueKind::Single, ValueKind::Wide, ValueKind::Object][(op - 0x01) as usize / 3];
+            (
+                Move {
+                    kind,
+                    dst: a,
+                    src: b,
+                },
+                1,
+            )
+        }
+        0x02 | 0x05 | 0x08 => {
+            let kind =
+                [ValueKind::Single, ValueKind::Wide, ValueKind::Object][(op - 0x02) as usize / 3];
+            (
+                Move {
+                    kind,
+                    dst: aa,
+                    src: units.get(1)?,
+                },
+                2,
+            )
+        }
+        0x03 | 0x06 | 0x09 => {
+            let kind =
+                [ValueKind::Single, ValueKind::Wide, ValueKind::Object][(op - 0x03) as usize / 3];
+            (
+                Move {
+                    kind,
+                    dst: units.get(1)?,
+                    src: units.get(2)?,
+                },
+                3,
+            )
+        }
+        0x0a..=0x0c => {
+            let kind = ACCESS_KINDS[(op - 0x0a) as usize];
+            (MoveResult { kind, dst: aa }, 1)
+        }
+        0x0d => (MoveException { dst: aa }, 1),
+        0x0e => (ReturnVoid, 1),
+        0x0f..=0x11 => {
+            let kind = ACCESS_KINDS[(op - 0x0f) as usize];
+            (Return { kind, src: aa }, 1)
+        }
+        0x12 => (
+            Const {
+                dst: a,
+                value: int::from((first as i16) >> 12),
+            },
+            1,
+        ),
+        0x13 => (
+            Const {
+                dst: aa,
+                value: offset16()?,
+            },
+            2,
+        ),
+        0x14 => (
+            Const {
+                dst: aa,
+                value: units.int(1)?,
+            },
+            3,
+        ),
+        0x15 => (
+            Const {
+                dst: aa,
+                value: (uint::from(units.get(1)?) << 16) as int,
+            },
+            2,
+        ),
+        0x16 => (
+            ConstWide {
+                dst: aa,
+                value: long::from(units.get(1)? as i16),
+            },
+            2,
+        ),
+        0x17 => (
+            ConstWide {
+                dst: aa,
+                value: long::from(units.int(1)?),
+            },
+            3,
+        ),
+        0x18 => {
+            let low = units.int(1)? as uint;
+            let high = units.int(3)? as uint;
+            (
+                ConstWide {
+                    dst: aa,
+                    value: ((u64::from(high) << 32) | u64::from(low)) as long,
+                },
+                5,
+            )
+        }
+        0x19 => (
+            ConstWide {
+                dst: aa,
+                value: (u64::from(units.get(1)?) << 48) as long,
+            },
+            2,
+        ),
+        0x1a => (
+            ConstString {
+                reg: aa,
+                string_idx: StringId::from(index(1)?),
+            },
+            2,
+        ),
+        0x1b => (
+            ConstString {
+                reg: aa,
+                string_idx: units.int(1)? as StringId,
+            },
+            3,
+        ),
+        0x1c => (
+            ConstClass {
+                reg: aa,
+                type_idx: TypeId::from(index(1)?),
+            },
+            2,
+        ),
+        0x1d => (MonitorEnter { reg: aa }, 1),
+        0x1e => (MonitorExit { reg: aa }, 1),
+        0x1f => (
+            CheckCast {
+                reg: aa,
+                type_idx: TypeId::from(index(1)?),
+            },
+            2,
+        ),
+        0x20 => (
+            InstanceOf {
+                dst: a,
+                src: b,
+                type_idx: TypeId::from(index(1)?),
+            },
+            2,
+        ),
+        0x21 => (ArrayLength { dst: a, array: b }, 1),
+        0x22 => (
+            NewInstance {
+                dst: aa,
+                type_idx: TypeId::from(index(1)?),
+            },
+            2,
+        ),
+        0x23 => (
+            NewArray {
+                dst: a,
+                size: b,
+                type_idx: TypeId::from(index(1)?),
+            },
+            2,
+        ),
+        0x24 => (
+            FilledNewArray {
+                type_idx: TypeId::from(index(1)?),
+                args: units.args()?,
+            },
+            3,
+        ),
+        0x25 => (
+            FilledNewArray {
+                type_idx: TypeId::from(index(1)?),
+                args: units.range()?,
+            },
+            3,
+        ),
+        0x26 => (
+            FillArrayData {
+                array: aa,
+                payload_offset: units.int(1)?,
+            },
+            3,
+        ),
+        0x27 => (Throw { reg: aa }, 1),
+        0x28 => (
+            Goto {
+                offset: int::from(aa as u8 as i8),
+            },
+            1,
+        ),
+        0x29 => (
+            Goto {
+                offset: offset16()?,
+            },
+            2,
+        ),
+        0x2a => (
+            Goto {
+                offset: units.int(1)?,
+            },
+            3,
+        ),
+        0x2b => (
+            PackedSwitch {
+                reg: aa,
+                payload_offset: units.int(1)?,
+            },
+            3,
+        ),
+        0x2c => (
+            SparseSwitch {
+                reg: aa,
+                payload_offset: units.int(1)?,
+            },
+            3,
+        ),
+        0x2d..=0x31 => {
+            let kind = [
+                CmpKind::LessFloat,
+                CmpKind::GreaterFloat,
+                CmpKind::LessDouble,
+                CmpKind::GreaterDouble,
+                CmpKind::Long,
+            ][(op - 0x2d) as usize];
+            let (bb, cc) = bb_cc()?;
+            (
+                Cmp {
+                    kind,
+                    dst: aa,
+                    a: bb,
+                    b: cc,
+                },
+                2,
+            )
+        }
+        0x32..=0x37 => (
+            If {
+                cond: IF_CONDS[(op - 0x32) as usize],
+                a,
+                b,
+                offset: offset16()?,
+            },
+            2,
+        ),
+        0x38..=0x3d => (
+            IfZero {
+                cond: IF_CONDS[(op - 0x38) as usize],
+                reg: aa,
+                offset: offset16()?,
+            },
+            2,
+        ),
+        0x44..=0x4a => {
+            let (bb, cc) = bb_cc()?;
+            (
+                ArrayGet {
+                    kind: ACCESS_KINDS[(op - 0x44) as usize],
+                    dst: aa,
+                    array: bb,
+                    index: cc,
+                },
+                2,
+            )
+        }
+        0x4b..=0x51 => {
+            let (bb, cc) = bb_cc()?;
+            (
+                ArrayPut {
+                    kind: ACCESS_KINDS[(op - 0x4b) as usize],
+                    src: aa,
+                    array: bb,
+                    index: cc,
+                },
+                2,
+            )
+        }
+        0x52..=0x58 => (
+            InstanceGet {
+                kind: ACCESS_KINDS[(op - 0x52) as usize],
+                dst: a,
+                object: b,
+                field_idx: FieldId::from(index(1)?),
+            },
+            2,
+        ),
+        0x59..=0x5f => (
+            InstancePut {
+                kind: ACCESS_KINDS[(op - 0x59) as usize],
+                src: a,
+                object: b,
+                field_idx: FieldId::from(index(1)?),
+            },
+            2,
+        ),
+        0x60..=0x66 => (
+            StaticGet {
+                kind: ACCESS_KINDS[(op - 0x60) as usize],
+                dst: aa,
+                field_idx: FieldId::from(index(1)?),
+            },
+            2,
+        ),
+        0x67..=0x6d => (
+            StaticPut {
+                kind: ACCESS_KINDS[(op - 0x67) as usize],
+                src: aa,
+                field_idx: FieldId::from(index(1)?),
+            },
+            2,
+        ),
+        0x6e..=0x72 => (
+            Invoke {
+                kind: INVOKE_KINDS[(op - 0x6e) as usize],
+                method_idx: MethodId::from(index(1)?),
+                args: units.args()?,
+            },
+            3,
+        ),
+        0x74..=0x78 => (
+            Invoke {
+                kind: INVOKE_KINDS[(op - 0x74) as usize],
+                method_idx: MethodId::from(index(1)?),
+                args: units.range()?,
+            },
+            3,
+        ),
+        0x7b..=0x8f => (
+            Unary {
+                op: UNARY_OPS[(op - 0x7b) as usize],
+                dst: a,
+                src: b,
+            },
+            1,
+        ),
+        0x90..=0xaf => {
+            let (ty, op) = binary_op(op - 0x90);
+            let (bb, cc) = bb_cc()?;
+            (
+                Binary {
+                    op,
+                    ty,
+                    dst: aa,
+                    a: bb,
+                    b: cc,
+                },
+                2,
+            )
+        }
+        0xb0..=0xcf => {
+            let (ty, op) = binary_op(op - 0xb0);
+            (
+                Binary {
+                    op,
+                    ty,
+                    dst: a,
+                    a,
+                    b,
+                },
+                1,
+            )
+        }
+        0xd0..=0xd7 => (
+            BinaryLit {
+                op: lit_op(op - 0xd0),
+                dst: a,
+                src: b,
+                literal: offset16()?,
+            },
+            2,
+        ),
+        0xd8..=0xe2 => {
+            let unit = units.get(1)?;
+            (
+                BinaryLit {
+                    op: lit_op(op - 0xd8),
+                    dst: aa,
+                    src: unit & 0xff,
+                    literal: int::from((unit >> 8) as u8 as i8),
+                },
+                2,
+            )
+        }
+        0xfa => (
+            InvokePolymorphic {
+                method_idx: MethodId::from(index(1)?),
+                proto_idx: ProtoId::from(index(3)?),
+                args: units.args()?,
+            },
+            4,
+        ),
+        0xfb => (
+            InvokePolymorphic {
+                method_idx: MethodId::from(index(1)?),
+                proto_idx: ProtoId::from(index(3)?),
+                args: units.range()?,
+            },
+            4,
+        ),
+        0xfc => (
+            InvokeCustom {
+                call_site_idx: uint::from(index(1)?),
+                args: units.args()?,
+            },
+            3,
+        ),
+        0xfd => (
+            InvokeCustom {
+                call_site_idx: uint::from(index(1)?),
+                args: units.range()?,
+            },
+            3,
+        ),
+        0xfe => (
+            ConstMethodHandle {
+                reg: aa,
+                method_handle_idx: MethodHandleId::from(index(1)?),
+            },
+            2,
+        ),
+        0xff => (
+            ConstMethodType {
+                reg: aa,
+                proto_idx: ProtoId::from(index(1)?),
+            },
+            2,
+        ),
+        _ => {
+            return Err(Error::MalFormed(format!(
+                "Unknown opcode {:#04x} at {}",
+                op, addr
+            )))
+        }
+    };
+    if addr + decoded.1 > insns.len() {
+        return Err(Error::MalFormed(format!(
+            "Instruction at {} out of bounds",
+            addr
+        )));
+    }
+    Ok(decoded)
+}
+
+/// Type and operation of the `binop` at `index` from the first one, `add-int`.
+fn binary_op(index: ushort) -> (NumericType, BinaryOp) {
+    match index {
+        0x00..=0x0a => (NumericType::Int, INT_OPS[index as usize]),
+        0x0b..=0x15 => (NumericType::Long, INT_OPS[index as usize - 0x0b]),
+        0x16..=0x1a => (NumericType::Float, INT_OPS[index as usize - 0x16]),
+        _ => (NumericType::Double, INT_OPS[index as usize - 0x1b]),
+    }
+}
+
+/// Operation of the `binop/lit` at `index` from the first one, `add-int/lit`.
+fn lit_op(index: ushort) -> BinaryOp {
+    match index {
+        0 => BinaryOp::Add,
+        1 => BinaryOp::Rsub,
+        index => INT_OPS[index as usize],
+    }
+}
+
+/// Iterator over the decoded instructions of a `CodeItem`, along with their
+/// addresses in 16-bit code units. The iteration stops after the first error.
+pub struct InstructionIterator<'a> {
+    insns: &'a [ushort],
+    addr: usize,
+    failed: bool,
+}
+
+impl<'a> InstructionIterator<'a> {
+    pub(crate) fn new(insns: &'a [ushort]) -> Self {
+        Self {
+            insns,
+            addr: 0,
+            failed: false,
+        }
+    }
+}
+
+impl Iterator for InstructionIterator<'_> {
+    type Item = Result<(usize, Instruction)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.addr >= self.insns.len() {
+            return None;
+        }
+        match decode(self.insns, self.addr) {
+            Ok((instruction, size)) => {
+                let addr = self.addr;
+                self.addr += size;
+                Some(Ok((addr, instruction)))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryOp, Instruction, InstructionIterator, InvokeKind, NumericType};
+    use crate::Result;
+
+    fn decode(insns: &[u16]) -> Result<Vec<(usize, Instruction)>> {
+        InstructionIterator::new(insns).collect()
+    }
+
+    #[test]
+    fn test_const_kinds() {
+        let insns = [
+            0xf012, // 0: const/4 v0, #-1
+            0x0113, 0x8000, // 1: const/16 v1, #-32768
+            0x0214, 0x5678, 0x1234, // 3: const v2, #0x12345678
+            0x0315, 0x4000, // 6: const/high16 v3, #0x40000000
+            0x0416, 0xfffe, // 8: const-wide/16 v4, #-2
+            0x0418, 0x0001, 0x0000, 0x0000, 0x8000, // 10: const-wide v4, #0x8000000000000001
+            0x0419, 0x3ff0, // 15: const-wide/high16 v4, #1.0
+            0x051a, 0x0007, // 17: const-string v5, string@7
+            0x051b, 0x0001, 0x0001, // 19: const-string/jumbo v5, string@0x10001
+            0x061c, 0x0002, // 22: const-class v6, type@2
+        ];
+        let expected = vec![
+            (0, Instruction::Const { dst: 0, value: -1 }),
+            (
+                1,
+                Instruction::Const {
+                    dst: 1,
+                    value: -32768,
+                },
+            ),
+            (
+                3,
+                Instruction::Const {
+                    dst: 2,
+                    value: 0x1234_5678,
+                },
+            ),
+            (
+                6,
+                Instruction::Const {
+                    dst: 3,
+                    value: 0x4000_0000,
+                },
+            ),
+            (8, Instruction::ConstWide { dst: 4, value: -2 }),
+            (
+                10,
+                Instruction::ConstWide {
+                    dst: 4,
+                    value: 0x8000_0000_0000_0001_u64 as i64,
+                },
+            ),
+            (
+                15,
+                Instruction::ConstWide {
+                    dst: 4,
+                    value: 1.0f64.to_bits() as i64,
+                },
+            ),
+            (
+                17,
+                Instruction::ConstString {
+                    reg: 5,
+                    string_idx: 7,
+                },
+            ),
+            (
+                19,
+                Instruction::ConstString {
+                    reg: 5,
+                    string_idx: 0x10001,
+                },
+            ),
+            (
+                22,
+                Instruction::ConstClass {
+                    reg: 6,
+                    type_idx: 2,
+                },
+            ),
+        ];
+        assert_eq!(decode(&insns).expect("cannot decode"), expected);
+    }
+
+    #[test]
+    fn test_invoke_kinds() {
+        let insns = [
+            0x206e, 0x0003, 0x0010, // 0: invoke-virtual {v0, v1}, meth@3
+            0x5471, 0x0004, 0x3210, // 3: invoke-static {v0, v1, v2, v3, v4}, meth@4
+            0x1070, 0x0005, 0x0002, // 6: invoke-direct {v2}, meth@5
+            0x0377, 0x0006, 0x0100, // 9: invoke-static/range {v256 .. v258}, meth@6
+            0x000e, // 12: return-void
+        ];
+        let expected = vec![
+            (
+                0,
+                Instruction::Invoke {
+                    kind: InvokeKind::Virtual,
+                    method_idx: 3,
+                    args: vec![0, 1],
+                },
+            ),
+            (
+                3,
+                Instruction::Invoke {
+                    kind: InvokeKind::Static,
+                    method_idx: 4,
+                    args: vec![0, 1, 2, 3, 4],
+                },
+            ),
+            (
+                6,
+                Instruction::Invoke {
+                    kind: InvokeKind::Direct,
+                    method_idx: 5,
+                    args: vec![2],
+                },
+            ),
+            (
+                9,
+                Instruction::Invoke {
+                    kind: InvokeKind::Static,
+                    method_idx: 6,
+                    args: vec![256, 257, 258],
+                },
+            ),
+            (12, Instruction::ReturnVoid),
+        ];
+        assert_eq!(decode(&insns).expect("cannot decode"), expected);
+    }
+
+    #[test]
+    fn test_binary_ops_and_payloads() {
+        let insns = [
+            0x01a3, 0x0302, // 0: shl-long v1, v2, v3
+            0x21c7, // 2: sub-float/2addr v1, v2
+            0x02d9, 0xff01, // 3: rsub-int/lit8 v2, v1, #-1
+            0x0000, // 5: nop
+            0x0100, 0x0002, 0x000a, 0x0000, 0x0004, 0x0000, 0x0006,
+            0x0000,
+            // 6: packed-switch-payload
+        ];
+        let decoded = decode(&insns).expect("cannot decode");
+        assert_eq!(
+            decoded[0].1,
+            Instruction::Binary {
+                op: BinaryOp::Shl,
+                ty: NumericType::Long,
+                dst: 1,
+                a: 2,
+                b: 3,
+            }
+        );
+        assert_eq!(
+            decoded[1].1,
+            Instruction::Binary {
+                op: BinaryOp::Sub,
+                ty: NumericType::Float,
+                dst: 1,
+                a: 1,
+                b: 2,
+            }
+        );
+        assert_eq!(
+            decoded[2].1,
+            Instruction::BinaryLit {
+                op: BinaryOp::Rsub,
+                dst: 2,
+                src: 1,
+                literal: -1,
+            }
+        );
+        assert_eq!(decoded[3], (5, Instruction::Nop));
+        assert_eq!(
+            decoded[4],
+            (
+                6,
+                Instruction::PackedSwitchPayload {
+                    first_key: 10,
+                    targets: vec![4, 6],
+                }
+            )
+        );
+        assert_eq!(decoded.len(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_dex_methods() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        for method in dex.methods_with_code() {
+            let method = method.expect("error parsing method");
+            let code = method.code().expect("missing code item");
+            let addrs = code
+                .instructions()
+                .map(|inst| inst.map(|(addr, _)| addr))
+                .collect::<Result<Vec<_>>>()
+                .expect("cannot decode");
+            let mut expected = Vec::new();
+            let mut addr = 0;
+            for inst in code.disassemble() {
+                expected.push(addr);
+                addr += inst.length() / 2;
+            }
+            assert_eq!(addrs, expected);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        // unused opcode
+        assert!(decode(&[0x003e]).is_err());
+        // truncated invoke
+        assert!(decode(&[0x106e, 0x0000]).is_err());
+        // the iteration stops after the error
+        let mut iter = InstructionIterator::new(&[0x0073, 0x000e]);
+        assert!(iter.next().expect("no instruction").is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_resolve() {
+        use super::{Reference, ValueKind};
+
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/SimpleRemoteViews;")
+            .expect("error finding class")
+            .expect("class not found");
+        let code = class
+            .methods()
+            .find(|method| method.name() == "setImageViewBitmap")
+            .and_then(|method| method.code())
+            .expect("method without code");
+        let instructions = code
+            .instructions()
+            .map(|instruction| instruction.expect("cannot decode instruction").1)
+            .collect::<Vec<_>>();
+        let references = instructions
+            .iter()
+            .map(|instruction| {
+                let resolved = instruction.resolve(&dex).expect("cannot resolve");
+                assert_eq!(resolved.instruction(), instruction);
+                resolved.reference().cloned()
+            })
+            .collect::<Vec<_>>();
+        match &references[..] {
+            [Some(Reference::String(string)), Some(Reference::Method(method)), None] => {
+                assert_eq!(string, "setImageBitmap");
+                assert_eq!(
+                    method.to_string(),
+                    "Lmobi/intuitit/android/widget/SimpleRemoteViews;->\
+                     setBitmap(ILjava/lang/String;Landroid/graphics/Bitmap;)V"
+                );
+            }
+            references => panic!("unexpected references: {:?}", references),
+        }
+
+        let new_instance = Instruction::NewInstance {
+            dst: 0,
+            type_idx: class.jtype().id(),
+        };
+        match new_instance
+            .resolve(&dex)
+            .expect("cannot resolve")
+            .reference()
+        {
+            Some(Reference::Type(jtype)) => assert_eq!(jtype, class.jtype()),
+            reference => panic!("unexpected reference: {:?}", reference),
+        }
+        let field = &class.instance_fields()[0];
+        let get = Instruction::InstanceGet {
+            kind: ValueKind::Object,
+            dst: 0,
+            object: 1,
+            field_idx: field.id(),
+        };
+        match get.resolve(&dex).expect("cannot resolve").reference() {
+            Some(Reference::Field(field_ref)) => assert_eq!(field_ref.name(), field.name()),
+            reference => panic!("unexpected reference: {:?}", reference),
+        }
+        let invalid = Instruction::ConstString {
+            reg: 0,
+            string_idx: u32::MAX,
+        };
+        assert!(invalid.resolve(&dex).is_err());
+    }
+}
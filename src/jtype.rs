@@ -1,5 +1,13 @@
 //! Dex `Type` and utilities
-use std::{clone::Clone, fmt};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    clone::Clone,
+    fmt,
+    hash::{Hash, Hasher},
+};
 
 use getset::{CopyGetters, Getters};
 
@@ -21,6 +29,8 @@ pub const LONG: &'static str = "J";
 pub const FLOAT: &'static str = "F";
 /// Dex representation of a double type
 pub const DOUBLE: &'static str = "D";
+/// Dex representation of the void type, only valid as a return type
+pub const VOID: &str = "V";
 
 /// Offset into the `TypeId`s section.
 pub type TypeId = uint;
@@ -88,11 +98,26 @@ impl Type {
         }
     }
 
-    /// Returns the Java representation of the `Type`
-    pub fn to_java_type(&self) -> String {
+    /// Returns `true` if both types have the same element type, ignoring
+    /// array dimensions. `[[Ljava/lang/String;` and `Ljava/lang/String;` have
+    /// the same base type.
+    pub fn same_base(&self, other: &Type) -> bool {
+        self.type_descriptor.trim_start_matches('[')
+            == other.type_descriptor.trim_start_matches('[')
+    }
+
+    /// Returns the Java name of the `Type`, e.g. `java.lang.String` for
+    /// `Ljava/lang/String;`, `int[]` for `[I`, `java.lang.Object[][]` for
+    /// `[[Ljava/lang/Object;` and `void` for `V`.
+    pub fn to_java_name(&self) -> String {
         to_java_type(&*self.type_descriptor)
     }
 
+    /// Same as `to_java_name`.
+    pub fn to_java_type(&self) -> String {
+        self.to_java_name()
+    }
+
     gen_is_type_method!(is_bool, BOOLEAN, "Returns `true` if the type is a boolean");
     gen_is_type_method!(is_byte, BYTE, "Returns `true` if the type is a byte");
     gen_is_type_method!(is_short, SHORT, "Returns `true` if the type is a short");
@@ -113,6 +138,7 @@ fn to_java_type(s: &str) -> String {
         LONG => "long".to_string(),
         FLOAT => "float".to_string(),
         DOUBLE => "double".to_string(),
+        VOID => "void".to_string(),
         s if s.starts_with('L') => s[1..].replace('/', ".").replace(';', ""),
         s if s.starts_with('[') => {
             let d = s.chars().take_while(|c| *c == '[').count();
@@ -124,6 +150,48 @@ fn to_java_type(s: &str) -> String {
     }
 }
 
+/// Returns `true` if the descriptor conforms to the `TypeDescriptor` syntax:
+/// a primitive, `V`, a class name of the form `L<name>;` or an array of
+/// at most 255 dimensions of a non-void type.
+pub(crate) fn is_valid_descriptor(descriptor: &str) -> bool {
+    let dimensions = descriptor.chars().take_while(|c| *c == '[').count();
+    if dimensions > 255 {
+        return false;
+    }
+    match &descriptor[dimensions..] {
+        VOID => dimensions == 0,
+        BOOLEAN | BYTE | SHORT | CHAR | INT | LONG | FLOAT | DOUBLE => true,
+        s if s.len() > 2 && s.starts_with('L') && s.ends_with(';') => s[1..s.len() - 1]
+            .split('/')
+            .all(|name| !name.is_empty() && !name.contains(';')),
+        _ => false,
+    }
+}
+
+/// Splits a sequence of type descriptors, such as the parameters of a method
+/// signature, into the individual descriptors. Returns `None` if one of them is invalid.
+pub(crate) fn split_descriptors(descriptors: &str) -> Option<Vec<&str>> {
+    let mut split = Vec::new();
+    let mut rest = descriptors;
+    while !rest.is_empty() {
+        let dimensions = rest.bytes().take_while(|c| *c == b'[').count();
+        let len = match rest.as_bytes().get(dimensions)? {
+            b'L' => dimensions + rest[dimensions..].find(';')? + 1,
+            _ => dimensions + 1,
+        };
+        if !rest.is_char_boundary(len) {
+            return None;
+        }
+        let (descriptor, tail) = rest.split_at(len);
+        if !is_valid_descriptor(descriptor) {
+            return None;
+        }
+        split.push(descriptor);
+        rest = tail;
+    }
+    Some(split)
+}
+
 impl Clone for Type {
     fn clone(&self) -> Self {
         Type {
@@ -139,6 +207,14 @@ impl PartialEq<Type> for Type {
     }
 }
 
+impl Eq for Type {}
+
+impl Hash for Type {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
 impl PartialEq<DexString> for Type {
     fn eq(&self, other: &DexString) -> bool {
         self.type_descriptor() == other
@@ -165,6 +241,20 @@ impl fmt::Display for Type {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_split_descriptors() {
+        use super::split_descriptors;
+        assert_eq!(split_descriptors(""), Some(vec![]));
+        assert_eq!(
+            split_descriptors("ILjava/lang/String;[[JZ"),
+            Some(vec!["I", "Ljava/lang/String;", "[[J", "Z"])
+        );
+        assert_eq!(split_descriptors("[V"), None);
+        assert_eq!(split_descriptors("Ljava/lang/String"), None);
+        assert_eq!(split_descriptors("IQ"), None);
+        assert_eq!(split_descriptors("["), None);
+    }
+
     #[test]
     fn test_to_java_type() {
         use super::to_java_type;
@@ -176,8 +266,62 @@ mod tests {
         assert_eq!(to_java_type(super::LONG), "long");
         assert_eq!(to_java_type(super::FLOAT), "float");
         assert_eq!(to_java_type(super::DOUBLE), "double");
+        assert_eq!(to_java_type(super::VOID), "void");
         assert_eq!(to_java_type("Ljava/lang/String;"), "java.lang.String");
+        assert_eq!(to_java_type("[I"), "int[]");
+        assert_eq!(to_java_type("[[Ljava/lang/Object;"), "java.lang.Object[][]");
         assert_eq!(to_java_type("[Ljava/lang/String;"), "java.lang.String[]");
         assert_eq!(to_java_type("[[Ljava/lang/String;"), "java.lang.String[][]");
     }
+
+    #[test]
+    fn test_same_base() {
+        use super::Type;
+        let jtype = |id, descriptor: &str| Type {
+            id,
+            type_descriptor: descriptor.to_string().into(),
+        };
+        let string = jtype(0, "Ljava/lang/String;");
+        assert!(string.same_base(&jtype(1, "[[Ljava/lang/String;")));
+        assert!(jtype(2, "[I").same_base(&jtype(3, "[[[I")));
+        assert!(string.same_base(&string));
+        assert!(!string.same_base(&jtype(4, "[Ljava/lang/Object;")));
+        assert!(!jtype(5, "I").same_base(&jtype(6, "[J")));
+    }
+
+    #[test]
+    fn test_to_java_name() {
+        use super::Type;
+        let java_name = |descriptor: &str| {
+            Type {
+                id: 0,
+                type_descriptor: descriptor.to_string().into(),
+            }
+            .to_java_name()
+        };
+        assert_eq!(java_name("Ljava/lang/String;"), "java.lang.String");
+        assert_eq!(java_name("[I"), "int[]");
+        assert_eq!(java_name("[[Ljava/lang/Object;"), "java.lang.Object[][]");
+        assert_eq!(java_name("Z"), "boolean");
+        assert_eq!(java_name("V"), "void");
+    }
+
+    #[test]
+    fn test_is_valid_descriptor() {
+        use super::is_valid_descriptor;
+        assert!(is_valid_descriptor(super::INT));
+        assert!(is_valid_descriptor(super::VOID));
+        assert!(is_valid_descriptor("Ljava/lang/String;"));
+        assert!(is_valid_descriptor("[[Ljava/lang/String;"));
+        assert!(is_valid_descriptor("[J"));
+        assert!(!is_valid_descriptor(""));
+        assert!(!is_valid_descriptor("[V"));
+        assert!(!is_valid_descriptor("["));
+        assert!(!is_valid_descriptor("X"));
+        assert!(!is_valid_descriptor("L;"));
+        assert!(!is_valid_descriptor("Ljava/lang/String"));
+        assert!(!is_valid_descriptor("Ljava//String;"));
+        assert!(!is_valid_descriptor("<init>"));
+        assert!(!is_valid_descriptor(&format!("{}I", "[".repeat(256))));
+    }
 }
@@ -1,8 +1,17 @@
 //! Dex is a library for reading Android's
 //! [dex](https://source.android.com/devices/tech/dalvik/dex-format) file format.
+//!
+//! The `std` feature, enabled by default, provides the constructors reading from the
+//! file system or from `std::io` readers, e.g. `DexReader::from_file`, and the ART
+//! profiles. Without it, the crate is `no_std` and only requires `alloc`: dex files
+//! are parsed from memory with `DexReader::read` or `DexReader::from_bytes`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 // Silence warnings in error module for now
 #![allow(bare_trait_objects)]
 
+#[macro_use]
+extern crate alloc;
+
 pub extern crate scroll;
 
 #[macro_use]
@@ -16,14 +25,26 @@ extern crate log;
 
 extern crate getset;
 
-pub use error::Error;
+pub use error::{Error, FlagsOwner};
 
-pub use crate::dex::{Dex, DexReader, Header};
+pub use crate::{
+    builder::DexBuilder,
+    cache::CacheStats,
+    dex::{
+        CompilerHint, Dex, DexReader, DexStats, Header, ItemType, MapItem, MapList,
+        MemberDistribution, ParseWarning, MAX_REFERENCES,
+    },
+};
 
 #[macro_use]
 mod utils;
 pub mod annotation;
+#[cfg(feature = "apk")]
+mod apk;
+mod builder;
 mod cache;
+pub mod callgraph;
+pub mod cfg;
 pub mod class;
 pub mod code;
 mod dex;
@@ -32,9 +53,22 @@ mod encoded_item;
 pub mod encoded_value;
 mod error;
 pub mod field;
+pub mod hiddenapi;
+pub mod index;
+#[cfg(feature = "regex")]
+pub mod indicators;
+pub mod instruction;
 pub mod jtype;
+pub mod liveness;
+pub mod metadata;
 pub mod method;
+pub mod multidex;
+#[cfg(feature = "std")]
+pub mod profile;
 mod search;
+#[cfg(feature = "serde")]
+mod serialize;
+mod smali;
 mod source;
 pub mod string;
 
@@ -69,7 +103,13 @@ pub type ulong = u64;
 pub type long = i64;
 
 /// A `Result` of `T` or an error of `error::Error`
-pub type Result<T> = std::result::Result<T, error::Error>;
+pub type Result<T> = core::result::Result<T, error::Error>;
+
+// the hash maps of `std` need a source of randomness, its own hasher.
+#[cfg(not(feature = "std"))]
+pub(crate) use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{HashMap, HashSet};
 
 // ref. https://source.android.com/devices/tech/dalvik/dex-format
 
@@ -0,0 +1,360 @@
+//! Liveness analysis of the registers of a `Method`'s code.
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::{
+    cfg::ControlFlowGraph,
+    error::Error,
+    instruction::{
+        BinaryOp, CmpKind, Instruction, InstructionIterator, NumericType, UnaryOp, ValueKind,
+    },
+    ushort, Result,
+};
+
+/// Registers live before and after each instruction of a `CodeItem`, i.e. holding a
+/// value which may be read later on.
+///
+/// Only the instructions of the blocks reachable from the entry are analysed. Exception
+/// edges are taken from the end of the blocks covered by a try block, so a register
+/// written in such a block and read by its handler is not considered live before the
+/// write.
+#[derive(Debug)]
+pub struct Liveness {
+    live_in: BTreeMap<usize, BTreeSet<ushort>>,
+    live_out: BTreeMap<usize, BTreeSet<ushort>>,
+}
+
+/// Registers written and read by an instruction.
+#[derive(Debug, Default)]
+struct Access {
+    defs: Vec<ushort>,
+    uses: Vec<ushort>,
+}
+
+impl Access {
+    fn def(&mut self, reg: ushort, wide: bool) {
+        self.defs.push(reg);
+        if wide {
+            self.defs.push(reg.wrapping_add(1));
+        }
+    }
+
+    fn read(&mut self, reg: ushort, wide: bool) {
+        self.uses.push(reg);
+        if wide {
+            self.uses.push(reg.wrapping_add(1));
+        }
+    }
+
+    /// Updates `live` from the registers live after the instruction to the ones
+    /// live before it.
+    fn transfer(&self, live: &mut BTreeSet<ushort>) {
+        for reg in &self.defs {
+            live.remove(reg);
+        }
+        live.extend(self.uses.iter().cloned());
+    }
+}
+
+/// Returns whether the destination and the source of a `unop` are register pairs.
+fn unary_widths(op: UnaryOp) -> (bool, bool) {
+    use UnaryOp::*;
+    let dst_wide = matches!(
+        op,
+        NegLong
+            | NotLong
+            | NegDouble
+            | IntToLong
+            | IntToDouble
+            | LongToDouble
+            | FloatToLong
+            | FloatToDouble
+            | DoubleToLong
+    );
+    let src_wide = matches!(
+        op,
+        NegLong
+            | NotLong
+            | NegDouble
+            | LongToInt
+            | LongToFloat
+            | LongToDouble
+            | DoubleToInt
+            | DoubleToLong
+            | DoubleToFloat
+    );
+    (dst_wide, src_wide)
+}
+
+/// Returns the registers accessed by `instruction`, found at `addr`. Registers not
+/// below `registers_size` are reported as errors.
+fn access(instruction: &Instruction, addr: usize, registers_size: ushort) -> Result<Access> {
+    use Instruction::*;
+    let wide = |kind: &ValueKind| *kind == ValueKind::Wide;
+    let mut access = Access::default();
+    match instruction {
+        Move { kind, dst, src } => {
+            access.def(*dst, wide(kind));
+            access.read(*src, wide(kind));
+        }
+        MoveResult { kind, dst } => access.def(*dst, wide(kind)),
+        MoveException { dst } | Const { dst, .. } | NewInstance { dst, .. } => {
+            access.def(*dst, false)
+        }
+        ConstString { reg, .. }
+        | ConstClass { reg, .. }
+        | ConstMethodHandle { reg, .. }
+        | ConstMethodType { reg, .. } => access.def(*reg, false),
+        ConstWide { dst, .. } => access.def(*dst, true),
+        Return { kind, src } => access.read(*src, wide(kind)),
+        MonitorEnter { reg }
+        | MonitorExit { reg }
+        | CheckCast { reg, .. }
+        | Throw { reg }
+        | PackedSwitch { reg, .. }
+        | SparseSwitch { reg, .. }
+        | IfZero { reg, .. } => access.read(*reg, false),
+        FillArrayData { array, .. } => access.read(*array, false),
+        InstanceOf { dst, src, .. }
+        | ArrayLength { dst, array: src }
+        | NewArray { dst, size: src, .. }
+        | BinaryLit { dst, src, .. } => {
+            access.def(*dst, false);
+            access.read(*src, false);
+        }
+        FilledNewArray { args, .. }
+        | Invoke { args, .. }
+        | InvokePolymorphic { args, .. }
+        | InvokeCustom { args, .. } => args.iter().for_each(|reg| access.read(*reg, false)),
+        If { a, b, .. } => {
+            access.read(*a, false);
+            access.read(*b, false);
+        }
+        Cmp { kind, dst, a, b } => {
+            let wide = !matches!(kind, CmpKind::LessFloat | CmpKind::GreaterFloat);
+            access.def(*dst, false);
+            access.read(*a, wide);
+            access.read(*b, wide);
+        }
+        ArrayGet {
+            kind,
+            dst,
+            array,
+            index,
+        } => {
+            access.def(*dst, wide(kind));
+            access.read(*array, false);
+            access.read(*index, false);
+        }
+        ArrayPut {
+            kind,
+            src,
+            array,
+            index,
+        } => {
+            access.read(*src, wide(kind));
+            access.read(*array, false);
+            access.read(*index, false);
+        }
+        InstanceGet {
+            kind, dst, object, ..
+        } => {
+            access.def(*dst, wide(kind));
+            access.read(*object, false);
+        }
+        InstancePut {
+            kind, src, object, ..
+        } => {
+            access.read(*src, wide(kind));
+            access.read(*object, false);
+        }
+        StaticGet { kind, dst, .. } => access.def(*dst, wide(kind)),
+        StaticPut { kind, src, .. } => access.read(*src, wide(kind)),
+        Unary { op, dst, src } => {
+            let (dst_wide, src_wide) = unary_widths(*op);
+            access.def(*dst, dst_wide);
+            access.read(*src, src_wide);
+        }
+        // the shift amount of the long shifts is an int.
+        Binary { op, ty, dst, a, b } => {
+            let wide = matches!(ty, NumericType::Long | NumericType::Double);
+            let is_shift = matches!(op, BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Ushr);
+            access.def(*dst, wide);
+            access.read(*a, wide);
+            access.read(*b, wide && !is_shift);
+        }
+        Nop
+        | ReturnVoid
+        | Goto { .. }
+        | PackedSwitchPayload { .. }
+        | SparseSwitchPayload { .. }
+        | FillArrayDataPayload { .. } => {}
+    }
+    let invalid = access
+        .defs
+        .iter()
+        .chain(access.uses.iter())
+        .find(|reg| **reg >= registers_size);
+    if let Some(reg) = invalid {
+        return Err(Error::MalFormed(format!(
+            "Register v{} of instruction at {} out of {} registers",
+            reg, addr, registers_size
+        )));
+    }
+    Ok(access)
+}
+
+impl Liveness {
+    pub(crate) fn new(
+        insns: &[ushort],
+        registers_size: ushort,
+        cfg: &ControlFlowGraph,
+    ) -> Result<Self> {
+        let instructions = InstructionIterator::new(insns).collect::<Result<Vec<_>>>()?;
+        let reachable = cfg.reachable_blocks();
+        // accesses of the instructions of each reachable block, in order.
+        let mut blocks = BTreeMap::new();
+        for block in &reachable {
+            let range = &cfg.blocks()[*block];
+            let accesses = instructions
+                .iter()
+                .filter(|(addr, _)| range.start_addr() <= *addr && *addr < range.end_addr())
+                .map(|(addr, instruction)| Ok((*addr, access(instruction, *addr, registers_size)?)))
+                .collect::<Result<Vec<_>>>()?;
+            blocks.insert(*block, accesses);
+        }
+
+        let mut block_live_in: BTreeMap<usize, BTreeSet<ushort>> = BTreeMap::new();
+        let block_live_out = |block: usize, live_in: &BTreeMap<usize, BTreeSet<ushort>>| {
+            cfg.blocks()[block]
+                .successors()
+                .iter()
+                .filter_map(|successor| live_in.get(successor))
+                .flatten()
+                .cloned()
+                .collect::<BTreeSet<_>>()
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (block, accesses) in blocks.iter().rev() {
+                let mut live = block_live_out(*block, &block_live_in);
+                for (_, access) in accesses.iter().rev() {
+                    access.transfer(&mut live);
+                }
+                if block_live_in.get(block) != Some(&live) {
+                    block_live_in.insert(*block, live);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut liveness = Self {
+            live_in: BTreeMap::new(),
+            live_out: BTreeMap::new(),
+        };
+        for (block, accesses) in &blocks {
+            let mut live = block_live_out(*block, &block_live_in);
+            for (addr, access) in accesses.iter().rev() {
+                liveness.live_out.insert(*addr, live.clone());
+                access.transfer(&mut live);
+                liveness.live_in.insert(*addr, live.clone());
+            }
+        }
+        Ok(liveness)
+    }
+
+    /// Registers live right before the instruction at `addr`, in 16-bit code units.
+    /// `None` if there is no reachable instruction at `addr`.
+    pub fn live_in(&self, addr: usize) -> Option<&BTreeSet<ushort>> {
+        self.live_in.get(&addr)
+    }
+
+    /// Registers live right after the instruction at `addr`, in 16-bit code units.
+    /// `None` if there is no reachable instruction at `addr`.
+    pub fn live_out(&self, addr: usize) -> Option<&BTreeSet<ushort>> {
+        self.live_out.get(&addr)
+    }
+
+    /// Peak number of registers live at the same time. Wide values count as two
+    /// registers.
+    pub fn max_live(&self) -> usize {
+        self.live_in
+            .values()
+            .chain(self.live_out.values())
+            .map(|live| live.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::Liveness;
+    use crate::{cfg::ControlFlowGraph, ushort};
+
+    fn liveness(insns: &[ushort], registers_size: ushort) -> crate::Result<Liveness> {
+        let cfg = ControlFlowGraph::new(insns, &[]).expect("cannot build cfg");
+        Liveness::new(insns, registers_size, &cfg)
+    }
+
+    fn regs(regs: &[ushort]) -> BTreeSet<ushort> {
+        regs.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_straight_line() {
+        let insns = [
+            0x1012, // 0: const/4 v0, 1
+            0x2112, // 1: const/4 v1, 2
+            0x10b0, // 2: add-int/2addr v0, v1
+            0x0016, 0x0003, // 3: const-wide/16 v0, 3
+            0x0010, // 5: return-wide v0
+        ];
+        let liveness = liveness(&insns, 3).expect("cannot compute liveness");
+        assert_eq!(liveness.live_in(0), Some(&regs(&[])));
+        assert_eq!(liveness.live_out(0), Some(&regs(&[0])));
+        assert_eq!(liveness.live_out(1), Some(&regs(&[0, 1])));
+        // The result of the addition is overwritten without being read.
+        assert_eq!(liveness.live_out(2), Some(&regs(&[])));
+        assert_eq!(liveness.live_in(5), Some(&regs(&[0, 1])));
+        assert_eq!(liveness.live_in(4), None);
+        assert_eq!(liveness.max_live(), 2);
+    }
+
+    #[test]
+    fn test_loop() {
+        let insns = [
+            0x0012, // 0: const/4 v0, 0
+            0x1112, // 1: const/4 v1, 1
+            0x10b0, // 2: add-int/2addr v0, v1
+            0x0038, 0xffff, // 3: if-eqz v0, -1
+            0x000f, // 5: return v0
+        ];
+        let liveness = liveness(&insns, 2).expect("cannot compute liveness");
+        // v1 is read on every iteration of the loop.
+        assert_eq!(liveness.live_in(2), Some(&regs(&[0, 1])));
+        assert_eq!(liveness.live_out(3), Some(&regs(&[0, 1])));
+        assert_eq!(liveness.live_in(5), Some(&regs(&[0])));
+        assert_eq!(liveness.max_live(), 2);
+    }
+
+    #[test]
+    fn test_invalid_register() {
+        let insns = [
+            0x020f, // 0: return v2
+        ];
+        assert!(liveness(&insns, 2).is_err());
+    }
+
+    #[test]
+    fn test_truncated_instruction() {
+        let cfg = ControlFlowGraph::new(&[0x000e], &[]).expect("cannot build cfg");
+        // 1: const v0, with its literal missing.
+        assert!(Liveness::new(&[0x000e, 0x0014], 1, &cfg).is_err());
+    }
+}
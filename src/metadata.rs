@@ -0,0 +1,172 @@
+//! User data attached to the classes, methods and fields of a `Dex`.
+//!
+//! These are not related to the annotations of the dex format: `Annotations` lets an
+//! analysis tag items with its own results, e.g. to mark methods as hot or suspicious,
+//! and share them across passes.
+use alloc::collections::{btree_map, BTreeMap};
+
+use crate::{
+    class::{Class, ClassId},
+    field::{Field, FieldId},
+    method::{Method, MethodId},
+};
+
+/// Global identifier of a class, method or field of a `Dex`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ItemId {
+    /// Index into the `TypeId`s list of a class.
+    Class(ClassId),
+    /// Index into the `MethodId`s list.
+    Method(MethodId),
+    /// Index into the `FieldId`s list.
+    Field(FieldId),
+}
+
+impl From<&Class> for ItemId {
+    fn from(class: &Class) -> Self {
+        ItemId::Class(class.id())
+    }
+}
+
+impl From<&Method> for ItemId {
+    fn from(method: &Method) -> Self {
+        ItemId::Method(method.id())
+    }
+}
+
+impl From<&Field> for ItemId {
+    fn from(field: &Field) -> Self {
+        ItemId::Field(field.id())
+    }
+}
+
+/// Side table associating user data with items of a `Dex`. Entries are ordered by
+/// their `ItemId`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotations<T> {
+    entries: BTreeMap<ItemId, T>,
+}
+
+impl<T> Annotations<T> {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Associates `value` with the item, returning the previous value if there was one.
+    pub fn insert<I: Into<ItemId>>(&mut self, item: I, value: T) -> Option<T> {
+        self.entries.insert(item.into(), value)
+    }
+
+    /// Returns the value associated with the item.
+    pub fn get<I: Into<ItemId>>(&self, item: I) -> Option<&T> {
+        self.entries.get(&item.into())
+    }
+
+    /// Returns a mutable reference to the value associated with the item.
+    pub fn get_mut<I: Into<ItemId>>(&mut self, item: I) -> Option<&mut T> {
+        self.entries.get_mut(&item.into())
+    }
+
+    /// Returns the entry of the item, for in-place updates.
+    pub fn entry<I: Into<ItemId>>(&mut self, item: I) -> btree_map::Entry<'_, ItemId, T> {
+        self.entries.entry(item.into())
+    }
+
+    /// Removes the value associated with the item and returns it.
+    pub fn remove<I: Into<ItemId>>(&mut self, item: I) -> Option<T> {
+        self.entries.remove(&item.into())
+    }
+
+    /// Returns `true` if a value is associated with the item.
+    pub fn contains<I: Into<ItemId>>(&self, item: I) -> bool {
+        self.entries.contains_key(&item.into())
+    }
+
+    /// Number of items with a value.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no item has a value.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterator over the items and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (ItemId, &T)> + '_ {
+        self.entries.iter().map(|(item, value)| (*item, value))
+    }
+
+    /// Iterator over the methods and their values.
+    pub fn methods(&self) -> impl Iterator<Item = (MethodId, &T)> + '_ {
+        self.iter().filter_map(|(item, value)| match item {
+            ItemId::Method(method_id) => Some((method_id, value)),
+            _ => None,
+        })
+    }
+}
+
+impl<T> Default for Annotations<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> core::iter::FromIterator<(ItemId, T)> for Annotations<T> {
+    fn from_iter<I: IntoIterator<Item = (ItemId, T)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Annotations, ItemId};
+    use crate::DexReader;
+
+    #[test]
+    fn test_annotations() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let method = class
+            .methods()
+            .find(|m| m.name() == "setText")
+            .expect("method not found");
+        let field = class.fields().next().expect("class has no fields");
+
+        let mut tags = Annotations::new();
+        assert!(tags.is_empty());
+        assert_eq!(tags.insert(&class, "ui"), None);
+        assert_eq!(tags.insert(method, "hot"), None);
+        assert_eq!(tags.insert(field, "state"), None);
+        assert_eq!(tags.insert(method, "suspicious"), Some("hot"));
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags.get(method), Some(&"suspicious"));
+        assert_eq!(tags.get(ItemId::Method(method.id())), Some(&"suspicious"));
+        // Ids of different kinds of items don't collide.
+        let mut kinds = Annotations::new();
+        kinds.insert(ItemId::Method(0), "method");
+        kinds.insert(ItemId::Field(0), "field");
+        assert_eq!(kinds.get(ItemId::Method(0)), Some(&"method"));
+        assert_eq!(kinds.get(ItemId::Field(0)), Some(&"field"));
+        assert_eq!(
+            tags.methods().collect::<Vec<_>>(),
+            vec![(method.id(), &"suspicious")]
+        );
+
+        let mut counts = Annotations::default();
+        for _ in 0..2 {
+            *counts.entry(method).or_insert(0) += 1;
+        }
+        assert_eq!(counts.get(method), Some(&2));
+        assert_eq!(counts.remove(method), Some(2));
+        assert!(counts.is_empty());
+    }
+}
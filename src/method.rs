@@ -1,18 +1,29 @@
 //! Dex `Method` and supporting structures
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{convert::TryFrom, fmt};
+
 use getset::{CopyGetters, Getters};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use scroll::{ctx, Pread, Uleb128};
 
 use crate::{
-    annotation::{AnnotationSetItem, AnnotationSetRefList},
-    code::CodeItem,
+    annotation::{AnnotationItem, AnnotationSetItem, AnnotationSetRefList},
+    class::{Class, ClassId},
+    code::{Arguments, CodeItem, ExceptionType, Opcode, DEBUG_INFO_OFFSET},
+    dex::ParseWarning,
     encoded_item::{EncodedItem, EncodedItemArray},
-    error::Error,
+    encoded_value::EncodedValue,
+    error::{Error, FlagsOwner},
     field::FieldId,
+    hiddenapi::HiddenApiFlags,
     jtype::{Type, TypeId},
     string::{DexString, StringId},
-    uint, ulong, ushort, utils,
+    uint, ulong, ushort, utils, HashSet,
 };
 
 bitflags! {
@@ -35,9 +46,37 @@ bitflags! {
     }
 }
 
+impl AccessFlags {
+    /// Returns the Java modifiers of a method declaration in canonical order, e.g.
+    /// `public static final`. `synchronized` is rendered for both the synchronized
+    /// and declared synchronized flags.
+    pub fn to_modifier_string(&self) -> String {
+        let synchronized = AccessFlags::SYNCHRONIZED | AccessFlags::DECLARED_SYNCHRONIZED;
+        [
+            (AccessFlags::PUBLIC, "public"),
+            (AccessFlags::PROTECTED, "protected"),
+            (AccessFlags::PRIVATE, "private"),
+            (AccessFlags::ABSTRACT, "abstract"),
+            (AccessFlags::STATIC, "static"),
+            (AccessFlags::FINAL, "final"),
+            (synchronized, "synchronized"),
+            (AccessFlags::NATIVE, "native"),
+            (AccessFlags::STRICT, "strictfp"),
+        ]
+        .iter()
+        .filter(|(flags, _)| self.intersects(*flags))
+        .map(|(_, modifier)| *modifier)
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
 /// Represents a `Class` method.
-#[derive(Debug, Getters, CopyGetters)]
+#[derive(Debug, Clone, Getters, CopyGetters)]
 pub struct Method {
+    /// Index into the `MethodId`s list for this method.
+    #[get_copy = "pub"]
+    id: MethodId,
     /// Parent class of the method.
     #[get = "pub"]
     class: Type,
@@ -47,24 +86,77 @@ pub struct Method {
     /// Access flags of the method.
     #[get_copy = "pub"]
     access_flags: AccessFlags,
-    /// Types of the parameters of the method.
-    #[get = "pub"]
-    params: Vec<Type>,
-    /// Shorty descriptor of the method, as described
-    /// [here](https://source.android.com/devices/tech/dalvik/dex-format#shortydescriptor)
-    #[get = "pub"]
-    shorty: DexString,
-    /// Return type of the method.
+    /// Prototype of the method.
     #[get = "pub"]
-    return_type: Type,
+    prototype: Prototype,
+    /// Offset from the start of the file to the `CodeItem` of the method, or 0 if the
+    /// method has no code.
+    #[get_copy = "pub"]
+    code_offset: uint,
     /// Code and DebugInfo of the method.
     code: Option<CodeItem>,
     /// Annotations of the method.
     #[get = "pub"]
-    annotations: AnnotationSetItem,
+    pub(crate) annotations: AnnotationSetItem,
     /// Annotations of the params.
     #[get = "pub"]
-    param_annotations: AnnotationSetRefList,
+    pub(crate) param_annotations: AnnotationSetRefList,
+    /// Hidden API restriction of the method, `None` if the dex file has no
+    /// `hiddenapi_class_data_item` or no flags for the class of the method.
+    #[get_copy = "pub"]
+    pub(crate) hidden_api_flags: Option<HiddenApiFlags>,
+}
+
+/// Signature of a method, without its name, parsed from a `ProtoIdItem`.
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct Prototype {
+    /// Shorty descriptor of the prototype, as described
+    /// [here](https://source.android.com/devices/tech/dalvik/dex-format#shortydescriptor)
+    shorty: DexString,
+    /// Return type of the prototype.
+    return_type: Type,
+    /// Types of the parameters of the prototype, empty if it has no parameters.
+    parameters: Vec<Type>,
+}
+
+impl Prototype {
+    /// Descriptor of the prototype, e.g. `(ILjava/lang/String;)V`.
+    pub fn descriptor(&self) -> String {
+        let mut descriptor = String::from("(");
+        for param in &self.parameters {
+            descriptor.push_str(param.type_descriptor());
+        }
+        descriptor.push(')');
+        descriptor.push_str(self.return_type.type_descriptor());
+        descriptor
+    }
+
+    pub(crate) fn try_from_dex<S: AsRef<[u8]>>(
+        dex: &super::Dex<S>,
+        proto_item: &ProtoIdItem,
+    ) -> super::Result<Self> {
+        Ok(Self {
+            shorty: proto_item.get_shorty(dex)?,
+            return_type: dex.get_type(proto_item.return_type)?,
+            parameters: dex.get_type_list(proto_item.params_off)?,
+        })
+    }
+}
+
+/// Exceptions a method can throw or catch, see `Method::exception_footprint`.
+#[derive(Debug, Clone, Default, PartialEq, Getters, CopyGetters)]
+pub struct ExceptionFootprint {
+    /// Exceptions declared in the `throws` clause of the method.
+    #[get = "pub"]
+    declared: Vec<Type>,
+    /// Distinct exception types handled by the try blocks of the method, in the order
+    /// of their first handler.
+    #[get = "pub"]
+    caught: Vec<Type>,
+    /// `true` if one of the try blocks of the method has a catch-all handler.
+    #[get_copy = "pub"]
+    catches_all: bool,
 }
 
 impl Method {
@@ -83,15 +175,508 @@ impl Method {
     gen_is_flag_set!(is_constructor, CONSTRUCTOR);
     gen_is_flag_set!(is_declared_synchronized, DECLARED_SYNCHRONIZED);
 
+    /// Types of the parameters of the method.
+    pub fn params(&self) -> &Vec<Type> {
+        &self.prototype.parameters
+    }
+
+    /// Shorty descriptor of the method. See `Prototype::shorty`.
+    pub fn shorty(&self) -> &DexString {
+        &self.prototype.shorty
+    }
+
+    /// Return type of the method.
+    pub fn return_type(&self) -> &Type {
+        &self.prototype.return_type
+    }
+
+    /// Returns the annotation of the method with the given type descriptor, e.g.
+    /// `Ljava/lang/Deprecated;`, regardless of its visibility.
+    pub fn annotation(&self, type_descriptor: &str) -> Option<&AnnotationItem> {
+        self.annotations.find_annotation(type_descriptor)
+    }
+
+    /// Annotations of each parameter of the method, in the order of `params`. The
+    /// list of a parameter without annotations is empty. The dex file can list fewer
+    /// parameters than the method has, javac omits the synthetic leading parameters of
+    /// inner class and enum constructors, so the listed ones are taken to be the last.
+    pub fn parameter_annotations(&self) -> Vec<&[AnnotationItem]> {
+        let count = self.params().len();
+        let listed = self.param_annotations.len().min(count);
+        let mut annotations = vec![&[][..]; count - listed];
+        annotations.extend(
+            self.param_annotations
+                .iter()
+                .take(listed)
+                .map(|set| set.as_slice()),
+        );
+        annotations
+    }
+
     /// Returns the value of `dalvik.annotation.Signature`.
     pub fn signature(&self) -> super::Result<Option<String>> {
         utils::get_signature(self.annotations())
     }
 
-    /// Code and DebugInfo of the method.
+    /// Returns the exceptions declared in the `throws` clause of the method, taken from
+    /// the `dalvik.annotation.Throws` annotation. The list is empty if the method
+    /// declares no exceptions.
+    pub fn throws(&self) -> super::Result<Vec<Type>> {
+        let item = match self
+            .annotations()
+            .find_annotation("Ldalvik/annotation/Throws;")
+        {
+            Some(item) => item,
+            None => return Ok(Vec::new()),
+        };
+        match item.annotation().find_element("value").map(|e| e.value()) {
+            Some(EncodedValue::Array(values)) => values
+                .iter()
+                .map(|value| match value {
+                    EncodedValue::Type(jtype) => Ok(jtype.clone()),
+                    other => Err(Error::MalFormed(format!(
+                        "Expected exception type, found: {:?}",
+                        other
+                    ))),
+                })
+                .collect(),
+            other => Err(Error::MalFormed(format!(
+                "Expected array of exception types, found: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Returns both the exceptions the method declares to throw, see `throws`, and the
+    /// ones caught by its try blocks.
+    pub fn exception_footprint(&self) -> super::Result<ExceptionFootprint> {
+        let mut footprint = ExceptionFootprint {
+            declared: self.throws()?,
+            ..Default::default()
+        };
+        let handlers = self
+            .code()
+            .into_iter()
+            .flat_map(|code| code.tries().iter())
+            .flat_map(|try_block| try_block.catch_handlers().iter());
+        for handler in handlers {
+            match handler.exception() {
+                ExceptionType::BaseException => footprint.catches_all = true,
+                ExceptionType::Ty(jtype) => {
+                    if !footprint.caught.contains(jtype) {
+                        footprint.caught.push(jtype.clone());
+                    }
+                }
+            }
+        }
+        Ok(footprint)
+    }
+
+    /// Returns the declaration of the method in Java source form, e.g.
+    /// `public void onCreate(android.os.Bundle)`. Generic types are taken from the
+    /// `dalvik.annotation.Signature` annotation when the method has one, and the
+    /// `throws` clause from `throws`. Constructors are named after their class and
+    /// static initializers read `static {}`.
+    pub fn to_java_declaration(&self) -> super::Result<String> {
+        if self.name == "<clinit>" {
+            return Ok("static {}".to_string());
+        }
+        let signature = match self.signature()? {
+            Some(signature) => Some(GenericSignature::parse(&signature).ok_or_else(|| {
+                Error::MalFormed(format!("Invalid generic signature: {}", signature))
+            })?),
+            None => None,
+        };
+        let (type_params, mut params, return_type, mut throws) = match signature {
+            Some(signature) => (
+                signature.type_params,
+                signature.params,
+                signature.return_type,
+                signature.throws,
+            ),
+            None => (
+                Vec::new(),
+                self.params().iter().map(Type::to_java_type).collect(),
+                self.return_type().to_java_type(),
+                Vec::new(),
+            ),
+        };
+        if throws.is_empty() {
+            throws = self.throws()?.iter().map(Type::to_java_type).collect();
+        }
+        if self.is_varargs() {
+            if let Some(last) = params.last_mut() {
+                if last.ends_with("[]") {
+                    last.truncate(last.len() - 2);
+                    last.push_str("...");
+                }
+            }
+        }
+
+        let mut declaration = self.access_flags.to_modifier_string();
+        let mut push = |part: &str| {
+            if !declaration.is_empty() {
+                declaration.push(' ');
+            }
+            declaration.push_str(part);
+        };
+        if !type_params.is_empty() {
+            push(&format!("<{}>", type_params.join(", ")));
+        }
+        if self.name == "<init>" {
+            let class = self.class.to_java_type();
+            push(class.rsplit(['.', '$']).next().unwrap_or(&class));
+        } else {
+            push(&return_type);
+            push(&self.name);
+        }
+        declaration.push_str(&format!("({})", params.join(", ")));
+        if !throws.is_empty() {
+            declaration.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        Ok(declaration)
+    }
+
+    /// Code and DebugInfo of the method. Always `None` for abstract and native methods.
     pub fn code(&self) -> Option<&CodeItem> {
         self.code.as_ref()
     }
+
+    /// Returns `true` if one of the try blocks of the method has a catch-all handler,
+    /// as generated for `finally` blocks and `synchronized` statements.
+    pub fn has_finally(&self) -> bool {
+        self.code().is_some_and(|code| {
+            code.tries()
+                .iter()
+                .any(|try_block| try_block.catch_all_addr().is_some())
+        })
+    }
+
+    /// Returns `true` if a virtual method with the same name and prototype is defined
+    /// in one of the super classes of this method's class. Only the classes defined in
+    /// `dex` are searched: the search stops at the first super class defined elsewhere,
+    /// e.g. in the framework, and `false` is returned if no match was found until then.
+    pub fn is_override<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<bool> {
+        if self.is_static() || self.is_private() || self.is_constructor() {
+            return Ok(false);
+        }
+        let mut visited = HashSet::new();
+        let mut class_def = dex.find_class_def_by_type(self.class.id())?;
+        while let Some((_, def)) = class_def {
+            if def.superclass_idx == super::NO_INDEX {
+                break;
+            }
+            if !visited.insert(def.superclass_idx) {
+                return Err(Error::MalFormed(format!(
+                    "Cycle in the super classes of {}",
+                    self.class
+                )));
+            }
+            class_def = dex.find_class_def_by_type(def.superclass_idx)?;
+            if let Some((index, ref super_def)) = class_def {
+                let super_class = Class::try_from_dex(dex, super_def, index)?;
+                let overridden = super_class
+                    .virtual_methods()
+                    .iter()
+                    .any(|method| method.name == self.name && method.prototype == self.prototype);
+                if overridden {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Name of the source file of the class declaring this method, `None` if the
+    /// class isn't defined in `dex` or if it has no source file information.
+    pub fn source_file<S: AsRef<[u8]>>(
+        &self,
+        dex: &super::Dex<S>,
+    ) -> super::Result<Option<DexString>> {
+        match dex.find_class_def_by_type(self.class.id())? {
+            Some((_, def)) => dex.get_source_file(def.source_file_idx),
+            None => Ok(None),
+        }
+    }
+
+    /// Shorty character of the return type of the method. See `ProtoIdItem::return_shorty`.
+    pub fn return_shorty(&self) -> char {
+        first_char(&self.prototype.shorty)
+    }
+
+    /// Returns `true` if the return type of the method is `void`.
+    pub fn returns_void(&self) -> bool {
+        self.return_shorty() == 'V'
+    }
+
+    /// Returns `true` if the control flow graphs of the methods have the same shape, see
+    /// `ControlFlowGraph::same_shape` for the approximation used. Register allocation and
+    /// the indices into the pools of the dex files are ignored. Two methods without code
+    /// are considered isomorphic.
+    pub fn cfg_isomorphic(&self, other: &Method) -> super::Result<bool> {
+        match (self.code(), other.code()) {
+            (Some(code), Some(other_code)) => Ok(code.cfg()?.same_shape(&other_code.cfg()?)),
+            (None, None) => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    /// Peak number of registers holding a live value at the same time, see
+    /// `CodeItem::liveness`. Always `0` for methods without code.
+    pub fn max_registers_live(&self) -> super::Result<ushort> {
+        match self.code() {
+            // bounded by the registers size of the code
+            Some(code) => Ok(code.liveness()?.max_live() as ushort),
+            None => Ok(0),
+        }
+    }
+
+    /// Disassembles the code of the method, defined in `dex`, one smali line per
+    /// instruction prefixed with its address, along with the source line of the
+    /// instruction taken from the debug info. The operands are rendered as by
+    /// `to_smali`. The list is empty if the method has no code, and an error is
+    /// returned if the code can't be decoded.
+    pub fn disassemble_with_lines<S: AsRef<[u8]>>(
+        &self,
+        dex: &super::Dex<S>,
+    ) -> super::Result<Vec<(Option<uint>, String)>> {
+        let code = match self.code() {
+            Some(code) => code,
+            None => return Ok(Vec::new()),
+        };
+        let debug_info = code.debug_info_item();
+        Ok(crate::smali::instruction_lines(dex, code)?
+            .into_iter()
+            .map(|(address, line)| {
+                let source_line = debug_info.and_then(|d| d.line_for_address(address));
+                (source_line, format!("{:04x}: {}", address, line))
+            })
+            .collect())
+    }
+
+    /// Renders the method in the smali syntax of baksmali: the `.method` declaration
+    /// with its access flags, name and prototype, then the register count and the
+    /// instructions of the code, with the strings, types, fields and methods they
+    /// reference resolved in `dex`. Registers are named `vN` and branch targets are
+    /// labelled by address, e.g. `:addr_1a`.
+    pub fn to_smali<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<String> {
+        crate::smali::method_to_smali(dex, self)
+    }
+
+    /// Addresses of the instructions of the method reachable from its entry, see
+    /// `CodeItem::reachable_instructions`. The set is empty if the method has no code.
+    pub fn reachable_instructions(&self) -> super::Result<BTreeSet<usize>> {
+        self.code()
+            .map_or_else(|| Ok(BTreeSet::new()), CodeItem::reachable_instructions)
+    }
+
+    /// Distinct opcodes used by the code of the method, `dex` being the dex the method
+    /// was parsed from. The payloads of the switch and `fill-array-data` instructions
+    /// count as `nop`, which is their first byte. The set is empty if the method has no
+    /// code, and an error is returned if the code can't be decoded.
+    pub fn opcode_set<S: AsRef<[u8]>>(
+        &self,
+        _dex: &super::Dex<S>,
+    ) -> super::Result<HashSet<Opcode>> {
+        let code = match self.code() {
+            Some(code) => code,
+            None => return Ok(HashSet::new()),
+        };
+        code.instructions()
+            .map(|instruction| Ok((code.insns()[instruction?.0] & 0xff) as Opcode))
+            .collect()
+    }
+
+    /// Names of the parameters of the method, taken from the debug info. A parameter
+    /// without a name in the debug info is `None`. The list is empty if the method
+    /// has no debug info.
+    pub fn parameter_names(&self) -> Vec<Option<DexString>> {
+        let names = match self.code().and_then(|code| code.debug_info_item()) {
+            Some(debug_info) => debug_info.parameter_names(),
+            None => return Vec::new(),
+        };
+        (0..self.params().len())
+            .map(|i| names.get(i).cloned().flatten())
+            .collect()
+    }
+}
+
+/// Generic signature of a method, as found in its `dalvik.annotation.Signature`
+/// annotation, with the types rendered in Java source form.
+struct GenericSignature {
+    type_params: Vec<String>,
+    params: Vec<String>,
+    return_type: String,
+    throws: Vec<String>,
+}
+
+impl GenericSignature {
+    /// Parses a method signature as described in the `MethodSignature` section of
+    /// the JVM specification. Returns `None` if the signature is malformed.
+    fn parse(signature: &str) -> Option<Self> {
+        let mut parser = SignatureParser {
+            signature: signature.as_bytes(),
+            pos: 0,
+        };
+        let type_params = if parser.peek() == Some(b'<') {
+            parser.type_params()?
+        } else {
+            Vec::new()
+        };
+        parser.expect(b'(')?;
+        let mut params = Vec::new();
+        while parser.peek()? != b')' {
+            params.push(parser.java_type()?);
+        }
+        parser.expect(b')')?;
+        let return_type = parser.java_type()?;
+        let mut throws = Vec::new();
+        while parser.peek() == Some(b'^') {
+            parser.pos += 1;
+            throws.push(parser.java_type()?);
+        }
+        if parser.pos != signature.len() {
+            return None;
+        }
+        Some(Self {
+            type_params,
+            params,
+            return_type,
+            throws,
+        })
+    }
+}
+
+struct SignatureParser<'a> {
+    signature: &'a [u8],
+    pos: usize,
+}
+
+impl SignatureParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.signature.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.next()? == c {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Reads an identifier, or a class name with its package, up to a delimiter.
+    fn identifier(&mut self) -> Option<String> {
+        let start = self.pos;
+        while !matches!(self.peek()?, b';' | b'<' | b'>' | b':' | b'.') {
+            self.pos += 1;
+        }
+        let identifier = core::str::from_utf8(&self.signature[start..self.pos]).ok()?;
+        if identifier.is_empty() {
+            return None;
+        }
+        Some(identifier.replace('/', "."))
+    }
+
+    /// `<T extends java.lang.Number, U>` without the angle brackets. Bounds on
+    /// `java.lang.Object` only are omitted.
+    fn type_params(&mut self) -> Option<Vec<String>> {
+        self.expect(b'<')?;
+        let mut type_params = Vec::new();
+        while self.peek()? != b'>' {
+            let name = self.identifier()?;
+            let mut bounds = Vec::new();
+            while self.peek()? == b':' {
+                self.pos += 1;
+                // the class bound is empty if the type only has interface bounds.
+                if !matches!(self.peek()?, b':' | b'>') {
+                    bounds.push(self.java_type()?);
+                }
+            }
+            bounds.retain(|bound| bound != "java.lang.Object");
+            if bounds.is_empty() {
+                type_params.push(name);
+            } else {
+                type_params.push(format!("{} extends {}", name, bounds.join(" & ")));
+            }
+        }
+        self.expect(b'>')?;
+        Some(type_params)
+    }
+
+    fn type_args(&mut self) -> Option<String> {
+        self.expect(b'<')?;
+        let mut type_args = Vec::new();
+        while self.peek()? != b'>' {
+            let type_arg = match self.peek()? {
+                b'*' => {
+                    self.pos += 1;
+                    "?".to_string()
+                }
+                b'+' => {
+                    self.pos += 1;
+                    format!("? extends {}", self.java_type()?)
+                }
+                b'-' => {
+                    self.pos += 1;
+                    format!("? super {}", self.java_type()?)
+                }
+                _ => self.java_type()?,
+            };
+            type_args.push(type_arg);
+        }
+        self.expect(b'>')?;
+        Some(format!("<{}>", type_args.join(", ")))
+    }
+
+    fn java_type(&mut self) -> Option<String> {
+        let java_type = match self.next()? {
+            b'Z' => "boolean".to_string(),
+            b'B' => "byte".to_string(),
+            b'S' => "short".to_string(),
+            b'C' => "char".to_string(),
+            b'I' => "int".to_string(),
+            b'J' => "long".to_string(),
+            b'F' => "float".to_string(),
+            b'D' => "double".to_string(),
+            b'V' => "void".to_string(),
+            b'[' => format!("{}[]", self.java_type()?),
+            b'T' => {
+                let name = self.identifier()?;
+                self.expect(b';')?;
+                name
+            }
+            b'L' => {
+                let mut name = self.identifier()?;
+                loop {
+                    if self.peek()? == b'<' {
+                        name.push_str(&self.type_args()?);
+                    }
+                    if self.peek()? != b'.' {
+                        break;
+                    }
+                    self.pos += 1;
+                    name.push('.');
+                    name.push_str(&self.identifier()?);
+                }
+                self.expect(b';')?;
+                name
+            }
+            _ => return None,
+        };
+        Some(java_type)
+    }
+}
+
+/// First character of a non-empty shorty descriptor.
+fn first_char(shorty: &str) -> char {
+    shorty.as_bytes()[0] as char
 }
 
 /// Index into the `ProtoId`s list.
@@ -99,7 +684,7 @@ pub type ProtoId = ulong;
 
 /// Method Prototypes.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#proto-id-item)
-#[derive(Pread, Debug, CopyGetters, PartialEq)]
+#[derive(Pread, Debug, Clone, CopyGetters, PartialEq)]
 #[get_copy = "pub"]
 pub struct ProtoIdItem {
     /// Index into the string_ids list for the short-form descriptor string of this prototype
@@ -112,6 +697,24 @@ pub struct ProtoIdItem {
 }
 
 impl ProtoIdItem {
+    /// Returns the shorty character of the return type of this prototype, e.g. `V`
+    /// for `void` and `L` for any reference type.
+    pub fn return_shorty<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<char> {
+        Ok(first_char(&self.get_shorty(dex)?))
+    }
+
+    /// Returns the shorty descriptor, which must contain at least the return type.
+    fn get_shorty<S: AsRef<[u8]>>(&self, dex: &super::Dex<S>) -> super::Result<DexString> {
+        let shorty = dex.get_string(self.shorty)?;
+        if shorty.is_empty() {
+            return Err(Error::MalFormed(format!(
+                "Empty shorty descriptor for proto_item: {:?}",
+                self
+            )));
+        }
+        Ok(shorty)
+    }
+
     pub(crate) fn try_from_dex<S: AsRef<[u8]>>(
         dex: &super::Dex<S>,
         offset: ulong,
@@ -127,58 +730,86 @@ impl Method {
         encoded_method: &EncodedMethod,
         annotations: AnnotationSetItem,
         param_annotations: AnnotationSetRefList,
+        warnings: &mut Vec<ParseWarning>,
     ) -> super::Result<Method> {
         debug!(target: "method", "encoded method: {:?}", encoded_method);
-        let source = &dex.source;
         let method_item = dex.get_method_item(encoded_method.method_id)?;
         let name = dex.get_string(method_item.name_idx)?;
         debug!(target: "method", "name: {}, method id item: {:?}", name, method_item);
         let proto_item = dex.get_proto_item(ProtoId::from(method_item.proto_idx))?;
         debug!(target: "method", "method proto_item: {:?}", proto_item);
-        let shorty = dex.get_string(proto_item.shorty)?;
-        let return_type = dex.get_type(proto_item.return_type)?;
-        let params = if proto_item.params_off != 0 {
-            if !dex.is_offset_in_data_section(proto_item.params_off) {
-                return Err(Error::BadOffset(
-                    proto_item.params_off as usize,
-                    format!(
-                        "Params offset not in data section for proto_item: {:?}",
-                        proto_item
-                    ),
-                ));
+        let prototype = Prototype::try_from_dex(dex, &proto_item)?;
+        debug!(target: "method", "code item offset: {}", encoded_method.code_offset);
+        let access_flags = match AccessFlags::from_bits(encoded_method.access_flags) {
+            Some(access_flags) => access_flags,
+            None if dex.lenient => {
+                warnings.push(ParseWarning::UnknownMethodAccessFlags {
+                    method_id: encoded_method.method_id,
+                    access_flags: encoded_method.access_flags,
+                });
+                AccessFlags::from_bits_truncate(encoded_method.access_flags)
+            }
+            None => {
+                return Err(Error::InvalidAccessFlags {
+                    class_idx: ClassId::from(method_item.class_idx),
+                    owner: FlagsOwner::Method(encoded_method.method_id),
+                    raw: encoded_method.access_flags,
+                })
             }
-            let offset = &mut (proto_item.params_off as usize);
-            let endian = dex.get_endian();
-            let len = source.gread_with::<uint>(offset, endian)?;
-            let type_ids: Vec<ushort> = try_gread_vec_with!(source, offset, len, endian);
-            utils::get_types(dex, &type_ids)?
-        } else {
-            Default::default()
         };
-        debug!(target: "method", "code item offset: {}", encoded_method.code_offset);
-        let code = dex.get_code_item(encoded_method.code_offset)?;
+        if access_flags.intersects(AccessFlags::ABSTRACT | AccessFlags::NATIVE)
+            && encoded_method.code_offset != 0
+        {
+            return Err(Error::MalFormed(format!(
+                "Abstract or native method {} has code at offset {}",
+                name, encoded_method.code_offset
+            )));
+        }
+        let class = dex.get_type(TypeId::from(method_item.class_idx))?;
+        let arguments = Arguments {
+            this: if access_flags.contains(AccessFlags::STATIC) {
+                None
+            } else {
+                Some(&class)
+            },
+            parameters: prototype.parameters(),
+        };
+        let code = dex.code_item_at(encoded_method.code_offset, Some(arguments))?;
+        let dropped_debug_info = dex.lenient
+            && code
+                .as_ref()
+                .is_some_and(|code| code.debug_info_item().is_none());
+        if dropped_debug_info {
+            // unless debug_info_off is 0, the debug info couldn't be read.
+            let debug_info_off: uint = dex.source.as_ref().pread_with(
+                encoded_method.code_offset as usize + DEBUG_INFO_OFFSET,
+                dex.get_endian(),
+            )?;
+            if debug_info_off != 0 {
+                warnings.push(ParseWarning::InvalidDebugInfo {
+                    method_id: encoded_method.method_id,
+                    debug_info_off,
+                });
+            }
+        }
         Ok(Self {
+            id: encoded_method.method_id,
             name,
-            class: dex.get_type(TypeId::from(method_item.class_idx))?,
-            access_flags: AccessFlags::from_bits(encoded_method.access_flags).ok_or_else(|| {
-                Error::InvalidId(format!(
-                    "Invalid access flags for method {}",
-                    method_item.name_idx
-                ))
-            })?,
-            shorty,
-            return_type,
-            params,
+            class,
+            access_flags,
+            prototype,
+            code_offset: encoded_method.code_offset,
             code,
             annotations,
             param_annotations,
+            hidden_api_flags: None,
         })
     }
 }
 
 /// Method identifier.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#method-id-item)
-#[derive(Pread, Debug, CopyGetters, PartialEq)]
+#[derive(Pread, Debug, Clone, CopyGetters, PartialEq)]
 #[get_copy = "pub"]
 pub struct MethodIdItem {
     /// Index into the `TypeId`s list for the definer of this method.
@@ -199,6 +830,53 @@ impl MethodIdItem {
     }
 }
 
+/// Reference to a method by its `MethodIdItem`, e.g. from an invoke instruction.
+/// Unlike a `Method`, the method doesn't need to be defined in the dex, so there are
+/// no access flags or code. Displays as the signature accepted by
+/// `Dex::find_method_by_signature`, e.g. `Lcom/example/Foo;->bar(I)V`.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct MethodRef {
+    /// Index into the `MethodId`s list for this method.
+    #[get_copy = "pub"]
+    id: MethodId,
+    /// Class declaring the method.
+    #[get = "pub"]
+    class: Type,
+    /// Name of the method.
+    #[get = "pub"]
+    name: DexString,
+    /// Prototype of the method.
+    #[get = "pub"]
+    prototype: Prototype,
+}
+
+impl MethodRef {
+    pub(crate) fn try_from_dex<S: AsRef<[u8]>>(
+        dex: &super::Dex<S>,
+        method_id: MethodId,
+    ) -> super::Result<Self> {
+        let method_item = dex.get_method_item(method_id)?;
+        Ok(Self {
+            id: method_id,
+            class: dex.get_type(TypeId::from(method_item.class_idx))?,
+            name: dex.get_string(method_item.name_idx)?,
+            prototype: dex.get_prototype(ProtoId::from(method_item.proto_idx))?,
+        })
+    }
+}
+
+impl fmt::Display for MethodRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}->{}{}",
+            self.class,
+            self.name,
+            self.prototype.descriptor()
+        )
+    }
+}
+
 /// Index into the `MethodId`s list.
 pub type MethodId = ulong;
 
@@ -219,7 +897,7 @@ pub struct EncodedMethod {
     /// Offset from the start of the file to the code structure for this method, or `0` if this
     /// method is either abstract or native.  The format of the data is specified by `CodeItem`.
     #[get = "pub"]
-    code_offset: ulong,
+    code_offset: uint,
 }
 
 impl EncodedItem for EncodedMethod {
@@ -240,6 +918,8 @@ impl<'a> ctx::TryFromCtx<'a, ulong> for EncodedMethod {
         let id = Uleb128::read(source, offset)?;
         let access_flags = Uleb128::read(source, offset)?;
         let code_offset = Uleb128::read(source, offset)?;
+        let code_offset = uint::try_from(code_offset)
+            .map_err(|_| Error::MalFormed(format!("Invalid code offset: {}", code_offset)))?;
         Ok((
             Self {
                 method_id: prev_id + id,
@@ -274,7 +954,7 @@ pub enum FieldOrMethodId {
 
 /// A method handle.
 /// [Android docs](https://source.android.com/devices/tech/dalvik/dex-format#method-handle-item)
-#[derive(Debug, CopyGetters, PartialEq)]
+#[derive(Debug, Clone, CopyGetters, PartialEq)]
 #[get_copy = "pub"]
 pub struct MethodHandleItem {
     ///  The type of this MethodHandleItem.
@@ -308,3 +988,176 @@ impl<'a, S: AsRef<[u8]>> ctx::TryFromCtx<'a, &super::Dex<S>> for MethodHandleIte
         Ok((Self { handle_type, id }, *offset))
     }
 }
+
+/// Constraints on the methods returned by `Dex::find_methods`, e.g. the public
+/// methods named `decrypt` returning `[B`. A constraint which isn't set matches every
+/// method.
+#[derive(Debug, Clone, Default)]
+pub struct MethodPattern {
+    name: Option<String>,
+    return_type: Option<String>,
+    params: Option<Vec<String>>,
+    access_flags: Option<AccessFlags>,
+}
+
+impl MethodPattern {
+    /// Returns a pattern matching every method.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches the methods named `pattern`, in which `*` matches any sequence of
+    /// characters, e.g. `get*`.
+    pub fn name(mut self, pattern: &str) -> Self {
+        self.name = Some(pattern.to_string());
+        self
+    }
+
+    /// Matches the methods returning the type with the given descriptor, e.g. `[B`.
+    pub fn return_type(mut self, descriptor: &str) -> Self {
+        self.return_type = Some(descriptor.to_string());
+        self
+    }
+
+    /// Matches the methods whose parameters have the given descriptors, in which `*`
+    /// matches any single parameter and a last `..` matches any remaining parameters,
+    /// e.g. `["I", "*", ".."]` for the methods with at least two parameters, the first
+    /// being an `int`.
+    pub fn params(mut self, params: &[&str]) -> Self {
+        self.params = Some(params.iter().map(|param| param.to_string()).collect());
+        self
+    }
+
+    /// Matches the methods with all the given access flags set.
+    pub fn access_flags(mut self, access_flags: AccessFlags) -> Self {
+        self.access_flags = Some(access_flags);
+        self
+    }
+
+    /// Checks that `..` is only used as the last parameter.
+    pub(crate) fn validate(&self) -> super::Result<()> {
+        let params = self.params.as_deref().unwrap_or_default();
+        match params.iter().position(|param| param == "..") {
+            Some(position) if position + 1 != params.len() => Err(Error::InvalidDescriptor(
+                format!("`..` is not the last parameter of pattern: {:?}", params),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the method satisfies every constraint of the pattern.
+    pub fn matches(&self, method: &Method) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|pattern| glob_matches(pattern, method.name()))
+            && self
+                .return_type
+                .as_ref()
+                .is_none_or(|descriptor| method.return_type() == descriptor.as_str())
+            && self
+                .params
+                .as_ref()
+                .is_none_or(|params| params_match(params, method.params()))
+            && self
+                .access_flags
+                .is_none_or(|flags| method.access_flags().contains(flags))
+    }
+}
+
+/// Matches `text` against `pattern`, in which `*` matches any sequence of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let last = match parts.split_last() {
+        Some((last, middle)) => {
+            // each part between two `*` matches at its first occurrence.
+            for part in middle {
+                match rest.find(part) {
+                    Some(index) => rest = &rest[index + part.len()..],
+                    None => return false,
+                }
+            }
+            last
+        }
+        // no `*` in the pattern
+        None => return rest.is_empty(),
+    };
+    rest.ends_with(last)
+}
+
+fn params_match(patterns: &[String], params: &[Type]) -> bool {
+    match patterns.split_first() {
+        Some((pattern, _)) if pattern == ".." => true,
+        Some((pattern, patterns)) => match params.split_first() {
+            Some((param, params)) => {
+                (pattern == "*" || param == pattern.as_str()) && params_match(patterns, params)
+            }
+            None => false,
+        },
+        None => params.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_matches, GenericSignature};
+
+    #[test]
+    fn test_glob_matches() {
+        for (pattern, text) in &[
+            ("decrypt", "decrypt"),
+            ("*", ""),
+            ("get*", "getName"),
+            ("*Name", "getName"),
+            ("g*t*e", "getName"),
+            ("a*a", "aa"),
+            ("**", "x"),
+        ] {
+            assert!(glob_matches(pattern, text), "{} {}", pattern, text);
+        }
+        for (pattern, text) in &[
+            ("decrypt", "decrypt2"),
+            ("get*", "setName"),
+            ("*Name", "getNames"),
+            ("a*a", "a"),
+            ("g*z*e", "getName"),
+        ] {
+            assert!(!glob_matches(pattern, text), "{} {}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn test_generic_signature() {
+        let signature = GenericSignature::parse(
+            "<K:Ljava/lang/Object;V::Ljava/lang/Comparable<-TV;>;:Ljava/io/Serializable;>\
+             (Ljava/util/Map<+TK;*>;[[TV;La/Outer<TK;>.Inner<TV;>;)TV;^Ljava/io/IOException;^TE;",
+        )
+        .expect("bad signature");
+        assert_eq!(
+            signature.type_params,
+            vec![
+                "K",
+                "V extends java.lang.Comparable<? super V> & java.io.Serializable"
+            ]
+        );
+        assert_eq!(
+            signature.params,
+            vec![
+                "java.util.Map<? extends K, ?>",
+                "V[][]",
+                "a.Outer<K>.Inner<V>"
+            ]
+        );
+        assert_eq!(signature.return_type, "V");
+        assert_eq!(signature.throws, vec!["java.io.IOException", "E"]);
+
+        assert!(GenericSignature::parse("(I)").is_none());
+        assert!(GenericSignature::parse("(Ljava/lang/String)V").is_none());
+        assert!(GenericSignature::parse("()VI").is_none());
+    }
+}
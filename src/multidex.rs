@@ -0,0 +1,186 @@
+//! Applications split across several dex files (`classes.dex`, `classes2.dex`, ...).
+//!
+//! The ids of a dex file, e.g. the `TypeId`s of a `Class`, only make sense in that
+//! dex file, so the classes are always returned along with the `Dex` defining them.
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "apk")]
+use std::{fs::File, io::BufReader};
+
+use getset::Getters;
+#[cfg(feature = "std")]
+use memmap2::Mmap;
+
+#[cfg(feature = "std")]
+use crate::DexReader;
+#[cfg(feature = "apk")]
+use crate::{apk, ubyte};
+use crate::{class::Class, Dex, Result};
+
+/// The dex files of an application, in class loading order.
+#[derive(Getters)]
+pub struct MultiDex<T> {
+    /// The dex files, the primary `classes.dex` first.
+    #[get = "pub"]
+    dex_files: Vec<Dex<T>>,
+}
+
+#[cfg(feature = "std")]
+impl MultiDex<Mmap> {
+    /// Loads `classes.dex`, `classes2.dex`, `classes3.dex`, ... from the directory,
+    /// stopping at the first missing file. Returns an error if there is no
+    /// `classes.dex`.
+    pub fn from_directory<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut dex_files = vec![DexReader::from_file(path.join("classes.dex"))?];
+        loop {
+            let file = path.join(format!("classes{}.dex", dex_files.len() + 1));
+            if !file.is_file() {
+                break;
+            }
+            dex_files.push(DexReader::from_file(file)?);
+        }
+        Ok(Self { dex_files })
+    }
+}
+
+#[cfg(feature = "apk")]
+impl MultiDex<Vec<ubyte>> {
+    /// Loads the `classes.dex`, `classes2.dex`, ... entries of the APK at the given
+    /// path into memory, ignoring the other entries. Returns `Error::MissingDex` if
+    /// the APK has no dex file.
+    pub fn from_apk<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let dex_files = apk::dex_entries(BufReader::new(File::open(path)?))?
+            .into_iter()
+            .map(|data| DexReader::new().read(data))
+            .collect::<Result<_>>()?;
+        Ok(Self { dex_files })
+    }
+}
+
+impl<T> MultiDex<T>
+where
+    T: AsRef<[u8]>,
+{
+    /// Creates a `MultiDex` from dex files in class loading order.
+    pub fn new(dex_files: Vec<Dex<T>>) -> Self {
+        Self { dex_files }
+    }
+
+    /// Iterator over the classes of all the dex files, along with the dex file
+    /// defining each of them.
+    pub fn classes(&self) -> impl Iterator<Item = Result<(&Dex<T>, Class)>> + '_ {
+        self.dex_files
+            .iter()
+            .flat_map(|dex| dex.classes().map(move |class| Ok((dex, class?))))
+    }
+
+    /// Finds the class with the given name, see `Dex::find_class_by_name`, along with
+    /// the dex file defining it. The dex files are searched in order, so the first
+    /// definition of a class defined more than once is returned, as the runtime does.
+    pub fn find_class_by_name(&self, name: &str) -> Result<Option<(&Dex<T>, Class)>> {
+        for dex in &self.dex_files {
+            if let Some(class) = dex.find_class_by_name(name)? {
+                return Ok(Some((dex, class)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::MultiDex;
+    use crate::DexReader;
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_directory() {
+        let dir = tempfile::tempdir().expect("cannot create temporary directory");
+        assert!(MultiDex::from_directory(dir.path()).is_err());
+        for name in &["classes.dex", "classes2.dex", "classes4.dex"] {
+            fs::copy("resources/classes.dex", dir.path().join(name)).expect("cannot copy dex");
+        }
+        let multi_dex = MultiDex::from_directory(dir.path()).expect("cannot load dex files");
+        assert_eq!(multi_dex.dex_files().len(), 2);
+
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(multi_dex.classes().count(), 2 * dex.classes().count());
+        for class in multi_dex.classes() {
+            let (class_dex, class) = class.expect("error parsing class");
+            assert_eq!(
+                class_dex.get_type(class.id()).expect("bad type"),
+                *class.jtype()
+            );
+        }
+
+        let (class_dex, class) = multi_dex
+            .find_class_by_name("com.devoteam.quickaction.QuickActionItem")
+            .expect("error finding class")
+            .expect("class not found");
+        assert!(std::ptr::eq(class_dex, &multi_dex.dex_files()[0]));
+        assert_eq!(
+            class.jtype().type_descriptor(),
+            "Lcom/devoteam/quickaction/QuickActionItem;"
+        );
+        assert!(multi_dex
+            .find_class_by_name("Lcom/example/Missing;")
+            .expect("error finding class")
+            .is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_from_apk() {
+        use std::io::Write;
+
+        let contents = fs::read("resources/classes.dex").expect("cannot read dex file");
+        let apk = crate::apk::tests::zip(&[
+            ("AndroidManifest.xml", b"manifest", true),
+            ("classes2.dex", &contents, true),
+            ("classes.dex", &contents, false),
+        ]);
+        let mut file = tempfile::NamedTempFile::new().expect("cannot create temporary file");
+        file.write_all(&apk).expect("cannot write APK");
+        let multi_dex = MultiDex::from_apk(file.path()).expect("cannot load APK");
+        assert_eq!(multi_dex.dex_files().len(), 2);
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        assert_eq!(multi_dex.classes().count(), 2 * dex.classes().count());
+        let multi_dex = crate::Dex::from_apk(file.path()).expect("cannot load APK");
+        assert_eq!(multi_dex.dex_files().len(), 2);
+
+        let apk = crate::apk::tests::zip(&[("AndroidManifest.xml", b"manifest", false)]);
+        let mut file = tempfile::NamedTempFile::new().expect("cannot create temporary file");
+        file.write_all(&apk).expect("cannot write APK");
+        assert!(matches!(
+            MultiDex::from_apk(file.path()),
+            Err(crate::Error::MissingDex)
+        ));
+    }
+
+    #[test]
+    fn test_scoped_lookups() {
+        let contents = fs::read("resources/classes.dex").expect("cannot read dex file");
+        // A primary dex file without classes, referencing the same types.
+        let mut empty = contents.clone();
+        empty[0x60..0x64].copy_from_slice(&0u32.to_le_bytes());
+        crate::utils::update_checksums(&mut empty, scroll::LE).expect("cannot update checksums");
+        let multi_dex = MultiDex::new(vec![
+            DexReader::new().read(empty).expect("cannot read dex"),
+            DexReader::new().read(contents).expect("cannot read dex"),
+        ]);
+        assert_eq!(
+            multi_dex.classes().count(),
+            multi_dex.dex_files()[1].classes().count()
+        );
+        let (class_dex, class) = multi_dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        assert!(std::ptr::eq(class_dex, &multi_dex.dex_files()[1]));
+        assert!(class.super_class().is_some());
+    }
+}
@@ -0,0 +1,367 @@
+//! Reader for ART profiles (`.prof` files) generated by the runtime or by
+//! baseline profile tooling. Only version `010` (Android P and later) is supported.
+use std::{collections::BTreeSet, io::Read};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use flate2::read::ZlibDecoder;
+use getset::{CopyGetters, Getters};
+use scroll::{Pread, LE};
+
+use crate::{error::Error, jtype::TypeId, method::MethodId, ubyte, uint, ushort, Result};
+
+const MAGIC: &[ubyte] = b"pro\0";
+const VERSION: &[ubyte] = b"010\0";
+
+/// Largest number of bytes allocated up front for the decompressed data, which
+/// otherwise grows as it is decompressed, since its size is read from the file.
+const MAX_PREALLOCATED_SIZE: usize = 1 << 20;
+
+/// Inline cache entries with one of these sizes have no classes listed.
+const IS_MISSING_TYPES_ENCODING: ubyte = 6;
+const IS_MEGAMORPHIC_ENCODING: ubyte = 7;
+
+/// An ART profile.
+#[derive(Debug, Getters)]
+pub struct ArtProfile {
+    /// Profile data of each dex file in the profile.
+    #[get = "pub"]
+    dex_files: Vec<ProfileDexData>,
+}
+
+/// Profile data of a single dex file.
+#[derive(Debug, Getters, CopyGetters)]
+pub struct ProfileDexData {
+    /// Key identifying the dex file, usually its location in the APK.
+    #[get = "pub"]
+    profile_key: String,
+    /// Adler32 checksum of the dex file.
+    #[get_copy = "pub"]
+    checksum: uint,
+    /// Number of `MethodId`s in the dex file.
+    #[get_copy = "pub"]
+    num_method_ids: uint,
+    /// Methods which are hot and should be compiled.
+    #[get = "pub"]
+    hot_methods: BTreeSet<MethodId>,
+    /// Methods executed during the startup of the application.
+    #[get = "pub"]
+    startup_methods: BTreeSet<MethodId>,
+    /// Methods executed after the startup of the application.
+    #[get = "pub"]
+    post_startup_methods: BTreeSet<MethodId>,
+    /// Classes which are loaded during the startup of the application.
+    #[get = "pub"]
+    classes: BTreeSet<TypeId>,
+}
+
+impl ProfileDexData {
+    /// Returns all the methods present in the profile, whatever their flags.
+    pub fn methods(&self) -> BTreeSet<MethodId> {
+        self.hot_methods
+            .iter()
+            .chain(self.startup_methods.iter())
+            .chain(self.post_startup_methods.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Header of the data of a dex file in the profile.
+struct LineHeader {
+    profile_key: String,
+    class_set_size: ushort,
+    method_region_size: uint,
+    checksum: uint,
+    num_method_ids: uint,
+}
+
+impl ArtProfile {
+    /// Reads the profile at the given path.
+    #[cfg(feature = "std")]
+    pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Self> {
+        Self::from_bytes(&fs::read(file)?)
+    }
+
+    /// Parses a profile from its contents.
+    pub fn from_bytes(source: &[ubyte]) -> Result<Self> {
+        let offset = &mut 0;
+        let magic: &[ubyte] = source.gread_with(offset, MAGIC.len())?;
+        if magic != MAGIC {
+            return Err(Error::MalFormed("Invalid ART profile magic".to_string()));
+        }
+        let version: &[ubyte] = source.gread_with(offset, VERSION.len())?;
+        if version != VERSION {
+            return Err(Error::MalFormed(format!(
+                "Unsupported ART profile version: {:?}",
+                String::from_utf8_lossy(version)
+            )));
+        }
+        let num_dex_files: ubyte = source.gread(offset)?;
+        let uncompressed_size: uint = source.gread_with(offset, LE)?;
+        let compressed_size: uint = source.gread_with(offset, LE)?;
+        let compressed: &[ubyte] = source.gread_with(offset, compressed_size as usize)?;
+
+        // reading one byte more than the declared size detects larger data without
+        // decompressing all of it.
+        let mut data = Vec::with_capacity((uncompressed_size as usize).min(MAX_PREALLOCATED_SIZE));
+        ZlibDecoder::new(compressed)
+            .take(u64::from(uncompressed_size) + 1)
+            .read_to_end(&mut data)?;
+        if data.len() != uncompressed_size as usize {
+            return Err(Error::MalFormed(format!(
+                "ART profile data size mismatch: expected: {}, found: {}",
+                uncompressed_size,
+                data.len()
+            )));
+        }
+
+        let offset = &mut 0;
+        let headers = (0..num_dex_files)
+            .map(|_| read_line_header(&data, offset))
+            .collect::<Result<Vec<_>>>()?;
+        let dex_files = headers
+            .into_iter()
+            .map(|header| read_line(&data, offset, header))
+            .collect::<Result<_>>()?;
+        Ok(Self { dex_files })
+    }
+}
+
+fn read_line_header(source: &[ubyte], offset: &mut usize) -> Result<LineHeader> {
+    let profile_key_size: ushort = source.gread_with(offset, LE)?;
+    let class_set_size = source.gread_with(offset, LE)?;
+    let method_region_size = source.gread_with(offset, LE)?;
+    let checksum = source.gread_with(offset, LE)?;
+    let num_method_ids = source.gread_with(offset, LE)?;
+    let profile_key: &[ubyte] = source.gread_with(offset, profile_key_size as usize)?;
+    Ok(LineHeader {
+        profile_key: String::from_utf8_lossy(profile_key).into_owned(),
+        class_set_size,
+        method_region_size,
+        checksum,
+        num_method_ids,
+    })
+}
+
+fn read_line(source: &[ubyte], offset: &mut usize, header: LineHeader) -> Result<ProfileDexData> {
+    // hot methods, each followed by its inline caches.
+    let methods: &[ubyte] = source.gread_with(offset, header.method_region_size as usize)?;
+    let mut hot_methods = BTreeSet::new();
+    let (method_offset, mut method_idx) = (&mut 0, 0);
+    while *method_offset < methods.len() {
+        method_idx += MethodId::from(methods.gread_with::<ushort>(method_offset, LE)?);
+        hot_methods.insert(method_idx);
+        let inline_cache_size: ushort = methods.gread_with(method_offset, LE)?;
+        for _ in 0..inline_cache_size {
+            let _dex_pc: ushort = methods.gread_with(method_offset, LE)?;
+            let dex_to_classes_size: ubyte = methods.gread(method_offset)?;
+            if dex_to_classes_size == IS_MISSING_TYPES_ENCODING
+                || dex_to_classes_size == IS_MEGAMORPHIC_ENCODING
+            {
+                continue;
+            }
+            for _ in 0..dex_to_classes_size {
+                let _dex_profile_index: ubyte = methods.gread(method_offset)?;
+                let classes_size: ubyte = methods.gread(method_offset)?;
+                let _classes: &[ubyte] = methods.gread_with(
+                    method_offset,
+                    classes_size as usize * core::mem::size_of::<ushort>(),
+                )?;
+            }
+        }
+    }
+
+    let mut classes = BTreeSet::new();
+    let mut type_idx = 0;
+    for _ in 0..header.class_set_size {
+        type_idx += TypeId::from(source.gread_with::<ushort>(offset, LE)?);
+        classes.insert(type_idx);
+    }
+
+    // one bit per method for the startup flag followed by one bit per method
+    // for the post startup flag.
+    let num_method_ids = header.num_method_ids as usize;
+    let bitmap: &[ubyte] = source.gread_with(offset, (num_method_ids * 2).div_ceil(8))?;
+    let is_set = |bit: usize| bitmap[bit / 8] & (1 << (bit % 8)) != 0;
+    let startup_methods = (0..num_method_ids)
+        .filter(|method_idx| is_set(*method_idx))
+        .map(|method_idx| method_idx as MethodId)
+        .collect();
+    let post_startup_methods = (0..num_method_ids)
+        .filter(|method_idx| is_set(num_method_ids + *method_idx))
+        .map(|method_idx| method_idx as MethodId)
+        .collect();
+
+    Ok(ProfileDexData {
+        profile_key: header.profile_key,
+        checksum: header.checksum,
+        num_method_ids: header.num_method_ids,
+        hot_methods,
+        startup_methods,
+        post_startup_methods,
+        classes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::ArtProfile;
+
+    /// Encodes a profile with a single dex file.
+    fn encode_profile(
+        checksum: u32,
+        num_method_ids: u32,
+        hot_methods: &[u16],
+        startup_methods: &[usize],
+    ) -> Vec<u8> {
+        let key = b"classes.dex";
+        let mut methods = Vec::new();
+        let mut last = 0;
+        for (index, method) in hot_methods.iter().enumerate() {
+            methods.extend_from_slice(&(method - last).to_le_bytes());
+            last = *method;
+            if index == 0 {
+                // one megamorphic and one monomorphic inline cache
+                methods.extend_from_slice(&2u16.to_le_bytes());
+                methods.extend_from_slice(&[1, 0, 7]);
+                methods.extend_from_slice(&[2, 0, 1, 0, 1, 3, 0]);
+            } else {
+                methods.extend_from_slice(&0u16.to_le_bytes());
+            }
+        }
+        let mut data = Vec::new();
+        data.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&(methods.len() as u32).to_le_bytes());
+        data.extend_from_slice(&checksum.to_le_bytes());
+        data.extend_from_slice(&num_method_ids.to_le_bytes());
+        data.extend_from_slice(key);
+        data.extend_from_slice(&methods);
+        data.extend_from_slice(&5u16.to_le_bytes());
+        let mut bitmap = vec![0u8; (num_method_ids as usize * 2).div_ceil(8)];
+        for method in startup_methods {
+            bitmap[method / 8] |= 1 << (method % 8);
+        }
+        let post_startup = num_method_ids as usize;
+        bitmap[post_startup / 8] |= 1 << (post_startup % 8);
+        data.extend_from_slice(&bitmap);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).expect("cannot compress profile");
+        let compressed = encoder.finish().expect("cannot compress profile");
+        let mut profile = b"pro\0".to_vec();
+        profile.extend_from_slice(b"010\0");
+        profile.push(1);
+        profile.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        profile.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        profile.extend_from_slice(&compressed);
+        profile
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = ArtProfile::from_bytes(&encode_profile(42, 20, &[3, 7, 12], &[1, 19]))
+            .expect("cannot parse profile");
+        assert_eq!(profile.dex_files().len(), 1);
+        let dex_data = &profile.dex_files()[0];
+        assert_eq!(dex_data.profile_key(), "classes.dex");
+        assert_eq!(dex_data.checksum(), 42);
+        assert_eq!(dex_data.num_method_ids(), 20);
+        assert_eq!(
+            dex_data.hot_methods().iter().cloned().collect::<Vec<_>>(),
+            vec![3, 7, 12]
+        );
+        assert_eq!(
+            dex_data
+                .startup_methods()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![1, 19]
+        );
+        assert_eq!(
+            dex_data
+                .post_startup_methods()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(
+            dex_data.classes().iter().cloned().collect::<Vec<_>>(),
+            vec![5]
+        );
+        assert_eq!(
+            dex_data.methods().into_iter().collect::<Vec<_>>(),
+            vec![0, 1, 3, 7, 12, 19]
+        );
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let dex =
+            crate::DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let (checksum, num_method_ids) = (dex.header().checksum(), dex.header().method_ids_size());
+        let profile = ArtProfile::from_bytes(&encode_profile(checksum, num_method_ids, &[3], &[8]))
+            .expect("cannot parse profile");
+        let mut methods = dex
+            .apply_profile(&profile)
+            .expect("cannot apply profile")
+            .into_iter()
+            .collect::<Vec<_>>();
+        methods.sort();
+        assert_eq!(methods, vec![0, 3, 8]);
+
+        let profile = ArtProfile::from_bytes(&encode_profile(checksum + 1, 20, &[3], &[]))
+            .expect("cannot parse profile");
+        assert!(dex
+            .apply_profile(&profile)
+            .expect("cannot apply profile")
+            .is_empty());
+
+        let profile = ArtProfile::from_bytes(&encode_profile(checksum, 20, &[3], &[]))
+            .expect("cannot parse profile");
+        assert!(dex.apply_profile(&profile).is_err());
+    }
+
+    #[test]
+    fn test_invalid_profile() {
+        let mut profile = encode_profile(42, 20, &[3], &[]);
+        assert!(ArtProfile::from_bytes(&profile[..profile.len() - 1]).is_err());
+        profile[6] = b'5';
+        assert!(ArtProfile::from_bytes(&profile).is_err());
+        assert!(ArtProfile::from_bytes(b"dex\n035\0").is_err());
+    }
+
+    #[test]
+    fn test_profile_data_size() {
+        // the declared size of the data is larger than the data.
+        let mut profile = encode_profile(42, 20, &[3], &[]);
+        profile[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ArtProfile::from_bytes(&profile).is_err());
+
+        // the data is much larger than its declared size.
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(&vec![0; 16 << 20])
+            .expect("cannot compress data");
+        let compressed = encoder.finish().expect("cannot compress data");
+        let mut profile = b"pro\0".to_vec();
+        profile.extend_from_slice(b"010\0");
+        profile.push(1);
+        profile.extend_from_slice(&16u32.to_le_bytes());
+        profile.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        profile.extend_from_slice(&compressed);
+        match ArtProfile::from_bytes(&profile) {
+            Err(crate::Error::MalFormed(message)) => {
+                assert!(message.contains("expected: 16, found: 17"), "{}", message)
+            }
+            other => panic!("expected a size mismatch, got {:?}", other),
+        }
+    }
+}
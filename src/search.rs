@@ -1,6 +1,6 @@
 use crate::Result;
+use core::{cmp::Ordering, fmt::Debug};
 use scroll::{ctx, Pread};
-use std::{cmp::Ordering, fmt::Debug};
 
 pub(crate) struct Section<'a> {
     inner: &'a [u8],
@@ -21,7 +21,7 @@ impl<'a> Section<'a> {
         predicate: F,
     ) -> Result<Option<usize>>
     where
-        S: std::fmt::Debug,
+        S: core::fmt::Debug,
         F: Fn(&T, &S) -> Result<Ordering>,
         T: ctx::TryFromCtx<'a, C, Size = usize, Error = scroll::Error> + Debug,
     {
@@ -0,0 +1,163 @@
+//! `Serialize` implementations of the parsed structures, enabled by the `serde` feature.
+//!
+//! Indices into the sections of the dex file are resolved, so the output doesn't need
+//! the dex file to be interpreted. A `Class` serializes, e.g. in JSON, as:
+//!
+//! ```json
+//! {
+//!   "name": "Lcom/example/Foo;",
+//!   "access_flags": 1,
+//!   "super_class": "Ljava/lang/Object;",
+//!   "interfaces": ["Ljava/lang/Runnable;"],
+//!   "source_file": "Foo.java",
+//!   "static_fields": [],
+//!   "instance_fields": [{"name": "count", "type": "I", "access_flags": 2}],
+//!   "direct_methods": [{
+//!     "class": "Lcom/example/Foo;",
+//!     "name": "<init>",
+//!     "access_flags": 65537,
+//!     "shorty": "V",
+//!     "return_type": "V",
+//!     "params": []
+//!   }],
+//!   "virtual_methods": []
+//! }
+//! ```
+//!
+//! `Type`s serialize as their descriptors, `DexString`s as the decoded strings and
+//! access flags as their bits. `super_class` and `source_file` are `null` when absent.
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::{class, field, jtype::Type, method, string::DexString};
+
+impl Serialize for DexString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for Type {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.type_descriptor())
+    }
+}
+
+impl Serialize for class::AccessFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.bits())
+    }
+}
+
+impl Serialize for method::AccessFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.bits())
+    }
+}
+
+impl Serialize for field::AccessFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.bits())
+    }
+}
+
+impl Serialize for class::Class {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Class", 9)?;
+        state.serialize_field("name", self.jtype())?;
+        state.serialize_field("access_flags", &self.access_flags())?;
+        state.serialize_field("super_class", self.super_type())?;
+        state.serialize_field("interfaces", self.interfaces())?;
+        state.serialize_field("source_file", &self.source_file())?;
+        state.serialize_field("static_fields", self.static_fields())?;
+        state.serialize_field("instance_fields", self.instance_fields())?;
+        state.serialize_field("direct_methods", self.direct_methods())?;
+        state.serialize_field("virtual_methods", self.virtual_methods())?;
+        state.end()
+    }
+}
+
+impl Serialize for method::Method {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Method", 6)?;
+        state.serialize_field("class", self.class())?;
+        state.serialize_field("name", self.name())?;
+        state.serialize_field("access_flags", &self.access_flags())?;
+        state.serialize_field("shorty", self.shorty())?;
+        state.serialize_field("return_type", self.return_type())?;
+        state.serialize_field("params", self.params())?;
+        state.end()
+    }
+}
+
+impl Serialize for field::Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Field", 3)?;
+        state.serialize_field("name", self.name())?;
+        state.serialize_field("type", self.jtype())?;
+        state.serialize_field("access_flags", &self.access_flags())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::DexReader;
+
+    #[test]
+    fn test_serialize_class() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lcom/devoteam/quickaction/QuickActionItem;")
+            .expect("error finding class")
+            .expect("class not found");
+        let json = serde_json::to_value(&class).expect("cannot serialize class");
+        assert_eq!(json["name"], "Lcom/devoteam/quickaction/QuickActionItem;");
+        assert_eq!(json["access_flags"], 1);
+        assert_eq!(json["super_class"], "Landroid/widget/LinearLayout;");
+        assert_eq!(json["interfaces"], json!(["Landroid/widget/Checkable;"]));
+        assert_eq!(json["source_file"], "QuickActionItem.java");
+        assert_eq!(
+            json["static_fields"],
+            json!([{"name": "CHECKED_STATE_SET", "type": "[I", "access_flags": 26}])
+        );
+        assert_eq!(
+            json["instance_fields"],
+            json!([{"name": "mChecked", "type": "Z", "access_flags": 2}])
+        );
+        assert!(json["direct_methods"].is_array());
+        let set_text = json["virtual_methods"]
+            .as_array()
+            .expect("virtual_methods is not an array")
+            .iter()
+            .find(|method| method["name"] == "setText")
+            .expect("setText not found");
+        assert_eq!(
+            *set_text,
+            json!({
+                "class": "Lcom/devoteam/quickaction/QuickActionItem;",
+                "name": "setText",
+                "access_flags": 1,
+                "shorty": "VL",
+                "return_type": "V",
+                "params": ["Ljava/lang/String;"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_escaped_string() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let string = dex
+            .strings()
+            .map(|string| string.expect("error reading string"))
+            .find(|string| string.to_string().contains('"'))
+            .expect("no string with a quote");
+        let json = serde_json::to_string(&string).expect("cannot serialize string");
+        assert_eq!(
+            serde_json::from_str::<String>(&json).expect("invalid JSON string"),
+            string.to_string()
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Rendering of methods in the smali syntax used by baksmali, see `Method::to_smali`.
+//!
+//! Registers are always named `vN`, branch targets and payloads are labelled by
+//! their address, e.g. `:addr_1a`, and the strings, types, fields, methods and
+//! prototypes referenced by the instructions are resolved.
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::{
+    code::CodeItem,
+    disass,
+    field::FieldId,
+    instruction::Instruction,
+    method::{self, Method, MethodId, ProtoId},
+    Dex, Result,
+};
+
+/// Smali names of the method access flags, in the order baksmali writes them.
+const ACCESS_FLAGS: [(method::AccessFlags, &str); 14] = [
+    (method::AccessFlags::PUBLIC, "public"),
+    (method::AccessFlags::PRIVATE, "private"),
+    (method::AccessFlags::PROTECTED, "protected"),
+    (method::AccessFlags::STATIC, "static"),
+    (method::AccessFlags::FINAL, "final"),
+    (method::AccessFlags::SYNCHRONIZED, "synchronized"),
+    (method::AccessFlags::BRIDGE, "bridge"),
+    (method::AccessFlags::VARARGS, "varargs"),
+    (method::AccessFlags::NATIVE, "native"),
+    (method::AccessFlags::ABSTRACT, "abstract"),
+    (method::AccessFlags::STRICT, "strictfp"),
+    (method::AccessFlags::SYNTHETIC, "synthetic"),
+    (method::AccessFlags::CONSTRUCTOR, "constructor"),
+    (
+        method::AccessFlags::DECLARED_SYNCHRONIZED,
+        "declared-synchronized",
+    ),
+];
+
+/// Renders `method`, defined in `dex`, as a smali `.method` block.
+pub(crate) fn method_to_smali<S: AsRef<[u8]>>(dex: &Dex<S>, method: &Method) -> Result<String> {
+    let mut smali = String::from(".method ");
+    for (flag, name) in ACCESS_FLAGS.iter() {
+        if method.access_flags().contains(*flag) {
+            smali.push_str(name);
+            smali.push(' ');
+        }
+    }
+    smali.push_str(method.name());
+    smali.push_str(&method.prototype().descriptor());
+    smali.push('\n');
+
+    if let Some(code) = method.code() {
+        let (labels, lines) = render_instructions(dex, code)?;
+        let mut body = format!("    .registers {}\n", code.registers_size());
+        for (addr, line) in &lines {
+            body.push('\n');
+            if labels.contains_key(addr) {
+                let _ = writeln!(body, "    {}", label(*addr));
+            }
+            for line in line.lines() {
+                let _ = writeln!(body, "    {}", line);
+            }
+        }
+        smali.push_str(&body);
+    }
+    smali.push_str(".end method\n");
+    Ok(smali)
+}
+
+/// Labelled addresses of a code, see `labels`.
+type Labels = BTreeMap<usize, Option<usize>>;
+
+/// Renders each instruction of `code` along with its address, see `Renderer`, and
+/// returns the labels of the code, see `labels`. The lines of a payload are indented
+/// below its directive.
+fn render_instructions<S: AsRef<[u8]>>(
+    dex: &Dex<S>,
+    code: &CodeItem,
+) -> Result<(Labels, Vec<(usize, String)>)> {
+    let instructions = code.instructions().collect::<Result<Vec<_>>>()?;
+    let labels = labels(&instructions);
+    let lines = instructions
+        .iter()
+        .map(|(addr, instruction)| {
+            let mnemonic = disass::mnemonic(usize::from(code.insns()[*addr] & 0xff));
+            let line = Renderer {
+                dex,
+                addr: *addr,
+                switch_addr: labels.get(addr).cloned().flatten(),
+                mnemonic,
+            }
+            .render(instruction)?;
+            Ok((*addr, line))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((labels, lines))
+}
+
+/// Renders the instructions of `code`, defined in `dex`, one smali line per
+/// instruction along with its address, see `Method::disassemble_with_lines`.
+pub(crate) fn instruction_lines<S: AsRef<[u8]>>(
+    dex: &Dex<S>,
+    code: &CodeItem,
+) -> Result<Vec<(usize, String)>> {
+    Ok(render_instructions(dex, code)?.1)
+}
+
+/// Addresses of the instructions targeted by a branch or referenced as a payload,
+/// along with the address of the switch instruction referencing a switch payload.
+fn labels(instructions: &[(usize, Instruction)]) -> Labels {
+    let mut labels = BTreeMap::new();
+    for (addr, instruction) in instructions {
+        let target = |offset: i32| (*addr as i64 + i64::from(offset)) as usize;
+        match instruction {
+            Instruction::Goto { offset }
+            | Instruction::If { offset, .. }
+            | Instruction::IfZero { offset, .. } => {
+                labels.entry(target(*offset)).or_insert(None);
+            }
+            Instruction::FillArrayData { payload_offset, .. } => {
+                labels.entry(target(*payload_offset)).or_insert(None);
+            }
+            Instruction::PackedSwitch { payload_offset, .. }
+            | Instruction::SparseSwitch { payload_offset, .. } => {
+                labels.insert(target(*payload_offset), Some(*addr));
+            }
+            _ => {}
+        }
+    }
+    for (addr, instruction) in instructions {
+        let targets = match instruction {
+            Instruction::PackedSwitchPayload { targets, .. }
+            | Instruction::SparseSwitchPayload { targets, .. } => targets,
+            _ => continue,
+        };
+        if let Some(Some(switch_addr)) = labels.get(addr).cloned() {
+            for target in targets {
+                let target = (switch_addr as i64 + i64::from(*target)) as usize;
+                labels.entry(target).or_insert(None);
+            }
+        }
+    }
+    labels
+}
+
+fn label(addr: usize) -> String {
+    format!(":addr_{:x}", addr)
+}
+
+fn hex(value: i64) -> String {
+    if value < 0 {
+        format!("-0x{:x}", value.unsigned_abs())
+    } else {
+        format!("0x{:x}", value)
+    }
+}
+
+fn registers(args: &[u16], range: bool) -> String {
+    match (range, args.first(), args.last()) {
+        (true, Some(first), Some(last)) => format!("{{v{} .. v{}}}", first, last),
+        _ => {
+            let args = args
+                .iter()
+                .map(|arg| format!("v{}", arg))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", args.join(", "))
+        }
+    }
+}
+
+/// Quotes and escapes a string as a smali string literal.
+fn quote(string: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ' '..='~' => quoted.push(c),
+            _ => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    let _ = write!(quoted, "\\u{:04x}", unit);
+                }
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Renders a single instruction, with the references resolved in `dex`.
+struct Renderer<'a, S> {
+    dex: &'a Dex<S>,
+    addr: usize,
+    /// Address of the switch instruction, when rendering its payload.
+    switch_addr: Option<usize>,
+    mnemonic: &'static str,
+}
+
+impl<S: AsRef<[u8]>> Renderer<'_, S> {
+    fn target(&self, offset: i32) -> String {
+        label((self.addr as i64 + i64::from(offset)) as usize)
+    }
+
+    fn string(&self, string_id: u32) -> Result<String> {
+        Ok(quote(&self.dex.get_string(string_id)?.to_string()))
+    }
+
+    fn jtype(&self, type_id: u32) -> Result<String> {
+        Ok(self.dex.get_type(type_id)?.type_descriptor().to_string())
+    }
+
+    fn field(&self, field_id: FieldId) -> Result<String> {
+        Ok(self.dex.resolve_field(field_id)?.to_string())
+    }
+
+    fn method(&self, method_id: MethodId) -> Result<String> {
+        Ok(self.dex.resolve_method(method_id)?.to_string())
+    }
+
+    fn proto(&self, proto_id: ProtoId) -> Result<String> {
+        Ok(self.dex.get_prototype(proto_id)?.descriptor())
+    }
+
+    fn render(&self, instruction: &Instruction) -> Result<String> {
+        let range = self.mnemonic.ends_with("/range");
+        let operands = match instruction {
+            Instruction::Nop | Instruction::ReturnVoid => String::new(),
+            Instruction::Move { dst, src, .. }
+            | Instruction::ArrayLength {
+                dst, array: src, ..
+            }
+            | Instruction::Unary { dst, src, .. } => format!("v{}, v{}", dst, src),
+            Instruction::MoveResult { dst: reg, .. }
+            | Instruction::MoveException { dst: reg }
+            | Instruction::Return { src: reg, .. }
+            | Instruction::MonitorEnter { reg }
+            | Instruction::MonitorExit { reg }
+            | Instruction::Throw { reg } => format!("v{}", reg),
+            Instruction::Const { dst, value } => format!("v{}, {}", dst, hex(i64::from(*value))),
+            Instruction::ConstWide { dst, value } => format!("v{}, {}L", dst, hex(*value)),
+            Instruction::ConstString { reg, string_idx } => {
+                format!("v{}, {}", reg, self.string(*string_idx)?)
+            }
+            Instruction::ConstClass { reg, type_idx }
+            | Instruction::CheckCast { reg, type_idx }
+            | Instruction::NewInstance {
+                dst: reg, type_idx, ..
+            } => format!("v{}, {}", reg, self.jtype(*type_idx)?),
+            Instruction::InstanceOf { dst, src, type_idx }
+            | Instruction::NewArray {
+                dst,
+                size: src,
+                type_idx,
+            } => format!("v{}, v{}, {}", dst, src, self.jtype(*type_idx)?),
+            Instruction::FilledNewArray { type_idx, args } => {
+                format!("{}, {}", registers(args, range), self.jtype(*type_idx)?)
+            }
+            Instruction::FillArrayData {
+                array: reg,
+                payload_offset,
+            }
+            | Instruction::PackedSwitch {
+                reg,
+                payload_offset,
+            }
+            | Instruction::SparseSwitch {
+                reg,
+                payload_offset,
+            } => format!("v{}, {}", reg, self.target(*payload_offset)),
+            Instruction::Goto { offset } => self.target(*offset),
+            Instruction::Cmp { dst, a, b, .. } => format!("v{}, v{}, v{}", dst, a, b),
+            Instruction::If { a, b, offset, .. } => {
+                format!("v{}, v{}, {}", a, b, self.target(*offset))
+            }
+            Instruction::IfZero { reg, offset, .. } => {
+                format!("v{}, {}", reg, self.target(*offset))
+            }
+            Instruction::ArrayGet {
+                dst: reg,
+                array,
+                index,
+                ..
+            }
+            | Instruction::ArrayPut {
+                src: reg,
+                array,
+                index,
+                ..
+            } => format!("v{}, v{}, v{}", reg, array, index),
+            Instruction::InstanceGet {
+                dst: reg,
+                object,
+                field_idx,
+                ..
+            }
+            | Instruction::InstancePut {
+                src: reg,
+                object,
+                field_idx,
+                ..
+            } => format!("v{}, v{}, {}", reg, object, self.field(*field_idx)?),
+            Instruction::StaticGet {
+                dst: reg,
+                field_idx,
+                ..
+            }
+            | Instruction::StaticPut {
+                src: reg,
+                field_idx,
+                ..
+            } => format!("v{}, {}", reg, self.field(*field_idx)?),
+            Instruction::Invoke {
+                method_idx, args, ..
+            } => format!("{}, {}", registers(args, range), self.method(*method_idx)?),
+            Instruction::InvokePolymorphic {
+                method_idx,
+                proto_idx,
+                args,
+            } => format!(
+                "{}, {}, {}",
+                registers(args, range),
+                self.method(*method_idx)?,
+                self.proto(*proto_idx)?
+            ),
+            Instruction::InvokeCustom {
+                call_site_idx,
+                args,
+            } => format!("{}, call_site_{}", registers(args, range), call_site_idx),
+            Instruction::Binary { dst, a, b, .. } => {
+                if self.mnemonic.ends_with("/2addr") {
+                    format!("v{}, v{}", dst, b)
+                } else {
+                    format!("v{}, v{}, v{}", dst, a, b)
+                }
+            }
+            Instruction::BinaryLit {
+                dst, src, literal, ..
+            } => format!("v{}, v{}, {}", dst, src, hex(i64::from(*literal))),
+            Instruction::ConstMethodHandle {
+                reg,
+                method_handle_idx,
+            } => format!("v{}, method_handle_{}", reg, method_handle_idx),
+            Instruction::ConstMethodType { reg, proto_idx } => {
+                format!("v{}, {}", reg, self.proto(*proto_idx)?)
+            }
+            Instruction::PackedSwitchPayload { first_key, targets } => {
+                let mut payload = format!(".packed-switch {}\n", hex(i64::from(*first_key)));
+                for target in targets {
+                    let _ = writeln!(payload, "    {}", self.switch_target(*target));
+                }
+                payload.push_str(".end packed-switch");
+                return Ok(payload);
+            }
+            Instruction::SparseSwitchPayload { keys, targets } => {
+                let mut payload = String::from(".sparse-switch\n");
+                for (key, target) in keys.iter().zip(targets) {
+                    let _ = writeln!(
+                        payload,
+                        "    {} -> {}",
+                        hex(i64::from(*key)),
+                        self.switch_target(*target)
+                    );
+                }
+                payload.push_str(".end sparse-switch");
+                return Ok(payload);
+            }
+            Instruction::FillArrayDataPayload {
+                element_width,
+                data,
+                ..
+            } => {
+                let mut payload = format!(".array-data {}\n", element_width);
+                for element in data.chunks(usize::from(*element_width).max(1)) {
+                    let value = element
+                        .iter()
+                        .rev()
+                        .fold(0u64, |value, byte| value << 8 | u64::from(*byte));
+                    let _ = writeln!(payload, "    0x{:x}", value);
+                }
+                payload.push_str(".end array-data");
+                return Ok(payload);
+            }
+        };
+        let mnemonic = self.mnemonic.replace('_', "-");
+        if operands.is_empty() {
+            Ok(mnemonic)
+        } else {
+            Ok(format!("{} {}", mnemonic, operands))
+        }
+    }
+
+    /// Label of a target of a switch payload, relative to the switch instruction.
+    fn switch_target(&self, offset: i32) -> String {
+        match self.switch_addr {
+            Some(addr) => label((addr as i64 + i64::from(offset)) as usize),
+            None => format!("{:+}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hex, quote};
+    #[cfg(feature = "std")]
+    use crate::{class::Class, method::Method, DexReader};
+
+    #[cfg(feature = "std")]
+    fn find_method<'a>(class: &'a Class, name: &str) -> &'a Method {
+        class
+            .methods()
+            .find(|method| method.name() == name)
+            .expect("method not found")
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_to_smali() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/SimpleRemoteViews;")
+            .expect("error finding class")
+            .expect("class not found");
+        let smali = find_method(&class, "setImageViewBitmap")
+            .to_smali(&dex)
+            .expect("cannot render method");
+        let expected = std::fs::read_to_string("resources/setImageViewBitmap.smali")
+            .expect("cannot read golden file");
+        assert_eq!(smali, expected);
+
+        let class = dex
+            .find_class_by_name("Lmobi/intuitit/android/widget/SimpleRemoteViews$ReflectionAction;")
+            .expect("error finding class")
+            .expect("class not found");
+        let smali = find_method(&class, "getParameterType")
+            .to_smali(&dex)
+            .expect("cannot render method");
+        assert!(smali.starts_with(".method private getParameterType()Ljava/lang/Class;\n"));
+        assert!(smali.contains("    packed-switch v0, :addr_2e\n"));
+        assert!(smali.contains("    :addr_2e\n    .packed-switch 0x1\n        :addr_7\n"));
+        assert!(smali.contains(
+            "    :addr_7\n    sget-object v0, Ljava/lang/Boolean;->TYPE:Ljava/lang/Class;\n"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_to_smali_without_code() {
+        let dex = DexReader::from_file("resources/classes.dex").expect("cannot open dex file");
+        let method = dex
+            .classes()
+            .filter_map(|class| class.ok())
+            .find_map(|class| {
+                class
+                    .methods()
+                    .find(|method| method.is_abstract())
+                    .map(|method| method.to_smali(&dex).expect("cannot render method"))
+            })
+            .expect("no abstract method");
+        assert!(method.starts_with(".method public abstract "));
+        assert_eq!(method.lines().nth(1), Some(".end method"));
+        assert_eq!(method.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(hex(26), "0x1a");
+        assert_eq!(hex(-1), "-0x1");
+        assert_eq!(hex(i64::MIN), "-0x8000000000000000");
+        assert_eq!(quote("a\"b\\\n\té"), "\"a\\\"b\\\\\\n\\t\\u00e9\"");
+    }
+}
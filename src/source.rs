@@ -1,12 +1,19 @@
-use std::{clone::Clone, convert::AsRef, ops::Index, rc::Rc};
+use alloc::rc::Rc;
+use core::{
+    clone::Clone,
+    convert::AsRef,
+    ops::{Index, Range},
+};
 
-use crate::ubyte;
+use crate::{error::Error, ubyte, ulong, Result};
 
 /// Represents the source `Dex` file. This is a
 /// wrapper type that allows for shallow copies
 /// of the dex file's source.
 pub(crate) struct Source<T> {
     inner: Rc<T>,
+    /// Range of `inner` visible through this source.
+    range: Range<usize>,
 }
 
 impl<T> Source<T>
@@ -14,8 +21,26 @@ where
     T: AsRef<[u8]>,
 {
     pub(crate) fn new(inner: T) -> Self {
+        let len = inner.as_ref().len();
         Self {
             inner: Rc::new(inner),
+            range: 0..len,
+        }
+    }
+
+    /// Returns a view of the `len` bytes starting at `offset` which shares
+    /// the underlying buffer. Offsets into the returned source start at `0`. Returns
+    /// `Error::OutOfBounds` if the view ends past the end of the source.
+    pub(crate) fn subsource(&self, offset: usize, len: usize) -> Result<Self> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.range.len() => Ok(Self {
+                inner: self.inner.clone(),
+                range: self.range.start + offset..self.range.start + end,
+            }),
+            _ => Err(Error::OutOfBounds {
+                offset: (offset as ulong).saturating_add(len as ulong),
+                file_size: self.range.len(),
+            }),
         }
     }
 }
@@ -31,24 +56,24 @@ where
     }
 }
 
-impl<T> Index<std::ops::Range<usize>> for Source<T>
+impl<T> Index<core::ops::Range<usize>> for Source<T>
 where
     T: AsRef<[u8]>,
 {
     type Output = [ubyte];
 
-    fn index(&self, index: std::ops::Range<usize>) -> &Self::Output {
+    fn index(&self, index: core::ops::Range<usize>) -> &Self::Output {
         &self.as_ref()[index]
     }
 }
 
-impl<T> Index<std::ops::RangeFrom<usize>> for Source<T>
+impl<T> Index<core::ops::RangeFrom<usize>> for Source<T>
 where
     T: AsRef<[u8]>,
 {
     type Output = [ubyte];
 
-    fn index(&self, index: std::ops::RangeFrom<usize>) -> &Self::Output {
+    fn index(&self, index: core::ops::RangeFrom<usize>) -> &Self::Output {
         &self.as_ref()[index]
     }
 }
@@ -57,12 +82,42 @@ impl<T> Clone for Source<T> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            range: self.range.clone(),
         }
     }
 }
 
 impl<T: AsRef<[u8]>> AsRef<[u8]> for Source<T> {
     fn as_ref(&self) -> &[ubyte] {
-        self.inner.as_ref().as_ref()
+        &self.inner.as_ref().as_ref()[self.range.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scroll::Pread;
+
+    use super::Source;
+    use crate::error::Error;
+
+    #[test]
+    fn test_subsource() {
+        let source = Source::new(vec![0u8, 1, 2, 3, 4, 5]);
+        let subsource = source.subsource(2, 3).expect("subsource out of bounds");
+        assert_eq!(subsource.as_ref(), &[2, 3, 4]);
+        assert_eq!(subsource[0], 2);
+        assert_eq!(subsource.pread::<u8>(2).expect("cannot read"), 4);
+        assert!(subsource.pread::<u8>(3).is_err());
+
+        let nested = subsource.subsource(1, 2).expect("subsource out of bounds");
+        assert_eq!(nested.as_ref(), &[3, 4]);
+        assert!(matches!(
+            subsource.subsource(1, 3).err(),
+            Some(Error::OutOfBounds {
+                offset: 4,
+                file_size: 3
+            })
+        ));
+        assert!(subsource.subsource(usize::MAX, 2).is_err());
     }
 }
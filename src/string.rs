@@ -1,15 +1,21 @@
 //! Dex String utilities
-use std::{
+use alloc::{borrow::Cow, rc::Rc, string::String, vec::Vec};
+use core::{
     convert::AsRef,
     fmt,
     ops::{Deref, Range},
+    str,
 };
 
-use cesu8::{from_java_cesu8, to_java_cesu8};
 use scroll::{self, ctx, Pread, Uleb128};
 
-use crate::{cache::Cache, error, error::Error, source::Source, uint, Result};
-use std::rc::Rc;
+use crate::{
+    cache::{Cache, CacheStats},
+    error,
+    error::Error,
+    source::Source,
+    uint, HashMap, Result,
+};
 
 /// Index into the `StringId`s section.
 pub type StringId = uint;
@@ -73,17 +79,118 @@ impl<'a> ctx::TryFromCtx<'a, scroll::Endian> for DexString {
         let size = *offset + bytes.len();
         Ok((
             DexString {
-                string: Rc::new(
-                    from_java_cesu8(bytes)
-                        .map_err(|e| Error::MalFormed(format!("Malformed string: {:?}", e)))?
-                        .into_owned(),
-                ),
+                string: Rc::new(decode_mutf8(bytes)?.into_owned()),
             },
             size,
         ))
     }
 }
 
+/// Returns the Shannon entropy of the characters of `string`, in bits per character.
+pub fn shannon_entropy(string: &str) -> f64 {
+    let mut counts = HashMap::new();
+    for c in string.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+    let len = string.chars().count() as f64;
+    counts
+        .values()
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Decodes the MUTF-8 encoded `bytes`, without their terminating NUL. Only code points
+/// which MUTF-8 encodes differently than UTF-8 require a copy: NUL, encoded on two
+/// bytes, and the code points above U+FFFF, encoded as a surrogate pair of 3 bytes each.
+pub(crate) fn decode_mutf8(bytes: &[u8]) -> Result<Cow<'_, str>> {
+    let malformed = |offset: usize| {
+        Error::MalFormed(format!(
+            "Malformed string: invalid MUTF-8 sequence at byte {}",
+            offset
+        ))
+    };
+    // valid UTF-8 without 4 bytes sequences doesn't use any of these encodings.
+    if let Ok(string) = str::from_utf8(bytes) {
+        match bytes.iter().position(|b| *b >= 0xf0) {
+            Some(offset) => return Err(malformed(offset)),
+            None => return Ok(Cow::Borrowed(string)),
+        }
+    }
+    let mut code_units = Vec::with_capacity(bytes.len());
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let continuation = |index: usize| match bytes.get(offset + index) {
+            Some(b) if b & 0xc0 == 0x80 => Ok(u16::from(b & 0x3f)),
+            _ => Err(malformed(offset)),
+        };
+        let b = u16::from(bytes[offset]);
+        let (code_unit, len) = match bytes[offset] {
+            0x00..=0x7f => (b, 1),
+            0xc0..=0xdf => ((b & 0x1f) << 6 | continuation(1)?, 2),
+            0xe0..=0xef => (
+                (b & 0x0f) << 12 | continuation(1)? << 6 | continuation(2)?,
+                3,
+            ),
+            _ => return Err(malformed(offset)),
+        };
+        code_units.push(code_unit);
+        offset += len;
+    }
+    char::decode_utf16(code_units)
+        .collect::<core::result::Result<String, _>>()
+        .map(Cow::Owned)
+        .map_err(|err| {
+            Error::MalFormed(format!(
+                "Malformed string: unpaired surrogate {:#x}",
+                err.unpaired_surrogate()
+            ))
+        })
+}
+
+/// Encodes `string` in MUTF-8, the reverse of `decode_mutf8`, without a terminating NUL.
+pub(crate) fn encode_mutf8(string: &str) -> Cow<'_, [u8]> {
+    if !string.chars().any(|c| c == '\0' || c > '\u{ffff}') {
+        return Cow::Borrowed(string.as_bytes());
+    }
+    let mut bytes = Vec::with_capacity(string.len() + 2);
+    for c in string.chars() {
+        match c {
+            '\0' => bytes.extend_from_slice(&[0xc0, 0x80]),
+            c if c > '\u{ffff}' => {
+                for code_unit in c.encode_utf16(&mut [0; 2]).iter() {
+                    bytes.extend_from_slice(&[
+                        0xe0 | (code_unit >> 12) as u8,
+                        0x80 | (code_unit >> 6 & 0x3f) as u8,
+                        0x80 | (code_unit & 0x3f) as u8,
+                    ]);
+                }
+            }
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Cow::Owned(bytes)
+}
+
+/// Overwrites every character of the MUTF-8 encoded `data` with a placeholder of
+/// the same encoded length, so that neither the size of the data nor the number
+/// of UTF-16 code units of the string change.
+pub(crate) fn redact_mutf8(data: &mut [u8]) {
+    let mut offset = 0;
+    while offset < data.len() {
+        let placeholder: &[u8] = match data[offset] {
+            b if b & 0x80 == 0 => "*".as_bytes(),
+            b if b & 0xe0 == 0xc0 => "¤".as_bytes(),
+            _ => "■".as_bytes(),
+        };
+        let len = placeholder.len().min(data.len() - offset);
+        data[offset..offset + len].copy_from_slice(&placeholder[..len]);
+        offset += len;
+    }
+}
+
 /// To prevent encoding/decoding Java strings to Rust strings
 /// every time, we cache the strings in memory. This also potentially
 /// reduces I/O because strings are used in a lot of places.
@@ -121,48 +228,66 @@ where
         }
     }
 
-    fn parse(&self, id: StringId) -> Result<DexString> {
-        let source = &self.source;
+    /// Offset of the string data of the string at `id`.
+    pub(crate) fn data_offset(&self, id: StringId) -> Result<uint> {
         let offset = self.offset as usize + id as usize * 4;
-        let string_data_off: uint = source.pread_with(offset, self.endian)?;
+        let string_data_off: uint = self.source.pread_with(offset, self.endian)?;
         if !self.data_section.contains(&string_data_off) {
             return Err(error::Error::BadOffset(
                 string_data_off as usize,
                 format!("string_data_off not in data section for StringId: {}", id),
             ));
         }
-        source.pread(string_data_off as usize)
+        Ok(string_data_off)
+    }
+
+    fn parse(&self, id: StringId) -> Result<DexString> {
+        self.source.pread(self.data_offset(id)? as usize)
     }
 
     /// Get the string at `id` updating the cache with the new item
     pub(crate) fn get(&self, id: StringId) -> Result<DexString> {
         if id >= self.len {
-            return Err(Error::InvalidId(format!("Invalid string id: {}", id)));
+            return Err(Error::InvalidStringIndex {
+                index: id,
+                max: self.len,
+            });
         }
         if let Some(string) = self.cache.get(&id) {
             Ok(string)
         } else {
-            self.cache.put(id, self.parse(id)?);
-            Ok(self.cache.get(&id).unwrap())
+            let string = self.parse(id)?;
+            self.cache.put(id, string.clone());
+            Ok(string)
         }
     }
 
+    /// Hit and miss counts of the string cache.
+    pub(crate) fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
     pub(crate) fn get_id(&self, string: &str) -> Result<Option<StringId>> {
         use crate::search::Section;
-        let java_string = to_java_cesu8(string);
+        let java_string = encode_mutf8(string);
         let (offset, len) = (self.offset as usize, self.len as usize);
-        let string_section = &self.source[offset..offset + len * std::mem::size_of::<StringId>()];
-        let section = Section::new(string_section);
+        let string_section = self
+            .source
+            .subsource(offset, len * core::mem::size_of::<StringId>())?;
+        let section = Section::new(string_section.as_ref());
         let source = self.source.clone();
         let index = section.binary_search(
             &java_string,
             self.endian,
-            move |data_offset: &uint, element: &std::borrow::Cow<[u8]>| {
+            move |data_offset: &uint, element: &alloc::borrow::Cow<[u8]>| {
                 let mut data_offset = *data_offset as usize;
                 let _ = Uleb128::read(source.as_ref(), &mut data_offset)
                     .map_err(crate::error::Error::from)?;
-                let value = &source[data_offset..data_offset + element.len()];
-                Ok((**element).cmp(value))
+                // compare the whole string, not just its first `element.len()` bytes,
+                // otherwise a string would match any string it is a prefix of.
+                let data = &source[data_offset..];
+                let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                Ok((**element).cmp(&data[..end]))
             },
         )?;
         Ok(index.map(|i| i as StringId))
@@ -218,6 +343,7 @@ impl<T: AsRef<[u8]>> Iterator for StringsIter<T> {
 #[cfg(test)]
 mod tests {
     #[test]
+    #[cfg(feature = "std")]
     fn test_get_string() {
         let dex = crate::DexReader::from_file("resources/classes.dex").expect("failed to open dex");
         let value = dex.strings.get_id("Lorg/adw/launcher/Launcher;");
@@ -232,4 +358,96 @@ mod tests {
             "Lorg/adw/launcher/Launcher;"
         );
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_strings_iter() {
+        use scroll::Pwrite;
+
+        let dex = crate::DexReader::from_file("resources/classes.dex").expect("failed to open dex");
+        let strings = dex.strings().collect::<crate::Result<Vec<_>>>();
+        let strings = strings.expect("error reading strings");
+        assert_eq!(strings.len(), dex.header().string_ids_size() as usize);
+        assert!(strings.windows(2).all(|pair| pair[0] != pair[1]));
+
+        // A malformed string doesn't end the iteration.
+        let (_file, patched) = crate::dex::tests::patched_dex(|contents| {
+            let offset = dex.header().string_ids_off() as usize + 4;
+            contents
+                .pwrite_with(u32::MAX, offset, scroll::LE)
+                .expect("cannot patch string id");
+        });
+        let patched = patched.strings().collect::<Vec<_>>();
+        assert_eq!(patched.len(), strings.len());
+        assert!(patched[1].is_err());
+        assert!(patched
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .all(|(i, string)| string.as_ref().ok() == Some(&strings[i])));
+    }
+
+    #[test]
+    fn test_mutf8() {
+        use super::DexString;
+        use scroll::Pread;
+
+        let parse = |bytes: &[u8]| {
+            bytes
+                .pread_with::<DexString>(0, scroll::LE)
+                .expect("bad string")
+                .to_string()
+        };
+        // NUL is encoded on two bytes so that the string data stays NUL terminated.
+        assert_eq!(parse(&[3, b'a', 0xc0, 0x80, b'b', 0]), "a\0b");
+        // code points above U+FFFF are encoded as a surrogate pair of 3 bytes each.
+        assert_eq!(
+            parse(&[2, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, 0]),
+            "\u{1f600}"
+        );
+        assert_eq!(parse(&[2, 0xc3, 0xa9, b'e', 0]), "\u{e9}e");
+
+        for string in &["a\0b", "\u{1f600} smile", "plain"] {
+            let mut bytes = vec![string.encode_utf16().count() as u8];
+            bytes.extend_from_slice(&super::encode_mutf8(string));
+            bytes.push(0);
+            assert!(!bytes[1..bytes.len() - 1].contains(&0));
+            assert_eq!(parse(&bytes), *string);
+        }
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        use super::shannon_entropy;
+        assert_eq!(shannon_entropy("aaaa"), 0.0);
+        assert_eq!(shannon_entropy("abab"), 1.0);
+        assert_eq!(shannon_entropy("abcd"), 2.0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_string_data_offset() {
+        use scroll::{Pread, Uleb128};
+
+        let dex = crate::DexReader::from_file("resources/classes.dex").expect("failed to open dex");
+        let string_id = dex
+            .strings
+            .get_id("Lorg/adw/launcher/Launcher;")
+            .expect("error searching string")
+            .expect("string not found");
+        let offset = &mut (dex
+            .string_data_offset(string_id)
+            .expect("error reading string data offset") as usize);
+        let utf16_size = Uleb128::read(dex.source.as_ref(), offset).expect("bad utf16 size");
+        assert_eq!(utf16_size, 27);
+        let data: &[u8] = dex
+            .source
+            .as_ref()
+            .pread_with(*offset, 27)
+            .expect("bad string data");
+        assert_eq!(data, b"Lorg/adw/launcher/Launcher;");
+        assert!(dex
+            .string_data_offset(dex.header().string_ids_size())
+            .is_err());
+    }
 }
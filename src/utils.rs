@@ -1,17 +1,26 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use scroll::Pwrite;
+use sha1::{Digest, Sha1};
+
 use crate::{
     annotation::AnnotationSetItem,
     encoded_item::{EncodedItem, EncodedItemArray},
     encoded_value::EncodedValue,
     error::Error,
-    jtype::{Type, TypeId},
-    ushort,
+    uint, ulong, Endian,
 };
 
 macro_rules! try_gread_vec_with {
     ($source:ident,$offset:ident,$cap:expr,$ctx:expr) => {{
         let cap = $cap as usize;
         let ctx = $ctx;
-        let mut vec = Vec::with_capacity(cap);
+        // the count comes from the file: every item takes at least a byte, so don't
+        // preallocate more than the bytes left.
+        let mut vec = Vec::with_capacity(cap.min($source.len().saturating_sub(*$offset)));
         // NOTE: gread_inout_with doesn't work when de-serializing encoded array
         // so using an explicit loop here.
         for _ in 0..cap {
@@ -32,6 +41,24 @@ macro_rules! encoded_array {
     };
 }
 
+/// Returns `Error::OutOfBounds` unless the `count` items of `item_size` bytes starting
+/// at `offset` fit in the file of `file_size` bytes.
+pub(crate) fn check_list_bounds(
+    offset: usize,
+    count: uint,
+    item_size: usize,
+    file_size: usize,
+) -> super::Result<()> {
+    let end = offset as ulong + ulong::from(count) * item_size as ulong;
+    if end > file_size as ulong {
+        return Err(Error::OutOfBounds {
+            offset: end,
+            file_size,
+        });
+    }
+    Ok(())
+}
+
 pub(crate) fn from_item<T, F, U>(
     array: Option<EncodedItemArray<T>>,
     f: F,
@@ -53,14 +80,14 @@ macro_rules! try_from_item {
     }};
 }
 
-pub(crate) fn get_types<S>(dex: &super::Dex<S>, type_ids: &[ushort]) -> super::Result<Vec<Type>>
-where
-    S: AsRef<[u8]>,
-{
-    type_ids
-        .iter()
-        .map(|type_id| dex.get_type(TypeId::from(*type_id)))
-        .collect()
+/// Recomputes the SHA-1 signature and the adler32 checksum in the header of the
+/// dex file `contents`.
+pub(crate) fn update_checksums(contents: &mut [u8], endian: Endian) -> super::Result<()> {
+    let signature = Sha1::digest(&contents[32..]);
+    contents[12..32].copy_from_slice(&signature);
+    let checksum = adler32::RollingAdler32::from_buffer(&contents[12..]).hash();
+    contents.pwrite_with(checksum, 8, endian)?;
+    Ok(())
 }
 
 macro_rules! gen_is_flag_set {
@@ -74,8 +101,7 @@ macro_rules! gen_is_flag_set {
 
 pub(crate) fn get_signature(annotations: &AnnotationSetItem) -> super::Result<Option<String>> {
     annotations
-        .iter()
-        .find(|item| item.jtype() == "Ldalvik/annotation/Signature;")
+        .find_annotation("Ldalvik/annotation/Signature;")
         .map(|item| {
             let element = item.annotation().find_element("value");
             if element.is_none() {